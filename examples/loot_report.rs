@@ -0,0 +1,110 @@
+//! Rolls every loot table many times and prints how the rewards are distributed, to balance
+//! `assets/data/base.loot.ron`.
+//!
+//! `cargo run --example loot_report -- [path to the tables] [seed] [luck]`
+
+use std::collections::BTreeMap;
+
+use insectivore::loot::table::LootTables;
+use rand::{rngs::StdRng, SeedableRng};
+
+const ROLLS: u32 = 100_000;
+const FLOORS: [u32; 3] = [1, 5, 10];
+
+fn main() {
+    let mut args = std::env::args().skip(1);
+    let path = args
+        .next()
+        .unwrap_or_else(|| "assets/data/base.loot.ron".to_string());
+    let seed = args.next().and_then(|seed| seed.parse().ok()).unwrap_or(42);
+    let luck = args.next().and_then(|luck| luck.parse().ok()).unwrap_or(0.);
+
+    let source = std::fs::read_to_string(&path).expect("could not read the loot tables");
+    let tables: LootTables = ron::from_str(&source).expect("invalid loot tables");
+    for missing in tables.missing_tables() {
+        println!("warning: table `{missing}` is referenced but not defined");
+    }
+
+    let mut names: Vec<&String> = tables.tables.keys().collect();
+    names.sort();
+    for name in names {
+        for floor in FLOORS {
+            report(&tables, name, floor, seed, luck);
+        }
+    }
+}
+
+fn percent(count: u32) -> f64 {
+    count as f64 * 100. / ROLLS as f64
+}
+
+fn report(tables: &LootTables, name: &str, floor: u32, seed: u64, luck: f32) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut gold = 0u64;
+    let mut drops = 0u32;
+    let mut empty = 0u32;
+    let mut notable = 0u32;
+    let mut rarities = vec![0u32; tables.rarities.len()];
+    let mut items: BTreeMap<String, u32> = BTreeMap::new();
+    let mut affixes: BTreeMap<String, (u32, i64)> = BTreeMap::new();
+
+    for _ in 0..ROLLS {
+        let loot = tables.roll(name, floor, luck, &mut rng);
+        gold += loot.gold as u64;
+        drops += loot.drops.len() as u32;
+        if loot.drops.is_empty() {
+            empty += 1;
+        }
+        if tables.is_notable(&loot) {
+            notable += 1;
+        }
+        for drop in &loot.drops {
+            if let Some(rarity) = drop.rarity {
+                rarities[rarity] += 1;
+            }
+            *items.entry(drop.item.id.clone()).or_default() += 1;
+            for affix in drop.item.affixes() {
+                let bonus = affix.bonus;
+                let total = bonus.attack
+                    + bonus.defense
+                    + bonus.magic
+                    + bonus.speed
+                    + bonus.max_hp
+                    + bonus.max_mp;
+                let entry = affixes.entry(affix.name.clone()).or_default();
+                entry.0 += 1;
+                entry.1 += total as i64;
+            }
+        }
+    }
+
+    println!("== {name}, floor {floor} ({ROLLS} rolls, seed {seed}, luck {luck})");
+    println!(
+        "   gold {:.2} / roll, {:.3} items / roll, nothing {:.2}%, notable {:.3}%",
+        gold as f64 / ROLLS as f64,
+        drops as f64 / ROLLS as f64,
+        percent(empty),
+        percent(notable),
+    );
+    let gear: u32 = rarities.iter().sum();
+    if gear > 0 {
+        for (rarity, count) in tables.rarities.iter().zip(&rarities) {
+            println!(
+                "   {:>10} {:>7.3}% of gear",
+                rarity.name,
+                *count as f64 * 100. / gear as f64
+            );
+        }
+    }
+    for (item, count) in &items {
+        println!("   {item:>16} {:>8.3}%", percent(*count));
+    }
+    for (affix, (count, total)) in &affixes {
+        println!(
+            "   {affix:>16} {:>8.3}%, average bonus {:.2}",
+            percent(*count),
+            *total as f64 / *count as f64
+        );
+    }
+    println!();
+}
@@ -1,4 +1,4 @@
-use crate::{loading::AudioAssets, GameState};
+use crate::{loading::AudioAssets, settings::Settings, GameState};
 use bevy::prelude::*;
 use bevy_kira_audio::prelude::*;
 
@@ -8,19 +8,43 @@ pub struct InternalAudioPlugin;
 impl Plugin for InternalAudioPlugin {
     fn build(&self, app: &mut App) {
         app.add_plugins(AudioPlugin)
-            .add_systems(OnExit(GameState::Loading), start_audio);
+            .add_audio_channel::<Music>()
+            .add_audio_channel::<Sfx>()
+            .add_systems(OnExit(GameState::Loading), start_audio)
+            .add_systems(Update, apply_volume.run_if(resource_changed::<Settings>()));
     }
 }
 
+/// Channel of the background music.
+#[derive(Resource)]
+pub struct Music;
+
+/// Channel of the sound effects.
+#[derive(Resource)]
+pub struct Sfx;
+
 #[derive(Resource)]
 struct FlyingAudio(Handle<AudioInstance>);
 
-fn start_audio(mut commands: Commands, audio_assets: Res<AudioAssets>, audio: Res<Audio>) {
-    audio.pause();
-    let handle = audio
+fn start_audio(
+    mut commands: Commands,
+    audio_assets: Res<AudioAssets>,
+    music: Res<AudioChannel<Music>>,
+) {
+    music.pause();
+    let handle = music
         .play(audio_assets.flying.clone())
         .looped()
         .with_volume(0.3)
         .handle();
     commands.insert_resource(FlyingAudio(handle));
 }
+
+fn apply_volume(
+    settings: Res<Settings>,
+    music: Res<AudioChannel<Music>>,
+    sfx: Res<AudioChannel<Sfx>>,
+) {
+    music.set_volume((settings.master_volume * settings.music_volume) as f64);
+    sfx.set_volume((settings.master_volume * settings.sfx_volume) as f64);
+}
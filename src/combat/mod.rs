@@ -0,0 +1,252 @@
+mod monster;
+pub mod sim;
+
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    dungeon::{
+        camera3d::{CameraDirection, GridPosition, Player},
+        labyrinth::Labyrinth,
+    },
+    idle::auto_playing,
+    inventory::item::Item,
+    party::Party,
+    GameState, PlayingState,
+};
+
+pub use self::monster::{DefeatedMonsters, Monster, MonsterPlugin};
+
+use self::{
+    monster::Disengaged,
+    sim::{Action, Encounter, Outcome, Side, TurnReport},
+};
+
+pub struct CombatPlugin;
+
+/// This plugin starts an encounter whenever the player runs into a monster and resolves it turn
+/// by turn during `PlayingState::Combat`. The rules themselves live in [`sim`] and do not depend
+/// on the ECS.
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(MonsterPlugin)
+            .init_resource::<CombatLog>()
+            .add_event::<ExperienceGained>()
+            .add_event::<MonsterDefeated>()
+            .add_event::<LootDropped>()
+            .add_systems(
+                Update,
+                start_encounter
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(in_state(PlayingState::Exploring)),
+            )
+            .add_systems(
+                Update,
+                (take_turn.run_if(not(auto_playing)), end_encounter)
+                    .chain()
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(in_state(PlayingState::Combat)),
+            )
+            .add_systems(Update, collect_rewards)
+            .add_systems(OnExit(GameState::Playing), abandon_encounter);
+    }
+}
+
+/// What happened during the last fights, most recent line last.
+#[derive(Resource, Default)]
+pub struct CombatLog {
+    lines: VecDeque<String>,
+}
+
+impl CombatLog {
+    const CAPACITY: usize = 100;
+
+    pub fn push(&mut self, line: impl Into<String>) {
+        let line = line.into();
+        info!("{}", line);
+        if self.lines.len() == Self::CAPACITY {
+            self.lines.pop_front();
+        }
+        self.lines.push_back(line);
+    }
+
+    pub fn push_turn(&mut self, encounter: &Encounter, report: &TurnReport) {
+        self.push(format!(
+            "{}: {}",
+            encounter.round(),
+            encounter.describe(report)
+        ));
+    }
+
+    /// The `count` most recent lines, oldest first.
+    pub fn last(&self, count: usize) -> impl Iterator<Item = &String> {
+        self.lines
+            .iter()
+            .skip(self.lines.len().saturating_sub(count))
+    }
+}
+
+/// The fight in progress, along with the monster entities taking part in it.
+#[derive(Resource)]
+pub struct ActiveEncounter {
+    pub encounter: Encounter,
+    pub monsters: Vec<Entity>,
+}
+
+#[derive(Event)]
+pub struct ExperienceGained(pub u32);
+
+/// Sent for each monster killed, its loot is rolled from its loot table.
+#[derive(Event)]
+pub struct MonsterDefeated(pub Monster);
+
+#[derive(Event)]
+pub struct LootDropped {
+    pub gold: u32,
+    pub items: Vec<Item>,
+    /// Something worth stopping auto-play for
+    pub rare: bool,
+}
+
+/// Engages every monster standing on the player's cell or right in front of them.
+fn start_encounter(
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<PlayingState>>,
+    mut log: ResMut<CombatLog>,
+    labyrinth: Res<Labyrinth>,
+    party: Res<Party>,
+    player: Query<(&GridPosition, &CameraDirection), With<Player>>,
+    monsters: Query<(Entity, &GridPosition, &Monster, Option<&Disengaged>)>,
+) {
+    let Ok((position, direction)) = player.get_single() else {
+        return;
+    };
+
+    let mut engaged = Vec::new();
+    let mut enemies = Vec::new();
+    for (entity, monster_position, monster, disengaged) in monsters.iter() {
+        if let Some(Disengaged(from)) = disengaged {
+            if from == position {
+                continue;
+            }
+            commands.entity(entity).remove::<Disengaged>();
+        }
+        if monster_position == position || position.faces(monster_position, *direction, &labyrinth)
+        {
+            engaged.push(entity);
+            enemies.push(monster.combatant());
+        }
+    }
+    if engaged.is_empty() {
+        return;
+    }
+
+    let names: Vec<String> = enemies.iter().map(|enemy| enemy.name.clone()).collect();
+    log.push(format!("{} attack!", names.join(", ")));
+    commands.insert_resource(ActiveEncounter {
+        encounter: Encounter::new(party.combatants(), enemies, rand::random()),
+        monsters: engaged,
+    });
+    next_state.set(PlayingState::Combat);
+}
+
+/// Plays one turn: the party waits for 1 (attack), 2 (defend), 3 (skill) or 4 (flee), monsters
+/// act on their own.
+pub fn take_turn(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut combat: ResMut<ActiveEncounter>,
+    mut log: ResMut<CombatLog>,
+) {
+    let encounter = &mut combat.encounter;
+    let Some(actor) = encounter.current() else {
+        return;
+    };
+
+    let action = if encounter.combatants()[actor].side == Side::Party {
+        let Some(target) = encounter.weakest_opponent(Side::Party) else {
+            return;
+        };
+        if keyboard_input.just_pressed(KeyCode::Key1) {
+            Action::Attack(target)
+        } else if keyboard_input.just_pressed(KeyCode::Key2) {
+            Action::Defend
+        } else if keyboard_input.just_pressed(KeyCode::Key3) {
+            Action::Skill(target)
+        } else if keyboard_input.just_pressed(KeyCode::Key4) {
+            Action::Flee
+        } else {
+            return;
+        }
+    } else {
+        encounter.auto_action(actor)
+    };
+
+    let report = encounter.act(action);
+    log.push_turn(encounter, &report);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn end_encounter(
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<PlayingState>>,
+    mut next_game_state: ResMut<NextState<GameState>>,
+    mut experience: EventWriter<ExperienceGained>,
+    mut defeated: EventWriter<MonsterDefeated>,
+    mut party: ResMut<Party>,
+    mut log: ResMut<CombatLog>,
+    mut slain: ResMut<DefeatedMonsters>,
+    combat: Res<ActiveEncounter>,
+    player: Query<&GridPosition, With<Player>>,
+    monsters: Query<(&Monster, &GridPosition)>,
+) {
+    let Some(outcome) = combat.encounter.outcome() else {
+        return;
+    };
+
+    // The party always comes first in the encounter
+    for (member, combatant) in party.members.iter_mut().zip(combat.encounter.combatants()) {
+        member.hp = combatant.stats.hp;
+        member.mp = combatant.stats.mp;
+    }
+
+    match outcome {
+        Outcome::Victory => {
+            log.push("Victory!");
+            for &entity in &combat.monsters {
+                if let Ok((monster, position)) = monsters.get(entity) {
+                    experience.send(ExperienceGained(monster.experience));
+                    defeated.send(MonsterDefeated(monster.clone()));
+                    slain.0.insert((position.0, position.1));
+                }
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+        Outcome::Fled => {
+            log.push("The party got away");
+            if let Ok(position) = player.get_single() {
+                for &entity in &combat.monsters {
+                    commands.entity(entity).insert(Disengaged(*position));
+                }
+            }
+        }
+        Outcome::Defeat => {
+            log.push("The party was defeated...");
+            next_game_state.set(GameState::GameOver);
+        }
+    }
+
+    commands.remove_resource::<ActiveEncounter>();
+    next_state.set(PlayingState::Exploring);
+}
+
+/// Leaving the game in the middle of a fight drops it, its monsters are despawned anyway.
+fn abandon_encounter(mut commands: Commands) {
+    commands.remove_resource::<ActiveEncounter>();
+}
+
+fn collect_rewards(mut party: ResMut<Party>, mut loot: EventReader<LootDropped>) {
+    for drop in loot.read() {
+        party.gold += drop.gold;
+    }
+}
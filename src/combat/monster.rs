@@ -0,0 +1,273 @@
+use std::collections::BTreeSet;
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use super::sim::{Combatant, Side, Stats};
+use crate::{
+    dungeon::{
+        camera3d::GridPosition,
+        camera3d::DUNGEON_CAMERA_LAYER,
+        cell_center,
+        config::DungeonConfig,
+        labyrinth::{CurrentFloor, FloorChanged, Labyrinth},
+    },
+    meta::run::Run,
+    save::{click_load_button, SaveLoaded},
+    GameState, PlayingEntity,
+};
+
+pub struct MonsterPlugin;
+
+impl Plugin for MonsterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DefeatedMonsters>()
+            .add_systems(OnEnter(GameState::Playing), spawn_monsters)
+            .add_systems(
+                Update,
+                repopulate
+                    .after(click_load_button)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[derive(Component, Clone)]
+pub struct Monster {
+    pub name: &'static str,
+    pub stats: Stats,
+    pub experience: u32,
+    /// Gold always dropped, on top of the loot table
+    pub gold: u32,
+    /// Name of the loot table rolled when the monster is defeated
+    pub loot: &'static str,
+    pub boss: bool,
+}
+
+impl Monster {
+    pub fn beetle() -> Self {
+        Monster {
+            name: "Giant Beetle",
+            stats: Stats {
+                max_hp: 18,
+                hp: 18,
+                attack: 6,
+                defense: 5,
+                speed: 3,
+                ..default()
+            },
+            experience: 12,
+            gold: 5,
+            loot: "beetle",
+            boss: false,
+        }
+    }
+
+    pub fn mantis() -> Self {
+        Monster {
+            name: "Mantis",
+            stats: Stats {
+                max_hp: 12,
+                hp: 12,
+                attack: 7,
+                defense: 2,
+                speed: 9,
+                ..default()
+            },
+            experience: 10,
+            gold: 3,
+            loot: "mantis",
+            boss: false,
+        }
+    }
+
+    pub fn mantis_queen() -> Self {
+        Monster {
+            name: "Mantis Queen",
+            stats: Stats {
+                max_hp: 45,
+                hp: 45,
+                max_mp: 15,
+                mp: 15,
+                attack: 9,
+                defense: 6,
+                magic: 10,
+                speed: 7,
+            },
+            experience: 60,
+            gold: 40,
+            loot: "mantis_queen",
+            boss: true,
+        }
+    }
+
+    /// Stronger version of the monster for deeper floors, floor 1 being the base one.
+    pub fn scaled(self, floor: u32) -> Self {
+        self.multiplied(1. + 0.2 * floor.saturating_sub(1) as f32)
+    }
+
+    /// The monster with its stats, experience and gold multiplied by `factor`.
+    pub fn multiplied(mut self, factor: f32) -> Self {
+        let scale = |value: i32| (value as f32 * factor).round() as i32;
+        let stats = &mut self.stats;
+        stats.max_hp = scale(stats.max_hp);
+        stats.hp = stats.max_hp;
+        stats.attack = scale(stats.attack);
+        stats.defense = scale(stats.defense);
+        stats.magic = scale(stats.magic);
+        self.experience = scale(self.experience as i32) as u32;
+        self.gold = scale(self.gold as i32) as u32;
+        self
+    }
+
+    pub fn combatant(&self) -> Combatant {
+        Combatant::new(self.name, Side::Enemy, self.stats)
+    }
+}
+
+/// Cells of the monsters already defeated on the current floor, so that they stay dead when a
+/// save is loaded. Monsters never leave the cell they are placed on.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct DefeatedMonsters(pub BTreeSet<(i32, i32)>);
+
+/// Set on a monster the party fled from, so it does not engage again until the player moves.
+#[derive(Component)]
+pub struct Disengaged(pub GridPosition);
+
+/// Mesh and material shared by every [`Monster`].
+#[derive(Resource)]
+struct MonsterAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn spawn_monster(
+    commands: &mut Commands,
+    assets: &MonsterAssets,
+    config: &DungeonConfig,
+    monster: Monster,
+    cell: (i32, i32),
+) {
+    // Rest the body on the floor, which sits half a cell below the eye level.
+    let translation = cell_center(config.size, cell) - Vec3::Y * config.size * 0.3;
+    commands.spawn((
+        PbrBundle {
+            mesh: assets.mesh.clone(),
+            material: assets.material.clone(),
+            transform: Transform::from_translation(translation),
+            ..default()
+        },
+        GridPosition(cell.0, cell.1),
+        Name::new(monster.name),
+        monster,
+        DUNGEON_CAMERA_LAYER,
+        PlayingEntity,
+    ));
+}
+
+pub fn spawn_monsters(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<DungeonConfig>,
+    labyrinth: Res<Labyrinth>,
+    floor: Res<CurrentFloor>,
+    run: Res<Run>,
+    defeated: Res<DefeatedMonsters>,
+) {
+    let assets = MonsterAssets {
+        mesh: meshes.add(Mesh::from(shape::Cube {
+            size: config.size * 0.4,
+        })),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.35, 0.55, 0.2),
+            perceptual_roughness: 0.6,
+            ..default()
+        }),
+    };
+    populate(
+        &mut commands,
+        &assets,
+        &config,
+        &labyrinth,
+        floor.0,
+        &run,
+        &defeated,
+    );
+    commands.insert_resource(assets);
+}
+
+/// Replaces the monsters of the previous floor with the ones of the new floor, or of the floor of
+/// a save loaded from the pause menu. None of a new floor has been defeated yet.
+#[allow(clippy::too_many_arguments)]
+fn repopulate(
+    mut commands: Commands,
+    mut floor_changes: EventReader<FloorChanged>,
+    mut loads: EventReader<SaveLoaded>,
+    assets: Option<Res<MonsterAssets>>,
+    config: Res<DungeonConfig>,
+    labyrinth: Res<Labyrinth>,
+    floor: Res<CurrentFloor>,
+    run: Res<Run>,
+    mut defeated: ResMut<DefeatedMonsters>,
+    monsters: Query<Entity, With<Monster>>,
+) {
+    let floor_changed = floor_changes.read().count() > 0;
+    let loaded = loads.read().count() > 0;
+    let Some(assets) = assets.filter(|_| floor_changed || loaded) else {
+        return;
+    };
+    // A loaded save brings the monsters defeated on its floor along
+    if floor_changed && !loaded {
+        defeated.0.clear();
+    }
+    for entity in monsters.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    populate(
+        &mut commands,
+        &assets,
+        &config,
+        &labyrinth,
+        floor.0,
+        &run,
+        &defeated,
+    );
+}
+
+/// Scatters the monsters of `floor` over its cells, away from the entrance. They get stronger with
+/// depth and a queen guards every third floor. A run always places them the same way, the
+/// `defeated` ones are left out.
+fn populate(
+    commands: &mut Commands,
+    assets: &MonsterAssets,
+    config: &DungeonConfig,
+    labyrinth: &Labyrinth,
+    floor: u32,
+    run: &Run,
+    defeated: &DefeatedMonsters,
+) {
+    let mut cells: Vec<(i32, i32)> = labyrinth
+        .cells
+        .keys()
+        .copied()
+        .filter(|&cell| cell != labyrinth.entrance)
+        .collect();
+    cells.sort();
+    cells.shuffle(&mut StdRng::seed_from_u64(run.floor_seed(floor)));
+
+    let mut roster = vec![Monster::beetle(), Monster::mantis(), Monster::beetle()];
+    if floor % 3 == 0 {
+        roster.push(Monster::mantis_queen());
+    }
+    for (monster, cell) in roster.into_iter().zip(cells) {
+        if defeated.0.contains(&cell) {
+            continue;
+        }
+        let monster = monster
+            .scaled(floor)
+            .multiplied(run.difficulty.monster_scale());
+        spawn_monster(commands, assets, config, monster, cell);
+    }
+}
@@ -0,0 +1,504 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+/// Mana spent by [`Action::Skill`].
+pub const SKILL_COST: i32 = 5;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Side {
+    Party,
+    Enemy,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    pub max_hp: i32,
+    pub hp: i32,
+    pub max_mp: i32,
+    pub mp: i32,
+    pub attack: i32,
+    pub defense: i32,
+    pub magic: i32,
+    pub speed: i32,
+}
+
+#[derive(Clone, Debug)]
+pub struct Combatant {
+    pub name: String,
+    pub side: Side,
+    pub stats: Stats,
+    pub defending: bool,
+}
+
+impl Combatant {
+    pub fn new(name: impl Into<String>, side: Side, stats: Stats) -> Self {
+        Self {
+            name: name.into(),
+            side,
+            stats,
+            defending: false,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.stats.hp > 0
+    }
+}
+
+/// What a combatant does on its turn. Targets are indices into [`Encounter::combatants`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Attack(usize),
+    Defend,
+    Skill(usize),
+    Flee,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Victory,
+    Defeat,
+    Fled,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ActionResult {
+    Hit {
+        target: usize,
+        damage: i32,
+        critical: bool,
+        defeated: bool,
+    },
+    Missed {
+        target: usize,
+    },
+    Defended,
+    OutOfMana,
+    Fled,
+    FleeFailed,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct TurnReport {
+    pub actor: usize,
+    pub action: Action,
+    pub result: ActionResult,
+}
+
+/// A single fight between the party and a group of enemies.
+///
+/// The encounter owns its own seeded RNG and does not touch the ECS, so the same fight can be
+/// replayed from a seed or run thousands of times headless with [`Encounter::resolve`].
+pub struct Encounter {
+    combatants: Vec<Combatant>,
+    order: Vec<usize>,
+    cursor: usize,
+    round: u32,
+    outcome: Option<Outcome>,
+    rng: StdRng,
+}
+
+impl Encounter {
+    pub fn new(party: Vec<Combatant>, enemies: Vec<Combatant>, seed: u64) -> Self {
+        let mut encounter = Self {
+            combatants: party.into_iter().chain(enemies).collect(),
+            order: Vec::new(),
+            cursor: 0,
+            round: 0,
+            outcome: None,
+            rng: StdRng::seed_from_u64(seed),
+        };
+        encounter.check_outcome();
+        if encounter.outcome.is_none() {
+            encounter.roll_initiative();
+        }
+        encounter
+    }
+
+    pub fn combatants(&self) -> &[Combatant] {
+        &self.combatants
+    }
+
+    pub fn round(&self) -> u32 {
+        self.round
+    }
+
+    pub fn outcome(&self) -> Option<Outcome> {
+        self.outcome
+    }
+
+    /// Index of the combatant whose turn it is, `None` once the fight is over.
+    pub fn current(&self) -> Option<usize> {
+        if self.outcome.is_some() {
+            return None;
+        }
+        self.order.get(self.cursor).copied()
+    }
+
+    /// Resolves `action` for the current combatant and advances to the next turn.
+    ///
+    /// Panics if the encounter is already over.
+    pub fn act(&mut self, action: Action) -> TurnReport {
+        let actor = self.current().expect("the encounter is already over");
+        self.combatants[actor].defending = false;
+
+        let result = match (action, self.opponent(actor, action)) {
+            (Action::Attack(_), Some(target)) => self.attack(actor, target),
+            (Action::Skill(_), Some(target)) => self.skill(actor, target),
+            // An opponent is always left while the fight goes on
+            (Action::Attack(_) | Action::Skill(_), None) => ActionResult::Defended,
+            (Action::Defend, _) => {
+                self.combatants[actor].defending = true;
+                ActionResult::Defended
+            }
+            (Action::Flee, _) => self.flee(actor),
+        };
+
+        if result == ActionResult::Fled {
+            self.outcome = Some(Outcome::Fled);
+        } else {
+            self.check_outcome();
+        }
+        if self.outcome.is_none() {
+            self.advance();
+        }
+
+        TurnReport {
+            actor,
+            action,
+            result,
+        }
+    }
+
+    /// Default tactic: hit the weakest living opponent, with a skill when it is worth the mana.
+    pub fn auto_action(&self, actor: usize) -> Action {
+        let combatant = &self.combatants[actor];
+        let Some(target) = self.weakest_opponent(combatant.side) else {
+            return Action::Defend;
+        };
+        if combatant.stats.mp >= SKILL_COST && combatant.stats.magic > combatant.stats.attack {
+            Action::Skill(target)
+        } else {
+            Action::Attack(target)
+        }
+    }
+
+    /// Plays the whole fight with [`Encounter::auto_action`] on both sides.
+    pub fn resolve(&mut self) -> Outcome {
+        while let Some(actor) = self.current() {
+            let action = self.auto_action(actor);
+            self.act(action);
+        }
+        self.outcome.unwrap()
+    }
+
+    /// The target of an attack or a skill of `actor`. A target that is gone, dead or on the side
+    /// of `actor` is replaced by the weakest living opponent.
+    fn opponent(&self, actor: usize, action: Action) -> Option<usize> {
+        let (Action::Attack(target) | Action::Skill(target)) = action else {
+            return None;
+        };
+        let side = self.combatants[actor].side;
+        match self.combatants.get(target) {
+            Some(combatant) if combatant.side != side && combatant.is_alive() => Some(target),
+            _ => self.weakest_opponent(side),
+        }
+    }
+
+    pub fn weakest_opponent(&self, side: Side) -> Option<usize> {
+        self.combatants
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| c.side != side && c.is_alive())
+            .min_by_key(|(_, c)| c.stats.hp)
+            .map(|(i, _)| i)
+    }
+
+    /// One line of text explaining what happened during a turn.
+    pub fn describe(&self, report: &TurnReport) -> String {
+        let actor = &self.combatants[report.actor].name;
+        match report.result {
+            ActionResult::Hit {
+                target,
+                damage,
+                critical,
+                defeated,
+            } => {
+                let verb = match report.action {
+                    Action::Skill(_) => "blasts",
+                    _ if critical => "critically hits",
+                    _ => "hits",
+                };
+                let target = &self.combatants[target].name;
+                let mut line = format!("{actor} {verb} {target} for {damage} damage");
+                if defeated {
+                    line.push_str(&format!(", {target} is defeated"));
+                }
+                line
+            }
+            ActionResult::Missed { target } => {
+                format!("{actor} misses {}", self.combatants[target].name)
+            }
+            ActionResult::Defended => format!("{actor} defends"),
+            ActionResult::OutOfMana => format!("{actor} is out of mana"),
+            ActionResult::Fled => format!("{actor} flees the fight"),
+            ActionResult::FleeFailed => format!("{actor} fails to flee"),
+        }
+    }
+
+    fn attack(&mut self, actor: usize, target: usize) -> ActionResult {
+        let attacker = self.combatants[actor].stats;
+        let defender = &self.combatants[target];
+        let hit_chance = hit_chance(&attacker, &defender.stats);
+        if !self.rng.gen_bool(hit_chance as f64) {
+            return ActionResult::Missed { target };
+        }
+        let critical = self.rng.gen_bool(CRITICAL_CHANCE);
+        let roll = self.rng.gen_range(0.85..=1.15);
+        let damage = physical_damage(
+            &attacker,
+            &defender.stats,
+            defender.defending,
+            critical,
+            roll,
+        );
+        self.deal(target, damage, critical)
+    }
+
+    fn skill(&mut self, actor: usize, target: usize) -> ActionResult {
+        if self.combatants[actor].stats.mp < SKILL_COST {
+            return ActionResult::OutOfMana;
+        }
+        self.combatants[actor].stats.mp -= SKILL_COST;
+        let caster = self.combatants[actor].stats;
+        let defender = &self.combatants[target];
+        let roll = self.rng.gen_range(0.9..=1.1);
+        let damage = magic_damage(&caster, &defender.stats, defender.defending, roll);
+        self.deal(target, damage, false)
+    }
+
+    fn flee(&mut self, actor: usize) -> ActionResult {
+        let side = self.combatants[actor].side;
+        let ours = self.average_speed(|c| c.side == side);
+        let theirs = self.average_speed(|c| c.side != side);
+        if self.rng.gen_bool(flee_chance(ours, theirs) as f64) {
+            ActionResult::Fled
+        } else {
+            ActionResult::FleeFailed
+        }
+    }
+
+    fn deal(&mut self, target: usize, damage: i32, critical: bool) -> ActionResult {
+        let stats = &mut self.combatants[target].stats;
+        stats.hp = (stats.hp - damage).max(0);
+        ActionResult::Hit {
+            target,
+            damage,
+            critical,
+            defeated: stats.hp == 0,
+        }
+    }
+
+    fn average_speed(&self, filter: impl Fn(&Combatant) -> bool) -> f32 {
+        let speeds: Vec<i32> = self
+            .combatants
+            .iter()
+            .filter(|c| c.is_alive() && filter(c))
+            .map(|c| c.stats.speed)
+            .collect();
+        if speeds.is_empty() {
+            return 0.;
+        }
+        speeds.iter().sum::<i32>() as f32 / speeds.len() as f32
+    }
+
+    fn check_outcome(&mut self) {
+        let alive = |side| {
+            self.combatants
+                .iter()
+                .any(|c| c.side == side && c.is_alive())
+        };
+        if !alive(Side::Enemy) {
+            self.outcome = Some(Outcome::Victory);
+        } else if !alive(Side::Party) {
+            self.outcome = Some(Outcome::Defeat);
+        }
+    }
+
+    fn advance(&mut self) {
+        self.cursor += 1;
+        while let Some(&next) = self.order.get(self.cursor) {
+            if self.combatants[next].is_alive() {
+                return;
+            }
+            self.cursor += 1;
+        }
+        self.roll_initiative();
+    }
+
+    /// Orders the living combatants for a new round: speed plus a random bonus of up to half of it.
+    fn roll_initiative(&mut self) {
+        self.round += 1;
+        self.cursor = 0;
+        let mut rolls: Vec<(usize, i32)> = Vec::new();
+        for (index, combatant) in self.combatants.iter().enumerate() {
+            if combatant.is_alive() {
+                let speed = combatant.stats.speed.max(0);
+                rolls.push((index, speed + self.rng.gen_range(0..=speed / 2)));
+            }
+        }
+        rolls.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        self.order = rolls.into_iter().map(|(index, _)| index).collect();
+    }
+}
+
+const CRITICAL_CHANCE: f64 = 0.05;
+
+pub fn hit_chance(attacker: &Stats, defender: &Stats) -> f32 {
+    (0.9 + (attacker.speed - defender.speed) as f32 * 0.01).clamp(0.6, 0.99)
+}
+
+/// `2 * attack - defense`, scaled by `roll`, halved when defending and boosted by 50% on criticals.
+pub fn physical_damage(
+    attacker: &Stats,
+    defender: &Stats,
+    defending: bool,
+    critical: bool,
+    roll: f32,
+) -> i32 {
+    let mut damage = (attacker.attack * 2 - defender.defense) as f32 * roll;
+    if critical {
+        damage *= 1.5;
+    }
+    if defending {
+        damage *= 0.5;
+    }
+    (damage.round() as i32).max(1)
+}
+
+/// `3 * magic - defense / 2`, scaled by `roll` and halved when defending. Skills never miss.
+pub fn magic_damage(caster: &Stats, defender: &Stats, defending: bool, roll: f32) -> i32 {
+    let mut damage = (caster.magic * 3 - defender.defense / 2) as f32 * roll;
+    if defending {
+        damage *= 0.5;
+    }
+    (damage.round() as i32).max(1)
+}
+
+pub fn flee_chance(our_speed: f32, their_speed: f32) -> f32 {
+    (0.5 + (our_speed - their_speed) * 0.05).clamp(0.1, 0.9)
+}
+
+/// Tally of many fights between the same sides, see [`batch`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct BatchReport {
+    pub victories: u32,
+    pub defeats: u32,
+    /// Rounds played over all the fights
+    pub rounds: u32,
+}
+
+/// Resolves `count` fights between fresh copies of `party` and `enemies`, seeded with `seed`,
+/// `seed + 1` and so on. Used to check the balance headless.
+pub fn batch(party: &[Combatant], enemies: &[Combatant], count: u32, seed: u64) -> BatchReport {
+    let mut report = BatchReport::default();
+    for offset in 0..count {
+        let mut encounter = Encounter::new(party.to_vec(), enemies.to_vec(), seed + offset as u64);
+        match encounter.resolve() {
+            Outcome::Victory => report.victories += 1,
+            Outcome::Defeat => report.defeats += 1,
+            // Auto actions never flee
+            Outcome::Fled => {}
+        }
+        report.rounds += encounter.round();
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(hp: i32, attack: i32, defense: i32, speed: i32) -> Stats {
+        Stats {
+            max_hp: hp,
+            hp,
+            attack,
+            defense,
+            speed,
+            ..Stats::default()
+        }
+    }
+
+    fn party() -> Vec<Combatant> {
+        vec![
+            Combatant::new("Fighter", Side::Party, stats(30, 8, 6, 5)),
+            Combatant::new("Thief", Side::Party, stats(20, 6, 3, 10)),
+            Combatant::new(
+                "Mage",
+                Side::Party,
+                Stats {
+                    max_mp: 20,
+                    mp: 20,
+                    magic: 9,
+                    ..stats(16, 3, 2, 6)
+                },
+            ),
+        ]
+    }
+
+    fn beetle() -> Combatant {
+        Combatant::new("Beetle", Side::Enemy, stats(18, 6, 5, 3))
+    }
+
+    #[test]
+    fn thousands_of_fights_all_end() {
+        let enemies = vec![beetle(), beetle(), beetle()];
+        let report = batch(&party(), &enemies, 5000, 0);
+        assert_eq!(report.victories + report.defeats, 5000);
+        // Minimum damage is 1, nothing drags on forever
+        assert!(report.rounds / 5000 < 50);
+    }
+
+    #[test]
+    fn the_party_beats_a_single_beetle() {
+        let report = batch(&party(), &[beetle()], 2000, 42);
+        assert!(report.victories > 1900, "{report:?}");
+    }
+
+    #[test]
+    fn a_seed_replays_the_same_fight() {
+        let play = |seed| {
+            let mut encounter = Encounter::new(party(), vec![beetle(), beetle()], seed);
+            let outcome = encounter.resolve();
+            let hp: Vec<i32> = encounter.combatants().iter().map(|c| c.stats.hp).collect();
+            (outcome, encounter.round(), hp)
+        };
+        for seed in 0..100 {
+            assert_eq!(play(seed), play(seed));
+        }
+    }
+
+    #[test]
+    fn invalid_targets_fall_on_an_opponent() {
+        let mut encounter = Encounter::new(party(), vec![beetle()], 7);
+        let beetle = encounter.combatants().len() - 1;
+        for _ in 0..20 {
+            let Some(actor) = encounter.current() else {
+                break;
+            };
+            let side = encounter.combatants()[actor].side;
+            // Always aim at oneself or past the end of the list
+            let target = if side == Side::Party { actor } else { 99 };
+            let report = encounter.act(Action::Attack(target));
+            match report.result {
+                ActionResult::Hit { target, .. } | ActionResult::Missed { target } => {
+                    assert_ne!(encounter.combatants()[target].side, side);
+                    if side == Side::Party {
+                        assert_eq!(target, beetle);
+                    }
+                }
+                result => panic!("unexpected {result:?}"),
+            }
+        }
+    }
+}
@@ -4,26 +4,66 @@ use bevy::{
     render::{camera::RenderTarget, view::RenderLayers},
 };
 use leafwing_input_manager::orientation::Direction;
-use std::f32::consts::{FRAC_PI_2, FRAC_PI_4};
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_2, PI};
 
-use super::config::DungeonConfig;
-use crate::{ui::HUDRenderViews, GameState};
+use super::{
+    cell_center,
+    config::DungeonConfig,
+    labyrinth::{Explored, Labyrinth},
+    Position,
+};
+use crate::{
+    message::GameMessage,
+    settings::{Action, Settings},
+    ui::HUDRenderViews,
+    GameState, PlayingEntity, PlayingState,
+};
 
 pub struct Camera3DPlugin;
 
 impl Plugin for Camera3DPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(Update, setup.run_if(resource_added::<HUDRenderViews>()))
-            .add_systems(Update, (handle_input,).run_if(in_state(GameState::Playing)));
+            .add_event::<PlayerMove>()
+            .add_event::<PlayerMoved>()
+            .add_systems(
+                Update,
+                (handle_input, move_player)
+                    .chain()
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(in_state(PlayingState::Exploring)),
+            )
+            .add_systems(
+                Update,
+                (
+                    animate_camera.after(move_player),
+                    apply_fov.run_if(resource_changed::<Settings>()),
+                )
+                    .run_if(in_state(GameState::Playing)),
+            );
     }
 }
 
 #[derive(Component)]
 pub struct DungeonCamera;
 
+/// Where the dungeon camera sees the player from, in world space. It trails behind the player
+/// when they step or turn, which animates the moves while the player itself moves from cell to
+/// cell at once.
+#[derive(Component)]
+struct ViewPose(Transform);
+
+/// Where the dungeon camera sits relative to the player once done moving.
+fn camera_rest() -> Transform {
+    Transform::from_xyz(0., 0., 3.).looking_at(Vec3::ZERO, Vec3::Y)
+}
+
 pub const DUNGEON_CAMERA_LAYER: RenderLayers = RenderLayers::layer(2);
 
-#[derive(Component, Default)]
+#[derive(
+    Component, Reflect, Default, Clone, Copy, PartialEq, Eq, Debug, Serialize, Deserialize,
+)]
 pub enum CameraDirection {
     #[default]
     North,
@@ -33,6 +73,13 @@ pub enum CameraDirection {
 }
 
 impl CameraDirection {
+    pub const ALL: [CameraDirection; 4] = [
+        CameraDirection::North,
+        CameraDirection::East,
+        CameraDirection::South,
+        CameraDirection::West,
+    ];
+
     // TODO: We should probably not use `leafwing_input_manager` here and just get rid of the dependency
     pub fn uvec(&self) -> Vec3 {
         match self {
@@ -42,30 +89,133 @@ impl CameraDirection {
             CameraDirection::West => Direction::WEST.unit_vector().extend(0.).xzy(),
         }
     }
+
+    /// Offset to the neighbouring cell in this direction, as `(x, z)`.
+    pub fn offset(&self) -> (i32, i32) {
+        match self {
+            CameraDirection::North => (0, 1),
+            CameraDirection::East => (1, 0),
+            CameraDirection::South => (0, -1),
+            CameraDirection::West => (-1, 0),
+        }
+    }
+
+    /// The wall of a cell that faces this direction.
+    pub fn wall(&self) -> Position {
+        match self {
+            CameraDirection::North => Position::Center,
+            CameraDirection::East => Position::Right,
+            CameraDirection::South => Position::Back,
+            CameraDirection::West => Position::Left,
+        }
+    }
+
+    /// World-space offset of one step of `size` in this direction.
+    pub fn translation(&self, size: f32) -> Vec3 {
+        match self {
+            CameraDirection::North | CameraDirection::South => -self.uvec() * size,
+            CameraDirection::East | CameraDirection::West => self.uvec() * size,
+        }
+    }
+
+    /// Rotation of the player when looking in this direction, North being the identity.
+    pub fn rotation(&self) -> Quat {
+        match self {
+            CameraDirection::North => Quat::IDENTITY,
+            CameraDirection::West => Quat::from_rotation_y(FRAC_PI_2),
+            CameraDirection::South => Quat::from_rotation_y(PI),
+            CameraDirection::East => Quat::from_rotation_y(-FRAC_PI_2),
+        }
+    }
+
+    pub fn left(&self) -> CameraDirection {
+        match self {
+            CameraDirection::North => CameraDirection::West,
+            CameraDirection::South => CameraDirection::East,
+            CameraDirection::East => CameraDirection::North,
+            CameraDirection::West => CameraDirection::South,
+        }
+    }
+
+    pub fn right(&self) -> CameraDirection {
+        self.left().opposite()
+    }
+
+    pub fn opposite(&self) -> CameraDirection {
+        match self {
+            CameraDirection::North => CameraDirection::South,
+            CameraDirection::East => CameraDirection::West,
+            CameraDirection::South => CameraDirection::North,
+            CameraDirection::West => CameraDirection::East,
+        }
+    }
 }
 
 #[derive(Component)]
 pub struct Player;
 
-pub fn setup(mut commands: Commands, config: Res<HUDRenderViews>) {
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PlayerMove {
+    Forward,
+    Backward,
+    TurnLeft,
+    TurnRight,
+}
+
+/// Sent when the player steps into another cell. Teleports and falls are not steps.
+#[derive(Event, Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PlayerMoved(pub GridPosition);
+
+/// Cell of the labyrinth an entity stands in, as `(x, z)`.
+#[derive(Component, Default, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct GridPosition(pub i32, pub i32);
+
+impl GridPosition {
+    pub fn step(&self, direction: CameraDirection) -> GridPosition {
+        let (dx, dz) = direction.offset();
+        GridPosition(self.0 + dx, self.1 + dz)
+    }
+
+    /// Whether `other` is the next cell in `direction` and no wall stands between them.
+    pub fn faces(
+        &self,
+        other: &GridPosition,
+        direction: CameraDirection,
+        labyrinth: &Labyrinth,
+    ) -> bool {
+        self.step(direction) == *other && labyrinth.can_move((self.0, self.1), direction)
+    }
+}
+
+pub fn setup(
+    mut commands: Commands,
+    config: Res<HUDRenderViews>,
+    dungeon: Res<DungeonConfig>,
+    labyrinth: Res<Labyrinth>,
+    settings: Res<Settings>,
+) {
+    let (x, z) = labyrinth.entrance;
+    let transform = Transform::from_translation(cell_center(dungeon.size, (x, z)));
     commands
         .spawn((
             TransformBundle {
-                local: Transform::from_xyz(0., 0., -1.),
+                local: transform,
                 ..default()
             },
             VisibilityBundle::default(),
             CameraDirection::default(),
+            GridPosition(x, z),
             Player,
             DUNGEON_CAMERA_LAYER,
+            PlayingEntity,
         ))
         .with_children(|builder| {
             builder.spawn((
                 UiCameraConfig { show_ui: false },
                 Camera3dBundle {
-                    transform: Transform::from_xyz(0., 0., 3.).looking_at(Vec3::ZERO, Vec3::Y),
+                    transform: camera_rest(),
                     projection: Projection::Perspective(PerspectiveProjection {
-                        fov: FRAC_PI_4,
+                        fov: settings.fov.to_radians(),
                         ..default()
                     }),
                     camera: Camera {
@@ -80,51 +230,108 @@ pub fn setup(mut commands: Commands, config: Res<HUDRenderViews>) {
                 },
                 DUNGEON_CAMERA_LAYER,
                 DungeonCamera,
+                ViewPose(transform),
             ));
         });
 }
 
 pub fn handle_input(
     keyboard_input: Res<Input<KeyCode>>,
-    mut camera: Query<(&mut Transform, &mut CameraDirection), With<Player>>,
+    settings: Res<Settings>,
+    mut moves: EventWriter<PlayerMove>,
+) {
+    let keys = &settings.keys;
+    if keyboard_input.just_pressed(keys.key(Action::Forward)) {
+        moves.send(PlayerMove::Forward);
+    } else if keyboard_input.just_pressed(keys.key(Action::Backward)) {
+        moves.send(PlayerMove::Backward);
+    } else if keyboard_input.just_pressed(keys.key(Action::TurnLeft)) {
+        moves.send(PlayerMove::TurnLeft);
+    } else if keyboard_input.just_pressed(keys.key(Action::TurnRight)) {
+        moves.send(PlayerMove::TurnRight);
+    }
+}
+
+/// Applies [`PlayerMove`]s to the player, whether they come from the keyboard or from auto-explore.
+/// Steps into a wall are ignored.
+pub fn move_player(
+    mut moves: EventReader<PlayerMove>,
+    mut moved: EventWriter<PlayerMoved>,
+    mut messages: EventWriter<GameMessage>,
+    mut explored: ResMut<Explored>,
+    mut camera: Query<(&mut Transform, &mut CameraDirection, &mut GridPosition), With<Player>>,
+    labyrinth: Res<Labyrinth>,
     config: Res<DungeonConfig>,
 ) {
-    if let Some((mut transform, mut direction)) = camera.iter_mut().next() {
-        if keyboard_input.just_pressed(KeyCode::W) {
-            transform.translation = match *direction {
-                CameraDirection::North | CameraDirection::South => {
-                    transform.translation - direction.uvec() * config.size
-                }
-                CameraDirection::East | CameraDirection::West => {
-                    transform.translation + direction.uvec() * config.size
-                }
-            }
-        } else if keyboard_input.just_pressed(KeyCode::S) {
-            transform.translation = match *direction {
-                CameraDirection::North | CameraDirection::South => {
-                    transform.translation + direction.uvec() * config.size
-                }
-                CameraDirection::East | CameraDirection::West => {
-                    transform.translation - direction.uvec() * config.size
+    let Some((mut transform, mut direction, mut grid)) = camera.iter_mut().next() else {
+        return;
+    };
+    for player_move in moves.read() {
+        match player_move {
+            PlayerMove::Forward | PlayerMove::Backward => {
+                let heading = if *player_move == PlayerMove::Forward {
+                    *direction
+                } else {
+                    direction.opposite()
+                };
+                if !labyrinth.can_move((grid.0, grid.1), heading) {
+                    messages.send(GameMessage::info("The party bumps into a wall"));
+                    continue;
                 }
+                *grid = grid.step(heading);
+                transform.translation += heading.translation(config.size);
+                explored.0.insert(*grid);
+                moved.send(PlayerMoved(*grid));
             }
-        } else if keyboard_input.just_pressed(KeyCode::A) {
-            //transform.translation.x -= config.size;
-            match *direction {
-                CameraDirection::North => *direction = CameraDirection::West,
-                CameraDirection::South => *direction = CameraDirection::East,
-                CameraDirection::East => *direction = CameraDirection::North,
-                CameraDirection::West => *direction = CameraDirection::South,
+            PlayerMove::TurnLeft => {
+                *direction = direction.left();
+                transform.rotate_y(FRAC_PI_2);
             }
-            transform.rotate_y(FRAC_PI_2);
-        } else if keyboard_input.just_pressed(KeyCode::D) {
-            match *direction {
-                CameraDirection::North => *direction = CameraDirection::East,
-                CameraDirection::South => *direction = CameraDirection::West,
-                CameraDirection::East => *direction = CameraDirection::South,
-                CameraDirection::West => *direction = CameraDirection::North,
+            PlayerMove::TurnRight => {
+                *direction = direction.right();
+                transform.rotate_y(-FRAC_PI_2);
             }
-            transform.rotate_y(-FRAC_PI_2);
+        }
+    }
+}
+
+/// Glides the dungeon camera to where the player now stands, at the movement speed setting.
+/// Jumps of more than a cell, like teleporters or a new floor, are not animated.
+fn animate_camera(
+    time: Res<Time>,
+    settings: Res<Settings>,
+    config: Res<DungeonConfig>,
+    player: Query<&Transform, (With<Player>, Without<DungeonCamera>)>,
+    mut cameras: Query<(&mut Transform, &mut ViewPose), With<DungeonCamera>>,
+) {
+    let Ok(player) = player.get_single() else {
+        return;
+    };
+    for (mut transform, mut pose) in cameras.iter_mut() {
+        let jumped = pose.0.translation.distance(player.translation) > config.size * 1.5;
+        if settings.move_speed <= 0. || jumped {
+            pose.0 = *player;
+        } else {
+            let step = 1. - (-settings.move_speed * time.delta_seconds()).exp();
+            pose.0.translation = pose.0.translation.lerp(player.translation, step);
+            pose.0.rotation = pose.0.rotation.slerp(player.rotation, step);
+        }
+        // Back into the space of the player, the camera being its child
+        let local = Transform::from_matrix(
+            player.compute_matrix().inverse()
+                * pose.0.compute_matrix()
+                * camera_rest().compute_matrix(),
+        );
+        if *transform != local {
+            *transform = local;
+        }
+    }
+}
+
+fn apply_fov(settings: Res<Settings>, mut cameras: Query<&mut Projection, With<DungeonCamera>>) {
+    for mut projection in cameras.iter_mut() {
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.fov = settings.fov.to_radians();
         }
     }
 }
@@ -0,0 +1,406 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::{bevy_egui::EguiContexts, egui};
+
+use super::{
+    camera3d::{CameraDirection, GridPosition, Player},
+    cell_center,
+    config::DungeonConfig,
+    feature::{Feature, FeatureKind},
+    format,
+    labyrinth::{Cell, Decoration, Labyrinth, Theme},
+    Position,
+};
+use crate::{
+    settings::{Action, Settings},
+    GameState, PlayingState,
+};
+
+pub struct LabyrinthEditorPlugin;
+
+/// This plugin is an in-game editor for the labyrinth, toggled with F2 while exploring.
+///
+/// The player flies through walls with the movement keys and edits the cell they stand in from
+/// the "Editor" window. Changes show up in the 3D view right away and can be saved to a labyrinth
+/// file.
+impl Plugin for LabyrinthEditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            toggle_editor
+                .run_if(in_state(GameState::Playing))
+                .run_if(in_state(PlayingState::Exploring).or_else(in_state(PlayingState::Editor))),
+        )
+        .add_systems(
+            Update,
+            (fly, editor_window)
+                .run_if(in_state(GameState::Playing))
+                .run_if(in_state(PlayingState::Editor)),
+        )
+        .add_systems(OnExit(PlayingState::Editor), land);
+    }
+}
+
+/// Folder labyrinth files are saved to, relative to the working directory.
+pub const LABYRINTH_FOLDER: &str = "assets/labyrinths";
+/// LDtk project whose definitions exported labyrinths reuse.
+pub const LDTK_TEMPLATE: &str = "assets/insectivore.ldtk";
+
+/// A change made to one cell from the editor.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Edit {
+    AddCell,
+    RemoveCell,
+    /// Puts up or knocks down a wall, on both sides when it is shared with a neighbour
+    Wall(Position, bool),
+    Theme(Theme),
+    Decoration(Option<Decoration>),
+    Feature(Option<Feature>),
+    Entrance,
+}
+
+/// Features that need nothing more than a cell to be placed.
+const PLACEABLE: [FeatureKind; 4] = [
+    FeatureKind::Stairs,
+    FeatureKind::Pit,
+    FeatureKind::Spikes { damage: 3 },
+    FeatureKind::Spinner,
+];
+
+pub fn apply(labyrinth: &mut Labyrinth, coordinates: (i32, i32), edit: Edit) {
+    if edit == Edit::AddCell {
+        labyrinth
+            .cells
+            .entry(coordinates)
+            .or_insert_with(Cell::closed);
+        return;
+    }
+    if edit == Edit::RemoveCell {
+        labyrinth.cells.remove(&coordinates);
+        return;
+    }
+    if let Edit::Wall(position, exists) = edit {
+        match CameraDirection::ALL
+            .into_iter()
+            .find(|direction| direction.wall() == position)
+        {
+            Some(direction) => labyrinth.set_wall(coordinates, direction, exists),
+            // Floors and ceilings belong to the cell alone
+            None => {
+                if let Some(cell) = labyrinth.cells.get_mut(&coordinates) {
+                    for (wall, wall_exists) in cell.walls.iter_mut() {
+                        if *wall == position {
+                            *wall_exists = exists;
+                        }
+                    }
+                }
+            }
+        }
+        return;
+    }
+    if edit == Edit::Entrance {
+        labyrinth.entrance = coordinates;
+        return;
+    }
+
+    let Some(cell) = labyrinth.cells.get_mut(&coordinates) else {
+        return;
+    };
+    match edit {
+        Edit::Theme(theme) => cell.theme = theme,
+        Edit::Decoration(decoration) => cell.decoration = decoration,
+        Edit::Feature(feature) => cell.feature = feature,
+        Edit::AddCell | Edit::RemoveCell | Edit::Wall(..) | Edit::Entrance => {}
+    }
+}
+
+/// Makes sure that `name` is a plain file name, so that nothing is written outside of
+/// [`LABYRINTH_FOLDER`].
+#[cfg(not(target_arch = "wasm32"))]
+fn check_file_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.starts_with('.') || name.contains(['/', '\\', ':']) {
+        return Err(format!("{name:?} is not a valid file name"));
+    }
+    Ok(())
+}
+
+/// Writes the labyrinth to `LABYRINTH_FOLDER/<name>.labyrinth.ron`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn save_labyrinth(labyrinth: &Labyrinth, name: &str) -> Result<String, String> {
+    check_file_name(name)?;
+    let ron = format::to_ron(labyrinth).map_err(|error| error.to_string())?;
+    let path = std::path::Path::new(LABYRINTH_FOLDER).join(format!("{name}.labyrinth.ron"));
+    std::fs::create_dir_all(LABYRINTH_FOLDER).map_err(|error| error.to_string())?;
+    std::fs::write(&path, ron).map_err(|error| error.to_string())?;
+    Ok(path.display().to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn save_labyrinth(_labyrinth: &Labyrinth, _name: &str) -> Result<String, String> {
+    Err("labyrinths can only be saved on desktop".into())
+}
+
+/// Writes the labyrinth to `LABYRINTH_FOLDER/<name>.ldtk`, to be opened in LDtk.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn export_labyrinth(labyrinth: &Labyrinth, name: &str) -> Result<String, String> {
+    check_file_name(name)?;
+    let template = std::fs::read_to_string(LDTK_TEMPLATE).map_err(|error| error.to_string())?;
+    let project = crate::labyrinth::project::to_ldtk(labyrinth, &template)
+        .map_err(|error| error.to_string())?;
+    let path = std::path::Path::new(LABYRINTH_FOLDER).join(format!("{name}.ldtk"));
+    std::fs::create_dir_all(LABYRINTH_FOLDER).map_err(|error| error.to_string())?;
+    std::fs::write(&path, project).map_err(|error| error.to_string())?;
+    Ok(path.display().to_string())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn export_labyrinth(_labyrinth: &Labyrinth, _name: &str) -> Result<String, String> {
+    Err("labyrinths can only be exported on desktop".into())
+}
+
+fn toggle_editor(
+    keyboard_input: Res<Input<KeyCode>>,
+    state: Res<State<PlayingState>>,
+    mut next_state: ResMut<NextState<PlayingState>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::F2) {
+        return;
+    }
+    next_state.set(match state.get() {
+        PlayingState::Editor => PlayingState::Exploring,
+        _ => PlayingState::Editor,
+    });
+}
+
+/// Moves the player one cell at a time, through walls and out of the labyrinth.
+fn fly(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    config: Res<DungeonConfig>,
+    mut player: Query<(&mut Transform, &mut CameraDirection, &mut GridPosition), With<Player>>,
+) {
+    let Ok((mut transform, mut direction, mut position)) = player.get_single_mut() else {
+        return;
+    };
+    let keys = &settings.keys;
+    if keyboard_input.just_pressed(keys.key(Action::Forward)) {
+        *position = position.step(*direction);
+    } else if keyboard_input.just_pressed(keys.key(Action::Backward)) {
+        *position = position.step(direction.opposite());
+    } else if keyboard_input.just_pressed(keys.key(Action::TurnLeft)) {
+        *direction = direction.left();
+    } else if keyboard_input.just_pressed(keys.key(Action::TurnRight)) {
+        *direction = direction.right();
+    } else {
+        return;
+    }
+    transform.translation = cell_center(config.size, (position.0, position.1));
+    transform.rotation = direction.rotation();
+}
+
+/// Puts the player back on solid ground when leaving the editor outside the labyrinth.
+fn land(
+    labyrinth: Res<Labyrinth>,
+    config: Res<DungeonConfig>,
+    mut player: Query<(&mut Transform, &mut GridPosition), With<Player>>,
+) {
+    let Ok((mut transform, mut position)) = player.get_single_mut() else {
+        return;
+    };
+    if !labyrinth.cells.contains_key(&(position.0, position.1)) {
+        let (x, z) = labyrinth.entrance;
+        *position = GridPosition(x, z);
+        transform.translation = cell_center(config.size, (x, z));
+    }
+}
+
+fn editor_window(
+    mut contexts: EguiContexts,
+    mut labyrinth: ResMut<Labyrinth>,
+    mut file_name: Local<String>,
+    mut status: Local<String>,
+    player: Query<(&GridPosition, &CameraDirection), With<Player>>,
+) {
+    let Ok((position, facing)) = player.get_single() else {
+        return;
+    };
+    if file_name.is_empty() {
+        *file_name = "custom".into();
+    }
+    let coordinates = (position.0, position.1);
+    let mut edits = Vec::new();
+    let mut save = false;
+    let mut export = false;
+
+    egui::Window::new("Editor").show(contexts.ctx_mut(), |ui| {
+        ui.label(format!("Cell {coordinates:?}, facing {facing:?}"));
+        ui.label("Movement keys: fly, F2: back to the game");
+        ui.separator();
+
+        match labyrinth.cells.get(&coordinates) {
+            None => {
+                if ui.button("Add cell").clicked() {
+                    edits.push(Edit::AddCell);
+                }
+            }
+            Some(cell) => {
+                ui.horizontal(|ui| {
+                    for (position, exists) in cell.walls {
+                        let mut checked = exists;
+                        let label: &'static str = position.into();
+                        if ui.checkbox(&mut checked, label).changed() {
+                            edits.push(Edit::Wall(position, checked));
+                        }
+                    }
+                });
+
+                egui::ComboBox::from_label("Theme")
+                    .selected_text(format!("{:?}", cell.theme))
+                    .show_ui(ui, |ui| {
+                        for theme in Theme::ALL {
+                            let label = format!("{theme:?}");
+                            if ui.selectable_label(cell.theme == theme, label).clicked() {
+                                edits.push(Edit::Theme(theme));
+                            }
+                        }
+                    });
+
+                egui::ComboBox::from_label("Decoration")
+                    .selected_text(format!("{:?}", cell.decoration))
+                    .show_ui(ui, |ui| {
+                        let choices = std::iter::once(None).chain(Decoration::ALL.map(Some));
+                        for decoration in choices {
+                            let label = format!("{decoration:?}");
+                            if ui
+                                .selectable_label(cell.decoration == decoration, label)
+                                .clicked()
+                            {
+                                edits.push(Edit::Decoration(decoration));
+                            }
+                        }
+                    });
+
+                let current = cell.feature.as_ref().map(|feature| &feature.kind);
+                egui::ComboBox::from_label("Feature")
+                    .selected_text(current.map_or("None", |kind| kind.name()))
+                    .show_ui(ui, |ui| {
+                        if ui.selectable_label(current.is_none(), "None").clicked() {
+                            edits.push(Edit::Feature(None));
+                        }
+                        for kind in PLACEABLE {
+                            let selected = current == Some(&kind);
+                            if ui.selectable_label(selected, kind.name()).clicked() {
+                                edits.push(Edit::Feature(Some(Feature::new(kind))));
+                            }
+                        }
+                    });
+
+                ui.horizontal(|ui| {
+                    let is_entrance = labyrinth.entrance == coordinates;
+                    if ui
+                        .add_enabled(!is_entrance, egui::Button::new("Set entrance"))
+                        .clicked()
+                    {
+                        edits.push(Edit::Entrance);
+                    }
+                    if ui
+                        .add_enabled(!is_entrance, egui::Button::new("Remove cell"))
+                        .clicked()
+                    {
+                        edits.push(Edit::RemoveCell);
+                    }
+                });
+            }
+        }
+
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.text_edit_singleline(&mut *file_name);
+            save = ui.button("Save").clicked();
+            export = ui.button("Export to LDtk").clicked();
+        });
+        if !status.is_empty() {
+            ui.label(status.as_str());
+        }
+    });
+
+    for edit in edits {
+        apply(&mut labyrinth, coordinates, edit);
+    }
+    if save {
+        *status = match save_labyrinth(&labyrinth, &file_name) {
+            Ok(path) => format!("Saved to {path}"),
+            Err(error) => format!("Failed to save: {error}"),
+        };
+        info!("{}", *status);
+    }
+    if export {
+        *status = match export_labyrinth(&labyrinth, &file_name) {
+            Ok(path) => format!("Exported to {path}"),
+            Err(error) => format!("Failed to export: {error}"),
+        };
+        info!("{}", *status);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::format::parse_ascii;
+
+    /// Two open cells side by side.
+    fn rooms() -> Labyrinth {
+        parse_ascii("+--+--+\n|E. ..|\n+--+--+").unwrap()
+    }
+
+    fn has_wall(labyrinth: &Labyrinth, cell: (i32, i32), position: Position) -> bool {
+        labyrinth.cells[&cell]
+            .walls
+            .iter()
+            .any(|(wall, exists)| *wall == position && *exists)
+    }
+
+    #[test]
+    fn walls_are_shared_with_the_neighbour() {
+        let mut labyrinth = rooms();
+        apply(&mut labyrinth, (0, 0), Edit::Wall(Position::Right, true));
+        assert!(has_wall(&labyrinth, (0, 0), Position::Right));
+        assert!(has_wall(&labyrinth, (1, 0), Position::Left));
+        apply(&mut labyrinth, (1, 0), Edit::Wall(Position::Left, false));
+        assert!(labyrinth.can_move((0, 0), CameraDirection::East));
+    }
+
+    #[test]
+    fn floors_belong_to_their_cell() {
+        let mut labyrinth = rooms();
+        apply(&mut labyrinth, (0, 0), Edit::Wall(Position::Floor, false));
+        assert!(!has_wall(&labyrinth, (0, 0), Position::Floor));
+        assert!(has_wall(&labyrinth, (1, 0), Position::Floor));
+    }
+
+    #[test]
+    fn cells_are_added_closed_and_removed() {
+        let mut labyrinth = rooms();
+        apply(&mut labyrinth, (0, 1), Edit::AddCell);
+        assert_eq!(labyrinth.cells[&(0, 1)], Cell::closed());
+        // Adding an existing cell keeps it as it is
+        let before = labyrinth.cells[&(0, 0)].clone();
+        apply(&mut labyrinth, (0, 0), Edit::AddCell);
+        assert_eq!(labyrinth.cells[&(0, 0)], before);
+
+        apply(&mut labyrinth, (1, 0), Edit::RemoveCell);
+        assert!(!labyrinth.cells.contains_key(&(1, 0)));
+        assert!(!labyrinth.can_move((0, 0), CameraDirection::East));
+        // Edits of a missing cell are ignored
+        apply(&mut labyrinth, (1, 0), Edit::Theme(Theme::Moss));
+        assert!(!labyrinth.cells.contains_key(&(1, 0)));
+    }
+
+    #[test]
+    fn file_names_stay_in_the_labyrinth_folder() {
+        assert!(check_file_name("custom").is_ok());
+        assert!(check_file_name("floor 2").is_ok());
+        for name in ["", "../custom", "a/b", "a\\b", ".hidden", "C:custom"] {
+            assert!(check_file_name(name).is_err(), "{name}");
+        }
+    }
+}
@@ -0,0 +1,535 @@
+use bevy::{prelude::*, utils::HashMap};
+use rand::{seq::SliceRandom, Rng};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    camera3d::{
+        move_player, CameraDirection, GridPosition, Player, PlayerMoved, DUNGEON_CAMERA_LAYER,
+    },
+    cell_center,
+    config::DungeonConfig,
+    generate::generate,
+    labyrinth::{CurrentFloor, Explored, FloorChanged, Labyrinth},
+};
+use crate::{
+    message::GameMessage,
+    meta::run::Run,
+    party::{character::Attribute, Party},
+    settings::{Action, Settings},
+    GameState, PlayingEntity, PlayingState,
+};
+
+pub struct FeaturePlugin;
+
+/// This plugin springs the traps and hazards of the cell the player walks into, and lets the
+/// party search around for hidden ones with R. Cells reached otherwise, by a teleporter or a
+/// loaded save, do not trigger.
+impl Plugin for FeaturePlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<Feature>()
+            .add_systems(
+                Update,
+                (trigger_features.after(move_player), search)
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(in_state(PlayingState::Exploring)),
+            )
+            .add_systems(
+                Update,
+                spawn_markers
+                    .run_if(resource_exists_and_changed::<Labyrinth>())
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// Something lying in a cell that reacts when the player enters it.
+#[derive(Reflect, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Feature {
+    pub kind: FeatureKind,
+    /// Hidden features still trigger, but are not shown until found
+    pub hidden: bool,
+    /// Perception needed to find it when hidden, compared to a d20 roll
+    pub difficulty: i32,
+}
+
+impl Feature {
+    pub fn new(kind: FeatureKind) -> Self {
+        Feature {
+            kind,
+            hidden: false,
+            difficulty: 0,
+        }
+    }
+
+    pub fn hidden(kind: FeatureKind, difficulty: i32) -> Self {
+        Feature {
+            kind,
+            hidden: true,
+            difficulty,
+        }
+    }
+}
+
+#[derive(Reflect, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub enum FeatureKind {
+    /// Hurts every member of the party, without ever taking one below 1 HP
+    Spikes {
+        damage: i32,
+    },
+    /// Drops the party to the floor below
+    Pit,
+    /// Leads down to the next floor
+    Stairs,
+    Teleporter {
+        to: (i32, i32),
+    },
+    /// Turns the player around without telling them
+    Spinner,
+    /// Opens the wall on the `side` of the cell `door`
+    PressurePlate {
+        door: (i32, i32),
+        side: CameraDirection,
+    },
+}
+
+/// What a feature does to the player once triggered.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Effect {
+    Damage(i32),
+    /// Moves the party to the next floor, `fell` telling a pit from the stairs
+    Descend {
+        fell: bool,
+    },
+    Teleport(GridPosition),
+    Turn(CameraDirection),
+    OpenDoor {
+        door: (i32, i32),
+        side: CameraDirection,
+    },
+}
+
+impl FeatureKind {
+    pub fn trigger(&self, facing: CameraDirection, rng: &mut impl Rng) -> Effect {
+        match *self {
+            FeatureKind::Spikes { damage } => Effect::Damage(damage),
+            FeatureKind::Pit => Effect::Descend { fell: true },
+            FeatureKind::Stairs => Effect::Descend { fell: false },
+            FeatureKind::Teleporter { to } => Effect::Teleport(GridPosition(to.0, to.1)),
+            FeatureKind::Spinner => {
+                let others = [facing.left(), facing.right(), facing.opposite()];
+                Effect::Turn(*others.choose(rng).unwrap())
+            }
+            FeatureKind::PressurePlate { door, side } => Effect::OpenDoor { door, side },
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            FeatureKind::Spikes { .. } => "spike trap",
+            FeatureKind::Pit => "pit",
+            FeatureKind::Stairs => "stairs",
+            FeatureKind::Teleporter { .. } => "teleporter",
+            FeatureKind::Spinner => "spinner",
+            FeatureKind::PressurePlate { .. } => "pressure plate",
+        }
+    }
+}
+
+/// Rolls a d20 plus `bonus` against `difficulty`.
+pub fn perception_check(bonus: i32, difficulty: i32, rng: &mut impl Rng) -> bool {
+    rng.gen_range(1..=20) + bonus >= difficulty
+}
+
+/// The sharpest eyes of the party: the best DEX and INT among the living members.
+pub fn perception_bonus(party: &Party) -> i32 {
+    party
+        .members
+        .iter()
+        .filter(|member| member.is_alive())
+        .map(|member| {
+            (member.attributes.get(Attribute::Dexterity)
+                + member.attributes.get(Attribute::Intelligence))
+                / 4
+        })
+        .max()
+        .unwrap_or(0)
+}
+
+/// Takes `damage` from every living member, leaving them at least 1 HP.
+pub fn wound(party: &mut Party, damage: i32) {
+    for member in party.members.iter_mut().filter(|member| member.is_alive()) {
+        member.hp = (member.hp - damage).max(1);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn trigger_features(
+    mut moves: EventReader<PlayerMoved>,
+    mut labyrinth: ResMut<Labyrinth>,
+    mut party: ResMut<Party>,
+    mut messages: EventWriter<GameMessage>,
+    mut explored: ResMut<Explored>,
+    mut floor: ResMut<CurrentFloor>,
+    mut floor_changes: EventWriter<FloorChanged>,
+    config: Res<DungeonConfig>,
+    run: Res<Run>,
+    mut player: Query<(&mut Transform, &mut CameraDirection, &mut GridPosition), With<Player>>,
+) {
+    if moves.read().last().is_none() {
+        return;
+    }
+    let Ok((mut transform, mut direction, mut position)) = player.get_single_mut() else {
+        return;
+    };
+    let cell = (position.0, position.1);
+    let Some(feature) = labyrinth.feature(cell).cloned() else {
+        return;
+    };
+
+    let effect = feature.kind.trigger(*direction, &mut rand::thread_rng());
+    if feature.hidden && feature.kind != FeatureKind::Spinner {
+        // Walking into a trap is one way to find it
+        if let Some(feature) = labyrinth
+            .cells
+            .get_mut(&cell)
+            .and_then(|c| c.feature.as_mut())
+        {
+            feature.hidden = false;
+        }
+    }
+
+    match effect {
+        Effect::Damage(damage) => {
+            wound(&mut party, damage);
+            messages.send(
+                GameMessage::danger(format!("A spike trap hurts the party for {damage}")).toast(),
+            );
+        }
+        Effect::Descend { fell } => {
+            floor.0 += 1;
+            let seed = run.floor_seed(floor.0);
+            info!("floor {} seed: {seed}", floor.0);
+            *labyrinth = generate(seed, floor.0);
+            let (x, z) = labyrinth.entrance;
+            *position = GridPosition(x, z);
+            *explored = Explored::entrance_of(&labyrinth);
+            transform.translation = cell_center(config.size, (x, z));
+            messages.send(if fell {
+                GameMessage::warning(format!(
+                    "The floor gives way! The party falls to floor {}",
+                    floor.0
+                ))
+                .toast()
+            } else {
+                GameMessage::info(format!(
+                    "The party takes the stairs down to floor {}",
+                    floor.0
+                ))
+                .toast()
+            });
+            floor_changes.send(FloorChanged(floor.0));
+        }
+        Effect::Teleport(to) => {
+            *position = to;
+            transform.translation = cell_center(config.size, (to.0, to.1));
+            explored.0.insert(to);
+            messages.send(
+                GameMessage::warning("The world blurs and the party stands somewhere else").toast(),
+            );
+        }
+        Effect::Turn(facing) => {
+            *direction = facing;
+            transform.rotation = facing.rotation();
+        }
+        Effect::OpenDoor { door, side } => {
+            if !labyrinth.can_move(door, side) {
+                labyrinth.set_wall(door, side, false);
+                messages.send(GameMessage::info(
+                    "A plate clicks underfoot and a wall slides open in the distance",
+                ));
+            }
+        }
+    }
+}
+
+/// Looks for hidden features in the player's cell and the cells next to it.
+fn search(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    mut labyrinth: ResMut<Labyrinth>,
+    mut messages: EventWriter<GameMessage>,
+    party: Res<Party>,
+    player: Query<&GridPosition, With<Player>>,
+) {
+    if !keyboard_input.just_pressed(settings.keys.key(Action::Search)) {
+        return;
+    }
+    let Ok(position) = player.get_single() else {
+        return;
+    };
+    let bonus = perception_bonus(&party);
+    let mut rng = rand::thread_rng();
+    let nearby = std::iter::once(*position).chain(
+        CameraDirection::ALL
+            .into_iter()
+            .filter(|direction| labyrinth.can_move((position.0, position.1), *direction))
+            .map(|direction| position.step(direction)),
+    );
+    let spotted: Vec<(i32, i32)> = nearby
+        .map(|cell| (cell.0, cell.1))
+        .filter(|&cell| {
+            labyrinth.feature(cell).map_or(false, |feature| {
+                feature.hidden && perception_check(bonus, feature.difficulty, &mut rng)
+            })
+        })
+        .collect();
+
+    let mut found = Vec::new();
+    for cell in spotted {
+        if let Some(feature) = labyrinth
+            .cells
+            .get_mut(&cell)
+            .and_then(|c| c.feature.as_mut())
+        {
+            feature.hidden = false;
+            found.push(feature.kind.name());
+        }
+    }
+
+    if found.is_empty() {
+        messages.send(GameMessage::info(
+            "The party searches around but finds nothing",
+        ));
+    } else {
+        messages.send(GameMessage::success(format!(
+            "The party found: {}",
+            found.join(", ")
+        )));
+    }
+}
+
+#[derive(Component)]
+struct FeatureMarker;
+
+/// Mesh and materials shared by every [`FeatureMarker`], one material per kind of feature.
+#[derive(Default)]
+struct MarkerAssets {
+    mesh: Option<Handle<Mesh>>,
+    materials: HashMap<&'static str, Handle<StandardMaterial>>,
+}
+
+/// Draws a tile on the floor of every feature the party knows about.
+fn spawn_markers(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut assets: Local<MarkerAssets>,
+    config: Res<DungeonConfig>,
+    labyrinth: Res<Labyrinth>,
+    markers: Query<Entity, With<FeatureMarker>>,
+) {
+    for entity in markers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let mesh = assets
+        .mesh
+        .get_or_insert_with(|| {
+            meshes.add(Mesh::from(shape::Plane {
+                size: config.size * 0.6,
+                subdivisions: 0,
+            }))
+        })
+        .clone();
+    for (&cell, feature) in labyrinth
+        .cells
+        .iter()
+        .filter_map(|(coordinates, cell)| Some((coordinates, cell.feature.as_ref()?)))
+        .filter(|(_, feature)| !feature.hidden)
+    {
+        let color = match feature.kind {
+            FeatureKind::Spikes { .. } => Color::rgb(0.6, 0.1, 0.1),
+            FeatureKind::Pit => Color::BLACK,
+            FeatureKind::Stairs => Color::rgb(0.2, 0.5, 0.8),
+            FeatureKind::Teleporter { .. } => Color::rgb(0.4, 0.2, 0.7),
+            FeatureKind::Spinner => Color::rgb(0.7, 0.6, 0.2),
+            FeatureKind::PressurePlate { .. } => Color::rgb(0.4, 0.4, 0.4),
+        };
+        // Just above the floor, which sits half a cell below the eye level
+        let translation = cell_center(config.size, cell) - Vec3::Y * config.size * 0.49;
+        let material = assets
+            .materials
+            .entry(feature.kind.name())
+            .or_insert_with(|| {
+                materials.add(StandardMaterial {
+                    base_color: color,
+                    perceptual_roughness: 0.9,
+                    ..default()
+                })
+            })
+            .clone();
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material,
+                transform: Transform::from_translation(translation),
+                ..default()
+            },
+            FeatureMarker,
+            Name::new(feature.kind.name()),
+            DUNGEON_CAMERA_LAYER,
+            PlayingEntity,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dungeon::{config::ConfigPlugin, format::parse_ascii},
+        party::{character::Attributes, starting_members},
+    };
+
+    /// A corridor of four cells, entered from the west, with features on some of them.
+    fn corridor(features: &[((i32, i32), FeatureKind)]) -> Labyrinth {
+        let mut labyrinth = parse_ascii("+--+--+--+--+\n|E. .. .. ..|\n+--+--+--+--+").unwrap();
+        for (cell, kind) in features {
+            labyrinth.cells.get_mut(cell).unwrap().feature = Some(Feature::new(kind.clone()));
+        }
+        labyrinth
+    }
+
+    fn app(labyrinth: Labyrinth, start: (i32, i32)) -> App {
+        let classes = ron::from_str(include_str!("../../assets/data/party.classes.ron")).unwrap();
+        let items = ron::from_str(include_str!("../../assets/data/base.items.ron")).unwrap();
+        let mut app = App::new();
+        app.add_plugins(ConfigPlugin)
+            .add_event::<PlayerMoved>()
+            .add_event::<GameMessage>()
+            .add_event::<FloorChanged>()
+            .insert_resource(Explored::entrance_of(&labyrinth))
+            .insert_resource(labyrinth)
+            .insert_resource(Party {
+                members: starting_members(&classes, &items, &Attributes::default()),
+                gold: 0,
+            })
+            .init_resource::<CurrentFloor>()
+            .init_resource::<Run>()
+            .add_systems(Update, trigger_features);
+        app.world.spawn((
+            Transform::default(),
+            CameraDirection::East,
+            GridPosition(start.0, start.1),
+            Player,
+        ));
+        app
+    }
+
+    fn position(app: &mut App) -> GridPosition {
+        *app.world
+            .query_filtered::<&GridPosition, With<Player>>()
+            .single(&app.world)
+    }
+
+    /// Moves the player the way `move_player` does.
+    fn step_to(app: &mut App, cell: (i32, i32)) {
+        let position = GridPosition(cell.0, cell.1);
+        *app.world
+            .query_filtered::<&mut GridPosition, With<Player>>()
+            .single_mut(&mut app.world) = position;
+        app.world.send_event(PlayerMoved(position));
+        app.update();
+    }
+
+    fn total_hp(app: &App) -> i32 {
+        app.world
+            .resource::<Party>()
+            .members
+            .iter()
+            .map(|member| member.hp)
+            .sum()
+    }
+
+    #[test]
+    fn standing_on_a_trap_does_not_spring_it() {
+        let mut app = app(
+            corridor(&[((1, 0), FeatureKind::Spikes { damage: 3 })]),
+            (1, 0),
+        );
+        let hp = total_hp(&app);
+        app.update();
+        app.update();
+        assert_eq!(total_hp(&app), hp);
+    }
+
+    #[test]
+    fn walking_into_a_trap_springs_it() {
+        let mut app = app(
+            corridor(&[((1, 0), FeatureKind::Spikes { damage: 3 })]),
+            (0, 0),
+        );
+        let hp = total_hp(&app);
+        step_to(&mut app, (1, 0));
+        assert!(total_hp(&app) < hp);
+        // Only once, until the party steps in again
+        let hurt = total_hp(&app);
+        app.update();
+        assert_eq!(total_hp(&app), hurt);
+    }
+
+    #[test]
+    fn chained_teleporters_do_not_bounce_the_party() {
+        let mut app = app(
+            corridor(&[
+                ((1, 0), FeatureKind::Teleporter { to: (3, 0) }),
+                ((3, 0), FeatureKind::Teleporter { to: (1, 0) }),
+            ]),
+            (0, 0),
+        );
+        step_to(&mut app, (1, 0));
+        assert_eq!(position(&mut app), GridPosition(3, 0));
+        app.update();
+        app.update();
+        assert_eq!(position(&mut app), GridPosition(3, 0));
+    }
+
+    #[test]
+    fn a_pit_drops_the_party_to_the_next_floor() {
+        let mut app = app(corridor(&[((1, 0), FeatureKind::Pit)]), (0, 0));
+        step_to(&mut app, (1, 0));
+        assert_eq!(app.world.resource::<CurrentFloor>().0, 2);
+        let entrance = app.world.resource::<Labyrinth>().entrance;
+        assert_eq!(position(&mut app), GridPosition(entrance.0, entrance.1));
+        let events = app.world.resource::<Events<FloorChanged>>();
+        let changes: Vec<u32> = events.get_reader().read(events).map(|e| e.0).collect();
+        assert_eq!(changes, vec![2]);
+    }
+
+    #[test]
+    fn a_spinner_turns_the_party() {
+        let mut app = app(corridor(&[((1, 0), FeatureKind::Spinner)]), (0, 0));
+        step_to(&mut app, (1, 0));
+        let direction = *app
+            .world
+            .query_filtered::<&CameraDirection, With<Player>>()
+            .single(&app.world);
+        assert_ne!(direction, CameraDirection::East);
+        assert_eq!(position(&mut app), GridPosition(1, 0));
+    }
+
+    #[test]
+    fn a_pressure_plate_opens_its_wall() {
+        let plate = FeatureKind::PressurePlate {
+            door: (2, 0),
+            side: CameraDirection::East,
+        };
+        let mut labyrinth = corridor(&[((1, 0), plate)]);
+        labyrinth.set_wall((2, 0), CameraDirection::East, true);
+        let mut app = app(labyrinth, (0, 0));
+        step_to(&mut app, (1, 0));
+        let labyrinth = app.world.resource::<Labyrinth>();
+        assert!(labyrinth.can_move((2, 0), CameraDirection::East));
+        assert!(labyrinth.can_move((3, 0), CameraDirection::West));
+    }
+}
@@ -0,0 +1,440 @@
+// Human-diffable labyrinth files.
+//
+// The walls are drawn as ASCII art, north up, each cell being two characters wide:
+//
+//     +--+--+--+
+//     |.. .. ..|
+//     +  +--+  +
+//     |..|..|..|
+//     +  +--+  +
+//     |.. E. ..|
+//     +--+--+--+
+//
+// `--` and `|` are walls, `..` a cell, `E.` the entrance and `>.` visible stairs. Blank cells are
+// not part of the labyrinth. Coordinates are counted from the entrance, or from the bottom-left
+// cell when there is none.
+//
+// `.labyrinth` files hold the drawing alone. `.labyrinth.ron` files hold it as a list of lines,
+// along with what a drawing can't show: themes, decorations, traps, items lying around and
+// missing floors or ceilings.
+
+use bevy::{
+    asset::{io::Reader, AssetLoader, AsyncReadExt, LoadContext},
+    utils::{BoxedFuture, HashMap},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    camera3d::CameraDirection,
+    feature::{Feature, FeatureKind},
+    labyrinth::{Cell, Decoration, Labyrinth, Theme},
+    Position,
+};
+use crate::inventory::item::Item;
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(std::io::Error),
+    Ron(ron::error::SpannedError),
+    Write(ron::Error),
+    Json(serde_json::Error),
+    /// The file is not ASCII text
+    Encoding,
+    /// The drawing is not made of `+` corners three characters apart
+    Shape(&'static str),
+    UnexpectedChar {
+        line: usize,
+        column: usize,
+        found: char,
+    },
+    /// A teleporter leads to a cell that is not part of the labyrinth
+    TeleporterOutside {
+        from: (i32, i32),
+        to: (i32, i32),
+    },
+}
+
+impl std::fmt::Display for FormatError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FormatError::Io(error) => write!(f, "could not read the labyrinth: {error}"),
+            FormatError::Ron(error) => write!(f, "invalid labyrinth: {error}"),
+            FormatError::Write(error) => write!(f, "could not write the labyrinth: {error}"),
+            FormatError::Json(error) => write!(f, "invalid LDtk project: {error}"),
+            FormatError::Encoding => write!(f, "labyrinth drawings must be ASCII"),
+            FormatError::Shape(reason) => write!(f, "invalid labyrinth drawing: {reason}"),
+            FormatError::UnexpectedChar {
+                line,
+                column,
+                found,
+            } => write!(f, "unexpected {found:?} at line {line}, column {column}"),
+            FormatError::TeleporterOutside { from, to } => write!(
+                f,
+                "the teleporter at {from:?} leads to {to:?}, outside the labyrinth"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FormatError {}
+
+/// What a `.labyrinth.ron` file holds.
+#[derive(Serialize, Deserialize)]
+struct LabyrinthFile {
+    grid: Vec<String>,
+    #[serde(default)]
+    cells: Vec<((i32, i32), CellMeta)>,
+}
+
+/// Everything about a cell that is not in the drawing.
+#[derive(Serialize, Deserialize, PartialEq)]
+struct CellMeta {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    feature: Option<Feature>,
+    #[serde(default)]
+    theme: Theme,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    decoration: Option<Decoration>,
+    #[serde(default = "yes")]
+    floor: bool,
+    #[serde(default = "yes")]
+    ceiling: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    items: Vec<Item>,
+}
+
+fn yes() -> bool {
+    true
+}
+
+impl Default for CellMeta {
+    fn default() -> Self {
+        CellMeta {
+            feature: None,
+            theme: Theme::default(),
+            decoration: None,
+            floor: true,
+            ceiling: true,
+            items: Vec::new(),
+        }
+    }
+}
+
+fn visible_stairs(cell: &Cell) -> bool {
+    cell.feature.as_ref().map_or(false, |feature| {
+        feature.kind == FeatureKind::Stairs && !feature.hidden
+    })
+}
+
+/// Whether a wall stands between `cell` and its neighbour in `direction`, on either side.
+fn wall_between(labyrinth: &Labyrinth, cell: (i32, i32), direction: CameraDirection) -> bool {
+    let (dx, dz) = direction.offset();
+    let neighbour = (cell.0 + dx, cell.1 + dz);
+    let has_wall = |cell, position| {
+        labyrinth
+            .cells
+            .get(&cell)
+            .map_or(false, |cell: &Cell| cell.has_wall(position))
+    };
+    has_wall(cell, direction.wall()) || has_wall(neighbour, direction.opposite().wall())
+}
+
+/// Draws the walls of the labyrinth, one string per line. A wall on either side of two cells is
+/// drawn as a wall on both.
+pub fn to_ascii(labyrinth: &Labyrinth) -> Vec<String> {
+    let Some((min_x, max_x, min_z, max_z)) = bounds(labyrinth) else {
+        return Vec::new();
+    };
+    let horizontal = |z: i32| {
+        let mut line = String::from("+");
+        for x in min_x..=max_x {
+            let wall = wall_between(labyrinth, (x, z), CameraDirection::North);
+            line.push_str(if wall { "--+" } else { "  +" });
+        }
+        line
+    };
+
+    let mut lines = vec![horizontal(max_z)];
+    for z in (min_z..=max_z).rev() {
+        let mut line = String::new();
+        for x in min_x..=max_x {
+            let west = wall_between(labyrinth, (x, z), CameraDirection::West);
+            line.push(if west { '|' } else { ' ' });
+            line.push_str(match labyrinth.cells.get(&(x, z)) {
+                None => "  ",
+                Some(_) if labyrinth.entrance == (x, z) => "E.",
+                Some(cell) if visible_stairs(cell) => ">.",
+                Some(_) => "..",
+            });
+        }
+        let east = wall_between(labyrinth, (max_x, z), CameraDirection::East);
+        line.push(if east { '|' } else { ' ' });
+        lines.push(line);
+        lines.push(horizontal(z - 1));
+    }
+    lines
+}
+
+fn bounds(labyrinth: &Labyrinth) -> Option<(i32, i32, i32, i32)> {
+    let xs = labyrinth.cells.keys().map(|(x, _)| *x);
+    let zs = labyrinth.cells.keys().map(|(_, z)| *z);
+    Some((xs.clone().min()?, xs.max()?, zs.clone().min()?, zs.max()?))
+}
+
+/// Reads a drawing made by [`to_ascii`].
+pub fn parse_ascii(text: &str) -> Result<Labyrinth, FormatError> {
+    if !text.is_ascii() {
+        return Err(FormatError::Encoding);
+    }
+    let mut lines: Vec<&[u8]> = text
+        .lines()
+        .map(|line| line.trim_end().as_bytes())
+        .collect();
+    while lines.last().map_or(false, |line| line.is_empty()) {
+        lines.pop();
+    }
+    while lines.first().map_or(false, |line| line.is_empty()) {
+        lines.remove(0);
+    }
+    let width = lines.iter().map(|line| line.len()).max().unwrap_or(0);
+    if lines.len() < 3 || lines.len() % 2 == 0 {
+        return Err(FormatError::Shape("expected an odd number of lines"));
+    }
+    if width < 4 || (width - 1) % 3 != 0 {
+        return Err(FormatError::Shape(
+            "lines must be 3 characters per cell, plus 1",
+        ));
+    }
+    // Editors trim trailing spaces, blank ends of lines are open
+    let at = |line: usize, column: usize| lines[line].get(column).copied().unwrap_or(b' ');
+    let unexpected = |line: usize, column: usize| FormatError::UnexpectedChar {
+        line: line + 1,
+        column: column + 1,
+        found: at(line, column) as char,
+    };
+
+    let rows = (lines.len() - 1) / 2;
+    let columns = (width - 1) / 3;
+    let interior = |row: usize, column: usize| {
+        [
+            at(2 * row + 1, 3 * column + 1),
+            at(2 * row + 1, 3 * column + 2),
+        ]
+    };
+    let entrance = (0..rows)
+        .flat_map(|row| (0..columns).map(move |column| (row, column)))
+        .find(|&(row, column)| interior(row, column) == *b"E.");
+    // Rows are drawn north first
+    let (origin_row, origin_column) = entrance.unwrap_or((rows - 1, 0));
+    let coordinates = |row: usize, column: usize| {
+        (
+            column as i32 - origin_column as i32,
+            origin_row as i32 - row as i32,
+        )
+    };
+
+    for line in (0..lines.len()).step_by(2) {
+        for column in 0..=columns {
+            if !matches!(at(line, 3 * column), b'+' | b' ') {
+                return Err(unexpected(line, 3 * column));
+            }
+        }
+    }
+
+    let mut labyrinth = Labyrinth {
+        cells: HashMap::default(),
+        entrance: (0, 0),
+    };
+    for row in 0..rows {
+        for column in 0..columns {
+            let feature = match interior(row, column) {
+                [b' ', b' '] => continue,
+                [b'.', b'.'] | [b'E', b'.'] => None,
+                [b'>', b'.'] => Some(Feature::new(FeatureKind::Stairs)),
+                [b'.' | b'E' | b'>', _] => return Err(unexpected(2 * row + 1, 3 * column + 2)),
+                _ => return Err(unexpected(2 * row + 1, 3 * column + 1)),
+            };
+            let horizontal =
+                |line: usize| match [at(line, 3 * column + 1), at(line, 3 * column + 2)] {
+                    [b'-', b'-'] => Ok(true),
+                    [b' ', b' '] => Ok(false),
+                    [b'-' | b' ', _] => Err(unexpected(line, 3 * column + 2)),
+                    _ => Err(unexpected(line, 3 * column + 1)),
+                };
+            let vertical = |column: usize| match at(2 * row + 1, 3 * column) {
+                b'|' => Ok(true),
+                b' ' => Ok(false),
+                _ => Err(unexpected(2 * row + 1, 3 * column)),
+            };
+            let cell = Cell {
+                walls: [
+                    (Position::Center, horizontal(2 * row)?),
+                    (Position::Left, vertical(column)?),
+                    (Position::Right, vertical(column + 1)?),
+                    (Position::Back, horizontal(2 * row + 2)?),
+                    (Position::Ceiling, true),
+                    (Position::Floor, true),
+                ],
+                feature,
+                ..Cell::closed()
+            };
+            labyrinth.cells.insert(coordinates(row, column), cell);
+        }
+    }
+    Ok(labyrinth)
+}
+
+/// Writes the labyrinth as a `.labyrinth.ron` file, counting cells from its entrance.
+pub fn to_ron(labyrinth: &Labyrinth) -> Result<String, FormatError> {
+    let (ex, ez) = labyrinth.entrance;
+    let mut cells: Vec<((i32, i32), CellMeta)> = labyrinth
+        .cells
+        .iter()
+        .map(|(&(x, z), cell)| {
+            // Stairs are drawn, unless the entrance is drawn over them
+            let drawn = visible_stairs(cell) && labyrinth.entrance != (x, z);
+            let meta = CellMeta {
+                feature: cell.feature.clone().filter(|_| !drawn),
+                theme: cell.theme,
+                decoration: cell.decoration,
+                floor: cell.has_wall(Position::Floor),
+                ceiling: cell.has_wall(Position::Ceiling),
+                items: cell.items.clone(),
+            };
+            ((x - ex, z - ez), meta)
+        })
+        .filter(|(_, meta)| *meta != CellMeta::default())
+        .collect();
+    cells.sort_by_key(|(coordinates, _)| *coordinates);
+
+    let file = LabyrinthFile {
+        grid: to_ascii(labyrinth),
+        cells,
+    };
+    ron::ser::to_string_pretty(&file, ron::ser::PrettyConfig::default()).map_err(FormatError::Write)
+}
+
+/// Reads a `.labyrinth.ron` file.
+pub fn parse_ron(text: &str) -> Result<Labyrinth, FormatError> {
+    let file: LabyrinthFile = ron::from_str(text).map_err(FormatError::Ron)?;
+    let mut labyrinth = parse_ascii(&file.grid.join("\n"))?;
+    for (coordinates, meta) in file.cells {
+        let Some(cell) = labyrinth.cells.get_mut(&coordinates) else {
+            continue;
+        };
+        if meta.feature.is_some() {
+            cell.feature = meta.feature;
+        }
+        cell.theme = meta.theme;
+        cell.decoration = meta.decoration;
+        cell.items = meta.items;
+        for (position, exists) in cell.walls.iter_mut() {
+            match position {
+                Position::Floor => *exists = meta.floor,
+                Position::Ceiling => *exists = meta.ceiling,
+                _ => {}
+            }
+        }
+    }
+    check_teleporters(&labyrinth)?;
+    Ok(labyrinth)
+}
+
+/// Makes sure that every teleporter leads somewhere in the labyrinth, a typo in a floor file
+/// would otherwise send the party into the void.
+pub fn check_teleporters(labyrinth: &Labyrinth) -> Result<(), FormatError> {
+    for (&from, cell) in &labyrinth.cells {
+        if let Some(FeatureKind::Teleporter { to }) = cell.feature.as_ref().map(|f| &f.kind) {
+            if !labyrinth.cells.contains_key(to) {
+                return Err(FormatError::TeleporterOutside { from, to: *to });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Loads `.labyrinth` drawings and `.labyrinth.ron` files as [`Labyrinth`] assets.
+#[derive(Default)]
+pub struct LabyrinthLoader;
+
+impl AssetLoader for LabyrinthLoader {
+    type Asset = Labyrinth;
+    type Settings = ();
+    type Error = FormatError;
+
+    fn load<'a>(
+        &'a self,
+        reader: &'a mut Reader,
+        _settings: &'a (),
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<Labyrinth, FormatError>> {
+        Box::pin(async move {
+            let mut bytes = Vec::new();
+            reader
+                .read_to_end(&mut bytes)
+                .await
+                .map_err(FormatError::Io)?;
+            let text = std::str::from_utf8(&bytes).map_err(|_| FormatError::Encoding)?;
+            let is_ron = load_context
+                .path()
+                .extension()
+                .map_or(false, |extension| extension == "ron");
+            if is_ron {
+                parse_ron(text)
+            } else {
+                parse_ascii(text)
+            }
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["labyrinth", "labyrinth.ron"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::generate::generate;
+
+    const FIRST_FLOOR: &str = include_str!("../../assets/labyrinths/floor1.labyrinth.ron");
+
+    #[test]
+    fn a_floor_file_survives_a_round_trip() {
+        let floor = parse_ron(FIRST_FLOOR).unwrap();
+        let written = to_ron(&floor).unwrap();
+        let reparsed = parse_ron(&written).unwrap();
+        assert_eq!(reparsed, floor);
+        assert_eq!(to_ron(&reparsed).unwrap(), written);
+    }
+
+    #[test]
+    fn generated_floors_survive_a_round_trip() {
+        for seed in 0..10 {
+            let floor = generate(seed, 1 + seed as u32 % 5);
+            let reparsed = parse_ron(&to_ron(&floor).unwrap()).unwrap();
+            assert_eq!(reparsed, floor, "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn teleporters_must_lead_into_the_labyrinth() {
+        let mut floor = parse_ron(FIRST_FLOOR).unwrap();
+        floor.cells.get_mut(&(0, 0)).unwrap().feature =
+            Some(Feature::new(FeatureKind::Teleporter { to: (40, 40) }));
+        assert!(matches!(
+            parse_ron(&to_ron(&floor).unwrap()),
+            Err(FormatError::TeleporterOutside {
+                from: (0, 0),
+                to: (40, 40)
+            })
+        ));
+    }
+
+    #[test]
+    fn a_drawing_survives_a_round_trip() {
+        let drawing = to_ascii(&generate(3, 2));
+        let reparsed = parse_ascii(&drawing.join("\n")).unwrap();
+        assert_eq!(to_ascii(&reparsed), drawing);
+    }
+}
@@ -0,0 +1,147 @@
+// Random floors, generated from a seed so that a floor can be reproduced from the logs.
+
+use bevy::utils::HashMap;
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use super::{
+    camera3d::CameraDirection,
+    feature::{Feature, FeatureKind},
+    labyrinth::{Cell, Labyrinth, Theme},
+};
+
+pub const WIDTH: i32 = 5;
+pub const HEIGHT: i32 = 5;
+
+/// Builds a perfect maze on `0..WIDTH` x `0..HEIGHT`, entered at `(0, 0)`, with stairs down
+/// somewhere and traps laid in it. Deeper floors get more of them, and they get harder to spot.
+pub fn generate(seed: u64, floor: u32) -> Labyrinth {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut labyrinth = Labyrinth {
+        cells: HashMap::default(),
+        entrance: (0, 0),
+    };
+    let theme = Theme::ALL[floor as usize % Theme::ALL.len()];
+    for x in 0..WIDTH {
+        for z in 0..HEIGHT {
+            let cell = Cell {
+                theme,
+                ..Cell::closed()
+            };
+            labyrinth.cells.insert((x, z), cell);
+        }
+    }
+
+    carve(&mut labyrinth, &mut rng);
+    place_features(&mut labyrinth, floor, &mut rng);
+    labyrinth
+}
+
+/// Recursive backtracker: walks to random unvisited neighbours, knocking walls down on the way.
+fn carve(labyrinth: &mut Labyrinth, rng: &mut StdRng) {
+    let mut visited = vec![(0, 0)];
+    let mut stack = vec![(0, 0)];
+    while let Some(&cell) = stack.last() {
+        let unvisited: Vec<CameraDirection> = CameraDirection::ALL
+            .into_iter()
+            .filter(|direction| {
+                let next = step(cell, *direction);
+                labyrinth.cells.contains_key(&next) && !visited.contains(&next)
+            })
+            .collect();
+        match unvisited.choose(rng) {
+            Some(&direction) => {
+                labyrinth.set_wall(cell, direction, false);
+                let next = step(cell, direction);
+                visited.push(next);
+                stack.push(next);
+            }
+            None => {
+                stack.pop();
+            }
+        }
+    }
+}
+
+fn place_features(labyrinth: &mut Labyrinth, floor: u32, rng: &mut StdRng) {
+    let mut free: Vec<(i32, i32)> = labyrinth
+        .cells
+        .keys()
+        .copied()
+        .filter(|&cell| cell != (0, 0))
+        .collect();
+    // Hash maps have no stable order, the seed must give the same floor every time
+    free.sort();
+    free.shuffle(rng);
+
+    let difficulty = 8 + floor as i32;
+    let traps = (2 + floor as usize / 2).min(free.len() / 3);
+
+    if let Some(cell) = free.pop() {
+        set(labyrinth, cell, Feature::new(FeatureKind::Stairs));
+    }
+    if let Some(cell) = free.pop() {
+        set(
+            labyrinth,
+            cell,
+            Feature::hidden(FeatureKind::Pit, difficulty),
+        );
+    }
+    for _ in 0..traps {
+        let Some(cell) = free.pop() else {
+            break;
+        };
+        let feature = match rng.gen_range(0..3) {
+            0 => Feature::hidden(
+                FeatureKind::Spikes {
+                    damage: 2 + floor as i32,
+                },
+                difficulty,
+            ),
+            1 => Feature::hidden(FeatureKind::Spinner, difficulty + 4),
+            _ => match free.first() {
+                // Never lands on another feature, which could bounce the party around forever
+                Some(&to) => Feature::new(FeatureKind::Teleporter { to }),
+                None => continue,
+            },
+        };
+        set(labyrinth, cell, feature);
+    }
+
+    // A plate somewhere opens a shortcut: a wall the maze left standing between two cells
+    let Some(plate) = free.pop() else {
+        return;
+    };
+    let mut walls: Vec<((i32, i32), CameraDirection)> = labyrinth
+        .cells
+        .keys()
+        .flat_map(|&cell| {
+            [
+                (cell, CameraDirection::North),
+                (cell, CameraDirection::East),
+            ]
+        })
+        .filter(|&(cell, direction)| {
+            labyrinth.cells.contains_key(&step(cell, direction))
+                && !labyrinth.can_move(cell, direction)
+        })
+        .collect();
+    walls.sort_by_key(|&(cell, direction)| (cell, direction == CameraDirection::North));
+    if let Some(&(door, side)) = walls.choose(rng) {
+        set(
+            labyrinth,
+            plate,
+            Feature::new(FeatureKind::PressurePlate { door, side }),
+        );
+    }
+}
+
+fn step((x, z): (i32, i32), direction: CameraDirection) -> (i32, i32) {
+    let (dx, dz) = direction.offset();
+    (x + dx, z + dz)
+}
+
+fn set(labyrinth: &mut Labyrinth, cell: (i32, i32), feature: Feature) {
+    if let Some(cell) = labyrinth.cells.get_mut(&cell) {
+        cell.feature = Some(feature);
+    }
+}
@@ -1,61 +1,272 @@
-use bevy::{prelude::*, utils::HashMap};
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use serde::{Deserialize, Serialize};
 
-use crate::GameState;
+use crate::{inventory::item::Item, loading::LabyrinthAssets, GameState};
 
-use super::Position;
+use super::{
+    camera3d::{CameraDirection, GridPosition},
+    feature::Feature,
+    Position,
+};
 
 pub struct LabyrinthPlugin;
 
 impl Plugin for LabyrinthPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnExit(GameState::Loading), setup)
+            .add_systems(OnEnter(GameState::Playing), mark_floor_changed)
+            .init_resource::<CurrentFloor>()
+            .add_event::<FloorChanged>()
             .register_type::<Labyrinth>();
     }
 }
 
-pub fn setup(mut commands: Commands) {
-    // (x,z)
-    // + -  - + -  - + -  - +
-    // | -1,2    0,2    1,2 |
-    // +      + -  - +      +
-    // | -1,1 |  0,1 |  1,1 |
-    // +      + -  - +      +
-    // | -1,0    0,0    1,0 |
-    // + -  - + -  - + -  - +
-
-    let mut cells = HashMap::new();
-    for z in 0..=2 {
-        for x in -1..=1 {
-            let left_wall = x == -1 || x == 1 && z == 1;
-            let right_wall = x == 1 || x == -1 && z == 1;
-            let front_wall = z == 2 || x == 0 && z == 0;
-            let back_wall = z == 0 || x == 0 && z == 2;
-
-            cells.insert(
-                (x, z),
-                Cell {
-                    walls: [
-                        (Position::Center, front_wall),
-                        (Position::Left, left_wall),
-                        (Position::Right, right_wall),
-                        (Position::Back, back_wall),
-                        (Position::Ceiling, true),
-                        (Position::Floor, true),
-                    ],
-                },
-            );
+/// Starts on the hand-made first floor.
+pub fn setup(
+    mut commands: Commands,
+    labyrinths: Res<LabyrinthAssets>,
+    assets: Res<Assets<Labyrinth>>,
+) {
+    let labyrinth = labyrinths.first_floor(&assets);
+    commands.insert_resource(Explored::entrance_of(&labyrinth));
+    commands.insert_resource(labyrinth);
+}
+
+#[derive(Resource, Asset, Reflect, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Labyrinth {
+    #[serde(with = "cell_list")]
+    pub cells: HashMap<(i32, i32), Cell>,
+    /// Where the party arrives on the floor
+    pub entrance: (i32, i32),
+}
+
+impl Labyrinth {
+    /// Whether the cell at `(x, z)` has a wall at `position`. Cells outside the labyrinth are solid.
+    pub fn has_wall(&self, cell: (i32, i32), position: Position) -> bool {
+        self.cells
+            .get(&cell)
+            .map_or(true, |cell| cell.has_wall(position))
+    }
+
+    /// Whether one can step from `cell` to its neighbour in `direction`. Walls are stored on both
+    /// sides, so either of them blocks the way.
+    pub fn can_move(&self, cell: (i32, i32), direction: CameraDirection) -> bool {
+        let (dx, dz) = direction.offset();
+        let neighbour = (cell.0 + dx, cell.1 + dz);
+        !self.has_wall(cell, direction.wall())
+            && !self.has_wall(neighbour, direction.opposite().wall())
+    }
+
+    /// Adds or removes the wall between `cell` and its neighbour in `direction`, on both sides.
+    pub fn set_wall(&mut self, cell: (i32, i32), direction: CameraDirection, exists: bool) {
+        let (dx, dz) = direction.offset();
+        let neighbour = (cell.0 + dx, cell.1 + dz);
+        for (cell, position) in [
+            (cell, direction.wall()),
+            (neighbour, direction.opposite().wall()),
+        ] {
+            if let Some(cell) = self.cells.get_mut(&cell) {
+                for (wall, wall_exists) in cell.walls.iter_mut() {
+                    if *wall == position {
+                        *wall_exists = exists;
+                    }
+                }
+            }
         }
     }
 
-    commands.insert_resource(Labyrinth { cells })
+    /// Cells that look different from `before`, including the ones added or removed since. Items
+    /// and features are drawn on their own and do not count.
+    pub fn changed_cells(&self, before: &HashMap<(i32, i32), Cell>) -> HashSet<(i32, i32)> {
+        let changed = self
+            .cells
+            .iter()
+            .filter(|(coordinates, cell)| {
+                before
+                    .get(*coordinates)
+                    .map_or(true, |old| !old.looks_like(cell))
+            })
+            .map(|(coordinates, _)| *coordinates);
+        let removed = before
+            .keys()
+            .filter(|coordinates| !self.cells.contains_key(*coordinates));
+        changed.chain(removed.copied()).collect()
+    }
+
+    pub fn feature(&self, cell: (i32, i32)) -> Option<&Feature> {
+        self.cells.get(&cell)?.feature.as_ref()
+    }
 }
 
-#[derive(Resource, Reflect)]
-pub struct Labyrinth {
-    pub cells: HashMap<(i32, i32), Cell>,
+/// The entities of the floor are despawned when leaving `GameState::Playing`. Marking the floor as
+/// changed on return spawns its rooms, features, levers and items again.
+fn mark_floor_changed(mut labyrinth: ResMut<Labyrinth>, mut floor: ResMut<CurrentFloor>) {
+    labyrinth.set_changed();
+    floor.set_changed();
 }
 
-#[derive(Reflect)]
+/// Depth of the floor being explored, starting at 1.
+#[derive(Resource)]
+pub struct CurrentFloor(pub u32);
+
+impl Default for CurrentFloor {
+    fn default() -> Self {
+        CurrentFloor(1)
+    }
+}
+
+/// Sent whenever the party moves to another floor.
+#[derive(Event)]
+pub struct FloorChanged(pub u32);
+
+/// Cells the player has already walked through.
+#[derive(Resource)]
+pub struct Explored(pub HashSet<GridPosition>);
+
+impl Explored {
+    /// Only the cell where the party arrives on the floor.
+    pub fn entrance_of(labyrinth: &Labyrinth) -> Self {
+        let (x, z) = labyrinth.entrance;
+        Explored(HashSet::from_iter([GridPosition(x, z)]))
+    }
+}
+
+/// Whether each side of a cell has a wall.
+pub type Walls = [(Position, bool); 6];
+
+#[derive(Reflect, Serialize, Deserialize, Clone, PartialEq, Debug)]
 pub struct Cell {
-    pub walls: [(Position, bool); 6],
+    pub walls: Walls,
+    pub feature: Option<Feature>,
+    pub theme: Theme,
+    pub decoration: Option<Decoration>,
+    /// Lying on the floor until picked up, saved along with the labyrinth
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    #[reflect(ignore)]
+    pub items: Vec<Item>,
+}
+
+impl Cell {
+    /// A cell closed on every side.
+    pub fn closed() -> Self {
+        Cell {
+            walls: [
+                (Position::Center, true),
+                (Position::Left, true),
+                (Position::Right, true),
+                (Position::Back, true),
+                (Position::Ceiling, true),
+                (Position::Floor, true),
+            ],
+            feature: None,
+            theme: Theme::default(),
+            decoration: None,
+            items: Vec::new(),
+        }
+    }
+
+    /// Whether both cells have the same surfaces: walls, theme and decoration.
+    pub fn looks_like(&self, other: &Cell) -> bool {
+        self.walls == other.walls
+            && self.theme == other.theme
+            && self.decoration == other.decoration
+    }
+}
+
+/// Look of the walls, floor and ceiling of a cell.
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Theme {
+    #[default]
+    Stone,
+    Brick,
+    Moss,
+    Hive,
+}
+
+impl Theme {
+    pub const ALL: [Theme; 4] = [Theme::Stone, Theme::Brick, Theme::Moss, Theme::Hive];
+
+    /// Multiplied with the textures of the surfaces.
+    pub fn tint(&self) -> Color {
+        match self {
+            Theme::Stone => Color::WHITE,
+            Theme::Brick => Color::rgb(0.9, 0.6, 0.5),
+            Theme::Moss => Color::rgb(0.6, 0.85, 0.55),
+            Theme::Hive => Color::rgb(0.95, 0.8, 0.4),
+        }
+    }
+}
+
+/// Props standing in a cell, for looks only.
+#[derive(Reflect, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Decoration {
+    Pillar,
+    Bones,
+    Brazier,
+}
+
+impl Decoration {
+    pub const ALL: [Decoration; 3] = [Decoration::Pillar, Decoration::Bones, Decoration::Brazier];
+}
+
+impl Cell {
+    pub fn has_wall(&self, position: Position) -> bool {
+        self.walls
+            .iter()
+            .any(|(wall, exists)| *wall == position && *exists)
+    }
+}
+
+/// Serializes cells as a list sorted by coordinates, since most formats only accept string keys
+/// and a stable order keeps files diffable.
+mod cell_list {
+    use bevy::utils::HashMap;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::Cell;
+
+    pub fn serialize<S: Serializer>(
+        cells: &HashMap<(i32, i32), Cell>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut list: Vec<_> = cells.iter().collect();
+        list.sort_by_key(|(coordinates, _)| **coordinates);
+        list.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<(i32, i32), Cell>, D::Error> {
+        let list = Vec::<((i32, i32), Cell)>::deserialize(deserializer)?;
+        Ok(list.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::{feature::FeatureKind, format::parse_ascii};
+
+    #[test]
+    fn only_cells_that_look_different_have_changed() {
+        let mut labyrinth = parse_ascii("+--+--+--+\n|E. ..|..|\n+--+--+--+").unwrap();
+        let before = labyrinth.cells.clone();
+        assert!(labyrinth.changed_cells(&before).is_empty());
+
+        let cell = labyrinth.cells.get_mut(&(0, 0)).unwrap();
+        cell.items.push(Item::new("rusty_key"));
+        cell.feature = Some(Feature::hidden(FeatureKind::Pit, 10));
+        assert!(labyrinth.changed_cells(&before).is_empty());
+
+        labyrinth.set_wall((1, 0), CameraDirection::East, false);
+        labyrinth.cells.get_mut(&(0, 0)).unwrap().decoration = Some(Decoration::Bones);
+        labyrinth.cells.remove(&(2, 0));
+        labyrinth.cells.insert((0, 1), Cell::closed());
+        let mut changed: Vec<_> = labyrinth.changed_cells(&before).into_iter().collect();
+        changed.sort();
+        assert_eq!(changed, vec![(0, 0), (0, 1), (1, 0), (2, 0)]);
+    }
 }
@@ -1,21 +1,32 @@
-mod camera3d;
-mod config;
-mod labyrinth;
+pub mod camera3d;
+pub mod config;
+pub mod editor;
+pub mod feature;
+pub mod format;
+pub mod generate;
+pub mod labyrinth;
+pub mod script;
 mod surface;
+pub mod torch;
 mod vec_utils;
 
-use bevy::{prelude::*, window::PrimaryWindow};
+use bevy::{prelude::*, utils::HashMap, window::PrimaryWindow};
 use bevy_inspector_egui::{bevy_egui::EguiContext, egui};
 
+use serde::{Deserialize, Serialize};
 use strum_macros::IntoStaticStr;
 
-use crate::GameState;
+use crate::{GameState, PlayingEntity};
 
 use self::{
     camera3d::{Camera3DPlugin, DUNGEON_CAMERA_LAYER},
     config::{ConfigPlugin, DungeonConfig},
-    labyrinth::{Labyrinth, LabyrinthPlugin},
-    surface::{SpawnSurfaceCommand, SurfacePlugin},
+    editor::LabyrinthEditorPlugin,
+    feature::FeaturePlugin,
+    labyrinth::{Cell, Labyrinth, LabyrinthPlugin},
+    script::ScriptPlugin,
+    surface::{Decor, SpawnDecorationCommand, SpawnSurfaceCommand, SurfacePlugin},
+    torch::TorchPlugin,
     vec_utils::{MoveBy, MoveDirection},
 };
 
@@ -23,18 +34,35 @@ pub struct DungeonPlugin;
 
 impl Plugin for DungeonPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins((Camera3DPlugin, ConfigPlugin, SurfacePlugin, LabyrinthPlugin))
-            .add_systems(OnEnter(GameState::Playing), setup)
-            .insert_resource(AmbientLight {
-                color: Color::WHITE,
-                brightness: 0.1,
-            })
-            .add_systems(
-                Update,
-                debug_update_position.run_if(in_state(GameState::Playing)),
-            )
-            .add_systems(Update, ui_example.run_if(in_state(GameState::Playing)))
-            .register_type::<Layout>();
+        app.add_plugins((
+            Camera3DPlugin,
+            ConfigPlugin,
+            SurfacePlugin,
+            LabyrinthPlugin,
+            TorchPlugin,
+            FeaturePlugin,
+            ScriptPlugin,
+            LabyrinthEditorPlugin,
+        ))
+        .init_resource::<SpawnedCells>()
+        .add_systems(OnEnter(GameState::Playing), setup)
+        .add_systems(OnExit(GameState::Playing), forget_spawned_cells)
+        .add_systems(
+            Update,
+            spawn_rooms
+                .run_if(resource_exists_and_changed::<Labyrinth>())
+                .run_if(in_state(GameState::Playing)),
+        )
+        .insert_resource(AmbientLight {
+            color: Color::WHITE,
+            brightness: 0.1,
+        })
+        .add_systems(
+            Update,
+            debug_update_position.run_if(in_state(GameState::Playing)),
+        )
+        .add_systems(Update, ui_example.run_if(in_state(GameState::Playing)))
+        .register_type::<Layout>();
     }
 }
 
@@ -62,9 +90,13 @@ impl Plugin for DungeonPlugin {
 pub struct Layout {
     depth: usize,
     position: Position,
+    /// Cell of the labyrinth the surface belongs to, as `(x, z)`
+    cell: (i32, i32),
 }
 
-#[derive(Clone, Copy, Reflect, IntoStaticStr, Hash, PartialEq, Eq)]
+#[derive(
+    Clone, Copy, Reflect, IntoStaticStr, Hash, PartialEq, Eq, Debug, Serialize, Deserialize,
+)]
 pub enum Position {
     Center,  // 1
     Right,   // 2
@@ -74,7 +106,7 @@ pub enum Position {
     Back,    // 6
 }
 
-pub fn setup(mut commands: Commands, config: Res<DungeonConfig>, labyrinth: Res<Labyrinth>) {
+pub fn setup(mut commands: Commands, config: Res<DungeonConfig>) {
     commands.spawn((
         PointLightBundle {
             transform: Transform::from_xyz(0., 0., config.size / 2.0 * -1.),
@@ -82,18 +114,67 @@ pub fn setup(mut commands: Commands, config: Res<DungeonConfig>, labyrinth: Res<
         },
         DUNGEON_CAMERA_LAYER,
         Name::new("Point Light"),
+        PlayingEntity,
     ));
+}
+
+/// Cells as they were when their surfaces were last spawned.
+#[derive(Resource, Default)]
+pub struct SpawnedCells(pub HashMap<(i32, i32), Cell>);
+
+/// The surfaces are gone with the other playing entities, every cell is spawned again on return.
+fn forget_spawned_cells(mut spawned: ResMut<SpawnedCells>) {
+    spawned.0.clear();
+}
+
+/// Respawns the surfaces of the cells that changed since they were spawned, be it a whole new
+/// floor, a single door opening or a cell repainted in the editor.
+fn spawn_rooms(
+    mut commands: Commands,
+    mut spawned: ResMut<SpawnedCells>,
+    labyrinth: Res<Labyrinth>,
+    surfaces: Query<(Entity, &Layout)>,
+    decorations: Query<(Entity, &Decor)>,
+) {
+    let dirty = labyrinth.changed_cells(&spawned.0);
+    if dirty.is_empty() {
+        return;
+    }
+    let surfaces = surfaces
+        .iter()
+        .map(|(entity, layout)| (entity, layout.cell));
+    let decorations = decorations
+        .iter()
+        .map(|(entity, decor)| (entity, decor.cell));
+    for (entity, _) in surfaces
+        .chain(decorations)
+        .filter(|(_, cell)| dirty.contains(cell))
+    {
+        commands.entity(entity).despawn_recursive();
+    }
 
-    for (&(x, y), cell) in labyrinth.cells.iter() {
-        let center = Vec3::new(0.0, 0.0, -config.size);
-        let center = center.move_by(MoveDirection::Forward, config.size * y as f32);
-        let center = center.move_by(MoveDirection::ShiftRight, config.size * x as f32);
-        spawn_room(&mut commands, center, &cell.walls);
+    for coordinates in dirty {
+        match labyrinth.cells.get(&coordinates) {
+            Some(cell) => {
+                spawn_room(&mut commands, coordinates, cell);
+                spawned.0.insert(coordinates, cell.clone());
+            }
+            None => {
+                spawned.0.remove(&coordinates);
+            }
+        }
     }
 }
 
-fn spawn_room(commands: &mut Commands, center: Vec3, side_walls: &[(Position, bool); 6]) {
-    for (position, exists) in side_walls.iter() {
+/// World position of the middle of the cell `(x, z)`, halfway between its front and back walls.
+pub fn cell_center(size: f32, (x, z): (i32, i32)) -> Vec3 {
+    Vec3::new(0.0, 0.0, -size / 2.0)
+        .move_by(MoveDirection::Forward, size * z as f32)
+        .move_by(MoveDirection::ShiftRight, size * x as f32)
+}
+
+fn spawn_room(commands: &mut Commands, coordinates: (i32, i32), cell: &Cell) {
+    for (position, exists) in cell.walls.iter() {
         if *exists {
             let direction = match position {
                 Position::Center => None,
@@ -104,12 +185,19 @@ fn spawn_room(commands: &mut Commands, center: Vec3, side_walls: &[(Position, bo
                 Position::Ceiling => Some(MoveDirection::Up),
             };
             commands.add(SpawnSurfaceCommand {
-                position: center,
+                cell: coordinates,
+                theme: cell.theme,
                 direction,
                 position_id: *position,
             })
         }
     }
+    if let Some(decoration) = cell.decoration {
+        commands.add(SpawnDecorationCommand {
+            cell: coordinates,
+            decoration,
+        });
+    }
 }
 
 pub fn debug_update_position(
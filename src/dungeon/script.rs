@@ -0,0 +1,505 @@
+use std::collections::BTreeSet;
+
+use bevy::{asset::Asset, prelude::*, reflect::TypePath};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    camera3d::{
+        move_player, CameraDirection, GridPosition, Player, PlayerMoved, DUNGEON_CAMERA_LAYER,
+    },
+    cell_center,
+    config::DungeonConfig,
+    labyrinth::{CurrentFloor, FloorChanged, Labyrinth},
+};
+use crate::{
+    inventory::{item::Item, pick_up, Inventory},
+    loading::{GameData, LabyrinthAssets},
+    message::GameMessage,
+    settings::{Action as KeyAction, Settings},
+    GameState, PlayingEntity, PlayingState,
+};
+
+pub struct ScriptPlugin;
+
+/// This plugin runs the puzzle scripts of hand-made floors: levers are pulled and locks opened
+/// with E while facing them, and rules react to them or to the player entering a cell by changing
+/// the labyrinth. Items lying on the cell are picked up first.
+///
+/// Scripts are plain assets, with the `file_watcher` feature they are reloaded when edited.
+impl Plugin for ScriptPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ScriptState>()
+            .add_event::<ScriptEvent>()
+            .add_systems(
+                Update,
+                (
+                    (
+                        enter_cell.after(move_player),
+                        // Sees the items before they are picked up
+                        (pull_lever, open_lock).before(pick_up),
+                    ),
+                    run_scripts,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(in_state(PlayingState::Exploring)),
+            )
+            .add_systems(
+                Update,
+                (reload_scripts, spawn_levers)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// Puzzle logic of a hand-made floor, loaded from `data/*.script.ron`.
+#[derive(Deserialize, Asset, TypePath)]
+pub struct Script {
+    /// Floor the script runs on
+    pub floor: u32,
+    #[serde(default)]
+    pub levers: Vec<Lever>,
+    #[serde(default)]
+    pub locks: Vec<Lock>,
+    pub rules: Vec<Rule>,
+}
+
+/// A lever on the `side` wall of `cell`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Lever {
+    pub id: String,
+    pub cell: (i32, i32),
+    pub side: CameraDirection,
+}
+
+/// A lock on the `side` wall of `cell`, opened once with the `key` item, which is used up.
+#[derive(Deserialize, Clone, Debug)]
+pub struct Lock {
+    pub id: String,
+    pub cell: (i32, i32),
+    pub side: CameraDirection,
+    pub key: String,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct Rule {
+    pub when: Trigger,
+    pub then: Vec<Action>,
+    /// Only run the first time the trigger happens
+    #[serde(default)]
+    pub once: bool,
+}
+
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+pub enum Trigger {
+    /// The player steps into the cell
+    Enter((i32, i32)),
+    /// The lever with this id is pulled
+    Pull(String),
+    /// The lock with this id is opened
+    Unlock(String),
+}
+
+#[derive(Deserialize, Clone, PartialEq, Eq, Debug)]
+pub enum Action {
+    /// Opens the wall on the `side` of `cell` if it is closed, closes it otherwise
+    ToggleWall {
+        cell: (i32, i32),
+        side: CameraDirection,
+    },
+    SetWall {
+        cell: (i32, i32),
+        side: CameraDirection,
+        exists: bool,
+    },
+    /// Writes to the log
+    Message(String),
+    /// Shows the hidden feature of the cell
+    Reveal((i32, i32)),
+}
+
+/// Something happened that scripts may react to.
+#[derive(Event, Clone, PartialEq, Eq, Debug)]
+pub struct ScriptEvent(pub Trigger);
+
+/// Rules with `once` that already ran, by index, and locks already opened. Cleared on floor change
+/// and script reload, saved with the run.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct ScriptState {
+    spent: BTreeSet<usize>,
+    unlocked: BTreeSet<String>,
+}
+
+impl Script {
+    /// Actions of every rule matching `trigger`, in order. `once` rules are marked as spent.
+    pub fn fire(&self, trigger: &Trigger, state: &mut ScriptState) -> Vec<Action> {
+        let mut actions = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            if rule.when != *trigger || state.spent.contains(&index) {
+                continue;
+            }
+            if rule.once {
+                state.spent.insert(index);
+            }
+            actions.extend(rule.then.iter().cloned());
+        }
+        actions
+    }
+
+    pub fn lever_at(&self, cell: (i32, i32), facing: CameraDirection) -> Option<&Lever> {
+        self.levers
+            .iter()
+            .find(|lever| lever.cell == cell && lever.side == facing)
+    }
+
+    pub fn lock_at(&self, cell: (i32, i32), facing: CameraDirection) -> Option<&Lock> {
+        self.locks
+            .iter()
+            .find(|lock| lock.cell == cell && lock.side == facing)
+    }
+}
+
+impl Action {
+    /// Runs the action, returning the message to show if any.
+    pub fn apply(&self, labyrinth: &mut Labyrinth) -> Option<String> {
+        match self {
+            Action::ToggleWall { cell, side } => {
+                let open = labyrinth.can_move(*cell, *side);
+                labyrinth.set_wall(*cell, *side, open);
+            }
+            Action::SetWall { cell, side, exists } => labyrinth.set_wall(*cell, *side, *exists),
+            Action::Message(message) => return Some(message.clone()),
+            Action::Reveal(cell) => {
+                if let Some(feature) = labyrinth
+                    .cells
+                    .get_mut(cell)
+                    .and_then(|cell| cell.feature.as_mut())
+                {
+                    feature.hidden = false;
+                }
+            }
+        }
+        None
+    }
+}
+
+fn enter_cell(mut moves: EventReader<PlayerMoved>, mut events: EventWriter<ScriptEvent>) {
+    for PlayerMoved(position) in moves.read() {
+        events.send(ScriptEvent(Trigger::Enter((position.0, position.1))));
+    }
+}
+
+/// Whether the interact key was pressed while facing a wall, and not to pick up items.
+fn interacts_with_wall(
+    keyboard_input: &Input<KeyCode>,
+    settings: &Settings,
+    labyrinth: &Labyrinth,
+    position: &GridPosition,
+) -> bool {
+    keyboard_input.just_pressed(settings.keys.key(KeyAction::Interact))
+        && labyrinth
+            .cells
+            .get(&(position.0, position.1))
+            .map_or(true, |cell| cell.items.is_empty())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn pull_lever(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    mut events: EventWriter<ScriptEvent>,
+    mut messages: EventWriter<GameMessage>,
+    floor: Res<CurrentFloor>,
+    labyrinth: Res<Labyrinth>,
+    data: GameData,
+    player: Query<(&GridPosition, &CameraDirection), With<Player>>,
+) {
+    let (Some(script), Ok((position, facing))) = (data.script(), player.get_single()) else {
+        return;
+    };
+    if script.floor != floor.0
+        || !interacts_with_wall(&keyboard_input, &settings, &labyrinth, position)
+    {
+        return;
+    }
+    if let Some(lever) = script.lever_at((position.0, position.1), *facing) {
+        messages.send(GameMessage::info("The lever creaks into place"));
+        events.send(ScriptEvent(Trigger::Pull(lever.id.clone())));
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn open_lock(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    mut state: ResMut<ScriptState>,
+    mut inventory: ResMut<Inventory>,
+    mut events: EventWriter<ScriptEvent>,
+    mut messages: EventWriter<GameMessage>,
+    floor: Res<CurrentFloor>,
+    labyrinth: Res<Labyrinth>,
+    data: GameData,
+    player: Query<(&GridPosition, &CameraDirection), With<Player>>,
+) {
+    let (Some(script), Some(items), Ok((position, facing))) =
+        (data.script(), data.items(), player.get_single())
+    else {
+        return;
+    };
+    if script.floor != floor.0
+        || !interacts_with_wall(&keyboard_input, &settings, &labyrinth, position)
+    {
+        return;
+    }
+    let Some(lock) = script
+        .lock_at((position.0, position.1), *facing)
+        .filter(|lock| !state.unlocked.contains(&lock.id))
+    else {
+        return;
+    };
+    let key = inventory
+        .slots
+        .iter_mut()
+        .find(|slot| slot.as_ref().map_or(false, |item| item.id == lock.key));
+    let name = items.name(&Item::new(&lock.key)).to_lowercase();
+    match key {
+        Some(key) => {
+            *key = None;
+            state.unlocked.insert(lock.id.clone());
+            messages.send(GameMessage::info(format!("The {name} fits the lock")));
+            events.send(ScriptEvent(Trigger::Unlock(lock.id.clone())));
+        }
+        None => messages.send(GameMessage::warning(format!("The lock needs a {name}"))),
+    }
+}
+
+fn run_scripts(
+    mut events: EventReader<ScriptEvent>,
+    mut state: ResMut<ScriptState>,
+    mut labyrinth: ResMut<Labyrinth>,
+    mut messages: EventWriter<GameMessage>,
+    floor: Res<CurrentFloor>,
+    data: GameData,
+) {
+    let Some(script) = data.script().filter(|script| script.floor == floor.0) else {
+        events.clear();
+        return;
+    };
+    for ScriptEvent(trigger) in events.read() {
+        for action in script.fire(trigger, &mut state) {
+            if let Some(message) = action.apply(&mut labyrinth) {
+                messages.send(GameMessage::info(message).toast());
+            }
+        }
+    }
+}
+
+/// Starts scripts over when they are edited or the party reaches another floor. An edited script
+/// also gets a fresh copy of its floor, whose walls the old one may have moved.
+#[allow(clippy::too_many_arguments)]
+fn reload_scripts(
+    mut commands: Commands,
+    mut state: ResMut<ScriptState>,
+    mut asset_events: EventReader<AssetEvent<Script>>,
+    mut floor_changes: EventReader<FloorChanged>,
+    floor: Res<CurrentFloor>,
+    data: GameData,
+    labyrinths: Res<LabyrinthAssets>,
+    floors: Res<Assets<Labyrinth>>,
+) {
+    let floor_changed = floor_changes.read().count() > 0;
+    let edited = asset_events
+        .read()
+        .any(|event| matches!(event, AssetEvent::Modified { .. }));
+    if edited {
+        info!("script reloaded");
+    }
+    // Only the first floor is hand-made, so it is the only one with a script
+    let on_script_floor = data
+        .script()
+        .map_or(false, |script| script.floor == floor.0);
+    if edited && !floor_changed && on_script_floor {
+        commands.insert_resource(labyrinths.first_floor(&floors));
+    }
+    if edited || floor_changed {
+        state.spent.clear();
+        state.unlocked.clear();
+    }
+}
+
+#[derive(Component)]
+struct LeverMarker;
+
+/// Puts a handle on the wall of every lever of the current floor, and a plate on every lock still
+/// closed.
+#[allow(clippy::too_many_arguments)]
+fn spawn_levers(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut asset_events: EventReader<AssetEvent<Script>>,
+    config: Res<DungeonConfig>,
+    floor: Res<CurrentFloor>,
+    state: Res<ScriptState>,
+    data: GameData,
+    levers: Query<Entity, With<LeverMarker>>,
+) {
+    if asset_events.read().count() == 0 && !floor.is_changed() && !state.is_changed() {
+        return;
+    }
+    for entity in levers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    let Some(script) = data.script().filter(|script| script.floor == floor.0) else {
+        return;
+    };
+
+    let mesh = meshes.add(Mesh::from(shape::Box::new(
+        config.size * 0.05,
+        config.size * 0.25,
+        config.size * 0.05,
+    )));
+    let material = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.5, 0.35, 0.2),
+        metallic: 0.3,
+        ..default()
+    });
+    for lever in &script.levers {
+        // Sticks out of the wall, a bit below the eye level
+        let translation = cell_center(config.size, lever.cell)
+            + lever.side.translation(config.size) * 0.45
+            - Vec3::Y * config.size * 0.15;
+        commands.spawn((
+            PbrBundle {
+                mesh: mesh.clone(),
+                material: material.clone(),
+                transform: Transform::from_translation(translation),
+                ..default()
+            },
+            LeverMarker,
+            Name::new(format!("lever_{}", lever.id)),
+            DUNGEON_CAMERA_LAYER,
+            PlayingEntity,
+        ));
+    }
+
+    let plate = meshes.add(Mesh::from(shape::Box::new(
+        config.size * 0.15,
+        config.size * 0.2,
+        config.size * 0.02,
+    )));
+    let iron = materials.add(StandardMaterial {
+        base_color: Color::rgb(0.3, 0.3, 0.32),
+        metallic: 0.8,
+        ..default()
+    });
+    for lock in script
+        .locks
+        .iter()
+        .filter(|lock| !state.unlocked.contains(&lock.id))
+    {
+        let translation =
+            cell_center(config.size, lock.cell) + lock.side.translation(config.size) * 0.49;
+        commands.spawn((
+            PbrBundle {
+                mesh: plate.clone(),
+                material: iron.clone(),
+                transform: Transform::from_translation(translation)
+                    .with_rotation(lock.side.rotation()),
+                ..default()
+            },
+            LeverMarker,
+            Name::new(format!("lock_{}", lock.id)),
+            DUNGEON_CAMERA_LAYER,
+            PlayingEntity,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::{
+        feature::{Feature, FeatureKind},
+        format::parse_ascii,
+    };
+
+    fn script() -> Script {
+        ron::from_str(include_str!("../../assets/data/floor1.script.ron")).unwrap()
+    }
+
+    /// Two cells with a wall between them.
+    fn rooms() -> Labyrinth {
+        parse_ascii("+--+--+\n|E.|..|\n+--+--+").unwrap()
+    }
+
+    #[test]
+    fn once_rules_only_fire_the_first_time() {
+        let script = script();
+        let mut state = ScriptState::default();
+        let enter = Trigger::Enter((0, 1));
+        assert_eq!(
+            script.fire(&enter, &mut state),
+            vec![Action::Message(
+                "A musty alcove, long forgotten".to_string()
+            )]
+        );
+        assert!(script.fire(&enter, &mut state).is_empty());
+        // Other rules keep firing
+        let pull = Trigger::Pull("L1".to_string());
+        assert_eq!(script.fire(&pull, &mut state).len(), 2);
+        assert_eq!(script.fire(&pull, &mut state).len(), 2);
+        assert!(script
+            .fire(&Trigger::Pull("L2".to_string()), &mut state)
+            .is_empty());
+    }
+
+    #[test]
+    fn toggling_a_wall_opens_and_closes_it() {
+        let mut labyrinth = rooms();
+        let toggle = Action::ToggleWall {
+            cell: (0, 0),
+            side: CameraDirection::East,
+        };
+        assert!(!labyrinth.can_move((0, 0), CameraDirection::East));
+        assert_eq!(toggle.apply(&mut labyrinth), None);
+        assert!(labyrinth.can_move((0, 0), CameraDirection::East));
+        assert!(labyrinth.can_move((1, 0), CameraDirection::West));
+        toggle.apply(&mut labyrinth);
+        assert!(!labyrinth.can_move((0, 0), CameraDirection::East));
+    }
+
+    #[test]
+    fn setting_a_wall_leaves_it_as_asked() {
+        let mut labyrinth = rooms();
+        let set = |exists| Action::SetWall {
+            cell: (1, 0),
+            side: CameraDirection::West,
+            exists,
+        };
+        for _ in 0..2 {
+            set(false).apply(&mut labyrinth);
+            assert!(labyrinth.can_move((0, 0), CameraDirection::East));
+        }
+        set(true).apply(&mut labyrinth);
+        assert!(!labyrinth.can_move((0, 0), CameraDirection::East));
+    }
+
+    #[test]
+    fn messages_and_reveals() {
+        let mut labyrinth = rooms();
+        labyrinth.cells.get_mut(&(1, 0)).unwrap().feature =
+            Some(Feature::hidden(FeatureKind::Pit, 15));
+        let before = labyrinth.clone();
+        let message = Action::Message("Click".to_string());
+        assert_eq!(message.apply(&mut labyrinth), Some("Click".to_string()));
+        assert_eq!(labyrinth, before);
+
+        assert_eq!(Action::Reveal((1, 0)).apply(&mut labyrinth), None);
+        assert!(!labyrinth.feature((1, 0)).unwrap().hidden);
+        // Cells without a feature are left alone
+        Action::Reveal((0, 0)).apply(&mut labyrinth);
+        assert_eq!(labyrinth.cells[&(0, 0)], before.cells[&(0, 0)]);
+    }
+}
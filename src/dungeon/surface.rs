@@ -1,13 +1,17 @@
 use bevy::ecs::system::Command;
 use bevy::prelude::*;
+use bevy::utils::HashMap;
 use std::f32::consts::{FRAC_PI_2, PI};
 
 use super::camera3d::DUNGEON_CAMERA_LAYER;
+use super::cell_center;
 use super::config::DungeonConfig;
+use super::labyrinth::{Decoration, Theme};
 use super::vec_utils::{MoveBy, MoveDirection};
 use super::{Layout, Position};
 use crate::loading::TextureAssets;
 use crate::GameState;
+use crate::PlayingEntity;
 
 pub struct SurfacePlugin;
 
@@ -21,6 +25,11 @@ impl Plugin for SurfacePlugin {
 #[derive(Resource)]
 struct RoomResources {
     pub surface_mesh: Handle<Mesh>,
+    pub themes: HashMap<Theme, ThemeMaterials>,
+    pub decorations: HashMap<Decoration, (Handle<Mesh>, Handle<StandardMaterial>)>,
+}
+
+struct ThemeMaterials {
     pub wall_material: Handle<StandardMaterial>,
     pub ceilling_material: Handle<StandardMaterial>,
     pub floor_material: Handle<StandardMaterial>,
@@ -33,38 +42,95 @@ pub fn setup(
     assets: Res<TextureAssets>,
     config: Res<DungeonConfig>,
 ) {
-    let wall_material = materials.add(StandardMaterial {
-        base_color_texture: Some(assets.wall.clone()),
-        normal_map_texture: Some(assets.wall_normal.clone()),
-        perceptual_roughness: 1.,
-        ..Default::default()
-    });
-    let floor_material = materials.add(StandardMaterial {
-        base_color_texture: Some(assets.floor.clone()),
-        perceptual_roughness: 0.9,
-        ..Default::default()
-    });
-    let ceilling_material = materials.add(StandardMaterial {
-        base_color_texture: Some(assets.ceilling.clone()),
-        perceptual_roughness: 0.9,
-        ..Default::default()
-    });
+    let mut themes = HashMap::default();
+    for theme in Theme::ALL {
+        let wall_material = materials.add(StandardMaterial {
+            base_color: theme.tint(),
+            base_color_texture: Some(assets.wall.clone()),
+            normal_map_texture: Some(assets.wall_normal.clone()),
+            perceptual_roughness: 1.,
+            ..Default::default()
+        });
+        let floor_material = materials.add(StandardMaterial {
+            base_color: theme.tint(),
+            base_color_texture: Some(assets.floor.clone()),
+            perceptual_roughness: 0.9,
+            ..Default::default()
+        });
+        let ceilling_material = materials.add(StandardMaterial {
+            base_color: theme.tint(),
+            base_color_texture: Some(assets.ceilling.clone()),
+            perceptual_roughness: 0.9,
+            ..Default::default()
+        });
+        themes.insert(
+            theme,
+            ThemeMaterials {
+                wall_material,
+                ceilling_material,
+                floor_material,
+            },
+        );
+    }
 
     let surface_mesh = meshes.add(Mesh::from(shape::Quad::new(Vec2::new(
         config.size,
         config.size,
     ))));
 
+    let mut decorations = HashMap::default();
+    for decoration in Decoration::ALL {
+        let (mesh, material) = match decoration {
+            Decoration::Pillar => (
+                Mesh::from(shape::Cylinder {
+                    radius: config.size * 0.1,
+                    height: config.size,
+                    ..default()
+                }),
+                StandardMaterial {
+                    base_color_texture: Some(assets.wall.clone()),
+                    perceptual_roughness: 1.,
+                    ..default()
+                },
+            ),
+            Decoration::Bones => (
+                Mesh::from(shape::Box::new(
+                    config.size * 0.3,
+                    config.size * 0.03,
+                    config.size * 0.1,
+                )),
+                StandardMaterial {
+                    base_color: Color::rgb(0.9, 0.88, 0.8),
+                    ..default()
+                },
+            ),
+            Decoration::Brazier => (
+                Mesh::from(shape::Cylinder {
+                    radius: config.size * 0.08,
+                    height: config.size * 0.3,
+                    ..default()
+                }),
+                StandardMaterial {
+                    base_color: Color::rgb(0.3, 0.2, 0.1),
+                    emissive: Color::rgb(0.9, 0.4, 0.1),
+                    ..default()
+                },
+            ),
+        };
+        decorations.insert(decoration, (meshes.add(mesh), materials.add(material)));
+    }
+
     commands.insert_resource(RoomResources {
         surface_mesh,
-        wall_material,
-        ceilling_material,
-        floor_material,
+        themes,
+        decorations,
     });
 }
 
 pub struct SpawnSurfaceCommand {
-    pub position: Vec3,
+    /// Cell of the labyrinth the surface belongs to
+    pub cell: (i32, i32),
+    pub theme: Theme,
     pub direction: Option<MoveDirection>,
     pub position_id: Position,
 }
@@ -73,11 +139,15 @@ impl Command for SpawnSurfaceCommand {
     fn apply(self, world: &mut World) {
         let resources = world.resource::<RoomResources>();
         let config = world.resource::<DungeonConfig>();
+        let (x, z) = self.cell;
+        let position = Vec3::new(0.0, 0.0, -config.size)
+            .move_by(MoveDirection::Forward, config.size * z as f32)
+            .move_by(MoveDirection::ShiftRight, config.size * x as f32);
 
         let mut transform = Transform::from_translation(
             self.direction
-                .map(|d| self.position.move_by(d, config.size))
-                .unwrap_or(self.position),
+                .map(|d| position.move_by(d, config.size))
+                .unwrap_or(position),
         );
 
         if let Some(direction) = self.direction {
@@ -94,10 +164,11 @@ impl Command for SpawnSurfaceCommand {
             transform = transform.with_rotation(rotation);
         }
 
+        let theme = &resources.themes[&self.theme];
         let material = match self.direction {
-            Some(MoveDirection::Up) => resources.ceilling_material.clone(),
-            Some(MoveDirection::Down) => resources.floor_material.clone(),
-            _ => resources.wall_material.clone(),
+            Some(MoveDirection::Up) => theme.ceilling_material.clone(),
+            Some(MoveDirection::Down) => theme.floor_material.clone(),
+            _ => theme.wall_material.clone(),
         };
 
         let surface_name: &'static str = self.position_id.into();
@@ -109,11 +180,53 @@ impl Command for SpawnSurfaceCommand {
                 ..default()
             },
             Layout {
-                depth: (-self.position.z / config.size) as usize,
+                depth: (-position.z / config.size) as usize,
                 position: self.position_id,
+                cell: self.cell,
             },
             Name::new(format!("{} Surface", surface_name)),
             DUNGEON_CAMERA_LAYER,
+            PlayingEntity,
+        ));
+    }
+}
+
+/// Put on the props of a cell, to find them when the cell is respawned.
+#[derive(Component)]
+pub struct Decor {
+    pub cell: (i32, i32),
+}
+
+pub struct SpawnDecorationCommand {
+    pub cell: (i32, i32),
+    pub decoration: Decoration,
+}
+
+impl Command for SpawnDecorationCommand {
+    fn apply(self, world: &mut World) {
+        let resources = world.resource::<RoomResources>();
+        let config = world.resource::<DungeonConfig>();
+        let (mesh, material) = resources.decorations[&self.decoration].clone();
+
+        // Props stand on the floor, half a cell below the eye level
+        let height = match self.decoration {
+            Decoration::Pillar => config.size,
+            Decoration::Bones => config.size * 0.03,
+            Decoration::Brazier => config.size * 0.3,
+        };
+        let translation =
+            cell_center(config.size, self.cell) - Vec3::Y * (config.size - height) * 0.5;
+        world.spawn((
+            PbrBundle {
+                mesh,
+                material,
+                transform: Transform::from_translation(translation),
+                ..default()
+            },
+            Decor { cell: self.cell },
+            Name::new(format!("{:?}", self.decoration)),
+            DUNGEON_CAMERA_LAYER,
+            PlayingEntity,
         ));
     }
 }
@@ -0,0 +1,76 @@
+use bevy::prelude::*;
+
+use super::{config::DungeonConfig, debug_update_position, labyrinth::FloorChanged};
+use crate::{GameState, PlayingState};
+
+pub struct TorchPlugin;
+
+/// The party's torch burns down while exploring and the dungeon gets darker with it. A fresh
+/// torch is lit on each new floor.
+impl Plugin for TorchPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Torch>()
+            .add_systems(
+                Update,
+                burn_torch
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(in_state(PlayingState::Exploring)),
+            )
+            .add_systems(
+                Update,
+                dim_light
+                    .after(debug_update_position)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct Torch {
+    /// Seconds a fresh torch lasts
+    duration: f32,
+    remaining: f32,
+}
+
+impl Default for Torch {
+    fn default() -> Self {
+        Torch {
+            duration: Torch::BASE_DURATION,
+            remaining: Torch::BASE_DURATION,
+        }
+    }
+}
+
+impl Torch {
+    pub const BASE_DURATION: f32 = 180.;
+    /// Share of the light left once the torch is out, the dungeon never gets pitch black
+    const EMBERS: f32 = 0.2;
+
+    pub fn set_duration(&mut self, duration: f32) {
+        self.duration = duration.max(1.);
+        self.remaining = self.remaining.min(self.duration);
+    }
+
+    pub fn refill(&mut self) {
+        self.remaining = self.duration;
+    }
+
+    /// Brightness factor, from 1 for a fresh torch down to the embers.
+    pub fn light(&self) -> f32 {
+        Self::EMBERS + (1. - Self::EMBERS) * (self.remaining / self.duration)
+    }
+}
+
+fn burn_torch(time: Res<Time>, mut torch: ResMut<Torch>, mut floors: EventReader<FloorChanged>) {
+    if floors.read().next().is_some() {
+        torch.refill();
+    } else if torch.remaining > 0. {
+        torch.remaining = (torch.remaining - time.delta_seconds()).max(0.);
+    }
+}
+
+fn dim_light(config: Res<DungeonConfig>, torch: Res<Torch>, mut lights: Query<&mut PointLight>) {
+    for mut light in lights.iter_mut() {
+        light.intensity = config.brightness * torch.light();
+    }
+}
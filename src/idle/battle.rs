@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+use super::{auto_playing, check_stop_rules, AutoPlay, PlaySpeed, Tactic};
+use crate::{
+    combat::{
+        sim::{Action, Encounter, Side},
+        ActiveEncounter, CombatLog,
+    },
+    GameState, PlayingState,
+};
+
+pub struct AutoBattlePlugin;
+
+impl Plugin for AutoBattlePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<BattleTimer>().add_systems(
+            Update,
+            auto_battle
+                .after(check_stop_rules)
+                .run_if(auto_playing)
+                .run_if(in_state(GameState::Playing))
+                .run_if(in_state(PlayingState::Combat)),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct BattleTimer(Timer);
+
+impl Default for BattleTimer {
+    fn default() -> Self {
+        BattleTimer(Timer::new(AutoPlay::default().step, TimerMode::Repeating))
+    }
+}
+
+impl Tactic {
+    pub fn choose(&self, encounter: &Encounter, actor: usize) -> Action {
+        let stats = encounter.combatants()[actor].stats;
+        let health = stats.hp as f32 / stats.max_hp.max(1) as f32;
+        match self {
+            Tactic::Balanced if health < 0.25 => Action::Defend,
+            Tactic::Cautious if health < 0.3 => Action::Flee,
+            Tactic::Cautious if health < 0.5 => Action::Defend,
+            _ => encounter.auto_action(actor),
+        }
+    }
+}
+
+/// Plays the party's turns with the configured [`Tactic`], one turn per step, or the whole fight
+/// at once with [`PlaySpeed::Instant`].
+fn auto_battle(
+    time: Res<Time>,
+    auto_play: Res<AutoPlay>,
+    mut timer: ResMut<BattleTimer>,
+    mut combat: ResMut<ActiveEncounter>,
+    mut log: ResMut<CombatLog>,
+) {
+    let instant = auto_play.speed == PlaySpeed::Instant;
+    if !instant && !auto_play.tick(&mut timer.0, &time) {
+        return;
+    }
+
+    let encounter = &mut combat.encounter;
+    while let Some(actor) = encounter.current() {
+        let action = match encounter.combatants()[actor].side {
+            Side::Party => auto_play.tactic.choose(encounter, actor),
+            Side::Enemy => encounter.auto_action(actor),
+        };
+        let report = encounter.act(action);
+        log.push_turn(encounter, &report);
+        if !instant {
+            break;
+        }
+    }
+}
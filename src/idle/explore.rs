@@ -0,0 +1,113 @@
+use std::collections::VecDeque;
+
+use bevy::{prelude::*, utils::HashMap};
+use rand::seq::SliceRandom;
+
+use super::{auto_playing, AutoPlay};
+use crate::{
+    dungeon::{
+        camera3d::{CameraDirection, GridPosition, Player, PlayerMove},
+        labyrinth::{Explored, Labyrinth},
+    },
+    GameState, PlayingState,
+};
+
+pub struct AutoExplorePlugin;
+
+impl Plugin for AutoExplorePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ExploreTimer>().add_systems(
+            Update,
+            auto_explore
+                .run_if(auto_playing)
+                .run_if(in_state(GameState::Playing))
+                .run_if(in_state(PlayingState::Exploring)),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct ExploreTimer(Timer);
+
+impl Default for ExploreTimer {
+    fn default() -> Self {
+        ExploreTimer(Timer::new(AutoPlay::default().step, TimerMode::Repeating))
+    }
+}
+
+/// Walks towards the closest unexplored cell, one [`PlayerMove`] per step. Once everything has
+/// been seen, the party wanders around at random.
+fn auto_explore(
+    time: Res<Time>,
+    auto_play: Res<AutoPlay>,
+    mut timer: ResMut<ExploreTimer>,
+    mut moves: EventWriter<PlayerMove>,
+    labyrinth: Res<Labyrinth>,
+    explored: Res<Explored>,
+    player: Query<(&GridPosition, &CameraDirection), With<Player>>,
+) {
+    if !auto_play.tick(&mut timer.0, &time) {
+        return;
+    }
+    let Ok((position, facing)) = player.get_single() else {
+        return;
+    };
+
+    let heading = path_to_unexplored(&labyrinth, &explored, *position)
+        .or_else(|| wander(&labyrinth, *position, *facing));
+    let Some(heading) = heading else {
+        return;
+    };
+
+    moves.send(if heading == *facing {
+        PlayerMove::Forward
+    } else if heading == facing.left() {
+        PlayerMove::TurnLeft
+    } else {
+        PlayerMove::TurnRight
+    });
+}
+
+/// First direction to take on the shortest path to a cell that is not in `explored` yet.
+fn path_to_unexplored(
+    labyrinth: &Labyrinth,
+    explored: &Explored,
+    from: GridPosition,
+) -> Option<CameraDirection> {
+    let mut first_steps: HashMap<GridPosition, CameraDirection> = HashMap::default();
+    let mut queue = VecDeque::from([from]);
+    while let Some(cell) = queue.pop_front() {
+        if !explored.0.contains(&cell) {
+            return first_steps.get(&cell).copied();
+        }
+        for direction in CameraDirection::ALL {
+            let next = cell.step(direction);
+            if next == from
+                || first_steps.contains_key(&next)
+                || !labyrinth.can_move((cell.0, cell.1), direction)
+            {
+                continue;
+            }
+            let first_step = first_steps.get(&cell).copied().unwrap_or(direction);
+            first_steps.insert(next, first_step);
+            queue.push_back(next);
+        }
+    }
+    None
+}
+
+/// Keeps going forward when possible, otherwise picks a random open direction.
+fn wander(
+    labyrinth: &Labyrinth,
+    from: GridPosition,
+    facing: CameraDirection,
+) -> Option<CameraDirection> {
+    if labyrinth.can_move((from.0, from.1), facing) {
+        return Some(facing);
+    }
+    let open: Vec<CameraDirection> = CameraDirection::ALL
+        .into_iter()
+        .filter(|direction| labyrinth.can_move((from.0, from.1), *direction))
+        .collect();
+    open.choose(&mut rand::thread_rng()).copied()
+}
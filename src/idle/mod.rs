@@ -0,0 +1,211 @@
+mod battle;
+mod explore;
+
+use std::time::Duration;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{ActiveEncounter, CombatLog, LootDropped, Monster},
+    party::Party,
+    GameState, PlayingState,
+};
+
+use self::{battle::AutoBattlePlugin, explore::AutoExplorePlugin};
+
+pub struct IdlePlugin;
+
+/// This plugin drives the idle side of the game: when auto-play is on, the party explores the
+/// labyrinth and fights on its own until one of the [`StopRules`] kicks in.
+///
+/// Space toggles auto-play, F cycles through the [`PlaySpeed`]s and T through the [`Tactic`]s.
+impl Plugin for IdlePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((AutoExplorePlugin, AutoBattlePlugin))
+            .init_resource::<AutoPlay>()
+            .add_systems(
+                Update,
+                (
+                    handle_input.run_if(not(in_state(PlayingState::Paused))),
+                    check_stop_rules,
+                )
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum PlaySpeed {
+    #[default]
+    X1,
+    X2,
+    X4,
+    /// Fights are resolved within a single frame
+    Instant,
+}
+
+impl PlaySpeed {
+    pub fn multiplier(&self) -> f32 {
+        match self {
+            PlaySpeed::X1 => 1.,
+            PlaySpeed::X2 => 2.,
+            PlaySpeed::X4 | PlaySpeed::Instant => 4.,
+        }
+    }
+
+    pub fn next(&self) -> PlaySpeed {
+        match self {
+            PlaySpeed::X1 => PlaySpeed::X2,
+            PlaySpeed::X2 => PlaySpeed::X4,
+            PlaySpeed::X4 => PlaySpeed::Instant,
+            PlaySpeed::Instant => PlaySpeed::X1,
+        }
+    }
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            PlaySpeed::X1 => "x1",
+            PlaySpeed::X2 => "x2",
+            PlaySpeed::X4 => "x4",
+            PlaySpeed::Instant => "instant",
+        }
+    }
+}
+
+/// How the party picks its actions when fighting on its own.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default, Serialize, Deserialize)]
+pub enum Tactic {
+    /// Always hit as hard as possible
+    Aggressive,
+    /// Defend when a member is badly hurt
+    #[default]
+    Balanced,
+    /// Defend early and run away from fights going wrong
+    Cautious,
+}
+
+impl Tactic {
+    pub fn next(&self) -> Tactic {
+        match self {
+            Tactic::Aggressive => Tactic::Balanced,
+            Tactic::Balanced => Tactic::Cautious,
+            Tactic::Cautious => Tactic::Aggressive,
+        }
+    }
+}
+
+/// Conditions that turn auto-play off so the player can take over.
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
+pub struct StopRules {
+    /// Stop when the party health ratio falls below this value
+    pub low_health: Option<f32>,
+    pub rare_loot: bool,
+    pub boss: bool,
+}
+
+impl Default for StopRules {
+    fn default() -> Self {
+        StopRules {
+            low_health: Some(0.3),
+            rare_loot: true,
+            boss: true,
+        }
+    }
+}
+
+#[derive(Resource)]
+pub struct AutoPlay {
+    pub enabled: bool,
+    pub speed: PlaySpeed,
+    pub tactic: Tactic,
+    pub stop_rules: StopRules,
+    /// Base delay between two automatic steps or combat turns, before the speed multiplier
+    pub step: Duration,
+}
+
+impl Default for AutoPlay {
+    fn default() -> Self {
+        AutoPlay {
+            enabled: false,
+            speed: PlaySpeed::default(),
+            tactic: Tactic::default(),
+            stop_rules: StopRules::default(),
+            step: AutoPlay::BASE_STEP,
+        }
+    }
+}
+
+impl AutoPlay {
+    /// Delay between two steps before any upgrade
+    pub const BASE_STEP: Duration = Duration::from_millis(600);
+
+    /// Ticks `timer` by the time elapsed this frame, sped up by the current [`PlaySpeed`].
+    pub fn tick(&self, timer: &mut Timer, time: &Time) -> bool {
+        timer.set_duration(self.step);
+        timer.tick(time.delta().mul_f32(self.speed.multiplier()));
+        timer.just_finished()
+    }
+
+    fn stop(&mut self, log: &mut CombatLog, reason: &str) {
+        if self.enabled {
+            self.enabled = false;
+            log.push(format!("Auto-play stopped: {reason}"));
+        }
+    }
+}
+
+/// Run condition for systems that only run while auto-play is on.
+pub fn auto_playing(auto_play: Res<AutoPlay>) -> bool {
+    auto_play.enabled
+}
+
+fn handle_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut auto_play: ResMut<AutoPlay>,
+    mut log: ResMut<CombatLog>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Space) {
+        auto_play.enabled = !auto_play.enabled;
+        log.push(if auto_play.enabled {
+            "Auto-play started"
+        } else {
+            "Auto-play paused"
+        });
+    } else if keyboard_input.just_pressed(KeyCode::F) {
+        auto_play.speed = auto_play.speed.next();
+    } else if keyboard_input.just_pressed(KeyCode::T) {
+        auto_play.tactic = auto_play.tactic.next();
+        log.push(format!("Party tactic: {:?}", auto_play.tactic));
+    }
+}
+
+fn check_stop_rules(
+    mut auto_play: ResMut<AutoPlay>,
+    mut log: ResMut<CombatLog>,
+    mut loot: EventReader<LootDropped>,
+    party: Res<Party>,
+    encounter: Option<Res<ActiveEncounter>>,
+    monsters: Query<&Monster>,
+) {
+    let rules = auto_play.stop_rules;
+    if let Some(threshold) = rules.low_health {
+        if party.is_changed() && party.health_ratio() < threshold {
+            auto_play.stop(&mut log, "the party is badly hurt");
+        }
+    }
+    if rules.rare_loot && loot.read().any(|drop| drop.rare) {
+        auto_play.stop(&mut log, "rare loot found");
+    }
+    if let Some(encounter) = encounter {
+        let boss = || {
+            encounter
+                .monsters
+                .iter()
+                .any(|&entity| monsters.get(entity).map_or(false, |monster| monster.boss))
+        };
+        if rules.boss && encounter.is_added() && boss() {
+            auto_play.stop(&mut log, "a boss blocks the way");
+        }
+    }
+}
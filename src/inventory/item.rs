@@ -0,0 +1,184 @@
+use bevy::{asset::Asset, reflect::TypePath};
+use serde::{Deserialize, Serialize};
+
+use crate::combat::sim::Stats;
+
+/// Every item of the game, loaded from `data/base.items.ron`.
+#[derive(Deserialize, Asset, TypePath)]
+pub struct ItemDatabase {
+    pub items: Vec<ItemDef>,
+}
+
+impl ItemDatabase {
+    pub fn get(&self, id: &str) -> Option<&ItemDef> {
+        self.items.iter().find(|item| item.id == id)
+    }
+
+    /// Display name of an item with its affixes, falling back to the id for items missing from
+    /// the database.
+    pub fn name(&self, item: &Item) -> String {
+        let base = self
+            .get(&item.id)
+            .map_or(item.id.as_str(), |def| def.name.as_str());
+        let mut name = base.to_string();
+        if let Some(prefix) = &item.prefix {
+            name = format!("{} {name}", prefix.name);
+        }
+        if let Some(suffix) = &item.suffix {
+            name = format!("{name} {}", suffix.name);
+        }
+        name
+    }
+}
+
+/// An item carried or worn by the party, items of the same kind may have different affixes.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Item {
+    pub id: String,
+    pub prefix: Option<Affix>,
+    pub suffix: Option<Affix>,
+}
+
+impl Item {
+    pub fn new(id: impl Into<String>) -> Self {
+        Item {
+            id: id.into(),
+            prefix: None,
+            suffix: None,
+        }
+    }
+
+    pub fn affixes(&self) -> impl Iterator<Item = &Affix> {
+        self.prefix.iter().chain(self.suffix.iter())
+    }
+}
+
+/// A random modifier rolled with the item, its bonus adds to the one of the item.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+pub struct Affix {
+    pub name: String,
+    pub bonus: Bonus,
+}
+
+#[derive(Deserialize)]
+pub struct ItemDef {
+    /// Identifier used in saves and other data files
+    pub id: String,
+    pub name: String,
+    pub kind: ItemKind,
+    /// Stat changes applied while the item is equipped
+    #[serde(default)]
+    pub bonus: Bonus,
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ItemKind {
+    Weapon,
+    Armour,
+    Accessory,
+    /// Used up to restore hit points and mana
+    Consumable {
+        hp: i32,
+        mp: i32,
+    },
+    /// Opens locked doors, cannot be equipped
+    Key,
+}
+
+impl ItemKind {
+    /// Equipment slot the item goes in, if it can be equipped at all.
+    pub fn slot(&self) -> Option<EquipSlot> {
+        match self {
+            ItemKind::Weapon => Some(EquipSlot::Weapon),
+            ItemKind::Armour => Some(EquipSlot::Armour),
+            ItemKind::Accessory => Some(EquipSlot::Accessory),
+            ItemKind::Consumable { .. } | ItemKind::Key => None,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+#[serde(default)]
+pub struct Bonus {
+    pub attack: i32,
+    pub defense: i32,
+    pub magic: i32,
+    pub speed: i32,
+    pub max_hp: i32,
+    pub max_mp: i32,
+}
+
+impl Bonus {
+    pub fn apply(&self, stats: &mut Stats) {
+        stats.attack += self.attack;
+        stats.defense += self.defense;
+        stats.magic += self.magic;
+        stats.speed += self.speed;
+        stats.max_hp += self.max_hp;
+        stats.max_mp += self.max_mp;
+    }
+
+    /// Multiplies every field, rounding to the nearest integer.
+    pub fn scaled(&self, factor: f32) -> Bonus {
+        let scale = |value: i32| (value as f32 * factor).round() as i32;
+        Bonus {
+            attack: scale(self.attack),
+            defense: scale(self.defense),
+            magic: scale(self.magic),
+            speed: scale(self.speed),
+            max_hp: scale(self.max_hp),
+            max_mp: scale(self.max_mp),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EquipSlot {
+    Weapon,
+    Armour,
+    Accessory,
+}
+
+impl EquipSlot {
+    pub const ALL: [EquipSlot; 3] = [EquipSlot::Weapon, EquipSlot::Armour, EquipSlot::Accessory];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EquipSlot::Weapon => "weapon",
+            EquipSlot::Armour => "armour",
+            EquipSlot::Accessory => "trinket",
+        }
+    }
+}
+
+/// Items worn by a character.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug, Default)]
+pub struct Equipment {
+    pub weapon: Option<Item>,
+    pub armour: Option<Item>,
+    pub accessory: Option<Item>,
+}
+
+impl Equipment {
+    pub fn get(&self, slot: EquipSlot) -> &Option<Item> {
+        match slot {
+            EquipSlot::Weapon => &self.weapon,
+            EquipSlot::Armour => &self.armour,
+            EquipSlot::Accessory => &self.accessory,
+        }
+    }
+
+    pub fn get_mut(&mut self, slot: EquipSlot) -> &mut Option<Item> {
+        match slot {
+            EquipSlot::Weapon => &mut self.weapon,
+            EquipSlot::Armour => &mut self.armour,
+            EquipSlot::Accessory => &mut self.accessory,
+        }
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &Item> {
+        EquipSlot::ALL
+            .into_iter()
+            .filter_map(|slot| self.get(slot).as_ref())
+    }
+}
@@ -0,0 +1,435 @@
+pub mod item;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::LootDropped,
+    dungeon::{
+        camera3d::{GridPosition, Player, DUNGEON_CAMERA_LAYER},
+        cell_center,
+        config::DungeonConfig,
+        labyrinth::Labyrinth,
+    },
+    loading::GameData,
+    message::GameMessage,
+    party::{class::ClassTable, Party},
+    settings::{Action, Settings},
+    GameState, PlayingEntity, PlayingState,
+};
+
+use self::item::{EquipSlot, Item, ItemDatabase, ItemKind};
+
+pub struct InventoryPlugin;
+
+/// This plugin holds the items carried by the party and the ones lying in the dungeon, which are
+/// picked up with E. Items on the floor are kept in the cells of the labyrinth, so they are saved
+/// with it. Loot that does not fit in the inventory is left on the floor.
+impl Plugin for InventoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Inventory>()
+            .add_systems(OnExit(GameState::Loading), setup_pickup_assets)
+            .add_systems(
+                Update,
+                pick_up
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(in_state(PlayingState::Exploring)),
+            )
+            .add_systems(
+                Update,
+                (
+                    store_loot,
+                    spawn_pickups.run_if(resource_exists_and_changed::<Labyrinth>()),
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// Number of items the party can carry, equipped ones excluded.
+pub const INVENTORY_SIZE: usize = 12;
+
+/// Items carried by the party. Slots keep their place so the player can arrange them.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Inventory {
+    pub slots: Vec<Option<Item>>,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Inventory {
+            slots: vec![None; INVENTORY_SIZE],
+        }
+    }
+}
+
+impl Inventory {
+    /// Puts the item in the first free slot, gives it back when the inventory is full.
+    pub fn add(&mut self, item: Item) -> Result<usize, Item> {
+        match self.slots.iter().position(Option::is_none) {
+            Some(index) => {
+                self.slots[index] = Some(item);
+                Ok(index)
+            }
+            None => Err(item),
+        }
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.slots.iter().all(Option::is_some)
+    }
+}
+
+/// Somewhere an item can be moved from or to.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ItemLocation {
+    Inventory(usize),
+    Equipment { member: usize, slot: EquipSlot },
+}
+
+fn slot_mut<'a>(
+    inventory: &'a mut Inventory,
+    party: &'a mut Party,
+    location: ItemLocation,
+) -> Option<&'a mut Option<Item>> {
+    match location {
+        ItemLocation::Inventory(index) => inventory.slots.get_mut(index),
+        ItemLocation::Equipment { member, slot } => party
+            .members
+            .get_mut(member)
+            .map(|character| character.equipment.get_mut(slot)),
+    }
+}
+
+pub fn item_at<'a>(
+    inventory: &'a Inventory,
+    party: &'a Party,
+    location: ItemLocation,
+) -> Option<&'a Item> {
+    match location {
+        ItemLocation::Inventory(index) => inventory.slots.get(index)?.as_ref(),
+        ItemLocation::Equipment { member, slot } => {
+            party.members.get(member)?.equipment.get(slot).as_ref()
+        }
+    }
+}
+
+/// Whether the item may be put at `location`.
+fn fits(items: &ItemDatabase, item: Option<&Item>, location: ItemLocation) -> bool {
+    match (item, location) {
+        (None, _) | (_, ItemLocation::Inventory(_)) => true,
+        (Some(item), ItemLocation::Equipment { slot, .. }) => items
+            .get(&item.id)
+            .map_or(false, |item| item.kind.slot() == Some(slot)),
+    }
+}
+
+/// Swaps the contents of two locations, equipping or unequipping items on the way.
+pub fn move_item(
+    inventory: &mut Inventory,
+    party: &mut Party,
+    classes: &ClassTable,
+    items: &ItemDatabase,
+    from: ItemLocation,
+    to: ItemLocation,
+) -> Result<(), &'static str> {
+    if from == to {
+        return Ok(());
+    }
+    let moved = item_at(inventory, party, from).cloned();
+    let swapped = item_at(inventory, party, to).cloned();
+    if moved.is_none() {
+        return Err("there is nothing to move");
+    }
+    if !fits(items, moved.as_ref(), to) || !fits(items, swapped.as_ref(), from) {
+        return Err("the item does not go there");
+    }
+
+    for (location, item) in [(from, swapped), (to, moved)] {
+        if let Some(slot) = slot_mut(inventory, party, location) {
+            *slot = item;
+        }
+    }
+    party.refresh(classes, items);
+    Ok(())
+}
+
+/// Uses a consumable from the inventory on the most hurt member still standing.
+pub fn use_item(
+    inventory: &mut Inventory,
+    party: &mut Party,
+    items: &ItemDatabase,
+    index: usize,
+) -> Option<String> {
+    let id = &inventory.slots.get(index)?.as_ref()?.id;
+    let item = items.get(id)?;
+    let ItemKind::Consumable { hp, mp } = item.kind else {
+        return None;
+    };
+    let target = party
+        .members
+        .iter_mut()
+        .filter(|member| member.is_alive())
+        .min_by(|a, b| a.health_ratio().total_cmp(&b.health_ratio()))?;
+
+    let stats = target.stats();
+    target.hp = (target.hp + hp).min(stats.max_hp);
+    target.mp = (target.mp + mp).min(stats.max_mp);
+    let message = format!("{} used a {}", target.name, item.name);
+    inventory.slots[index] = None;
+    Some(message)
+}
+
+/// An item lying on the floor of a cell.
+#[derive(Component)]
+pub struct Pickup(pub Item);
+
+/// Mesh and material shared by every [`Pickup`].
+#[derive(Resource)]
+struct PickupAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+}
+
+fn setup_pickup_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    config: Res<DungeonConfig>,
+) {
+    commands.insert_resource(PickupAssets {
+        mesh: meshes.add(Mesh::from(shape::Box::new(
+            config.size * 0.25,
+            config.size * 0.02,
+            config.size * 0.25,
+        ))),
+        material: materials.add(StandardMaterial {
+            base_color: Color::rgb(0.8, 0.65, 0.2),
+            metallic: 0.4,
+            ..default()
+        }),
+    });
+}
+
+/// Shows the items lying in the labyrinth, replacing the ones shown before.
+fn spawn_pickups(
+    mut commands: Commands,
+    assets: Res<PickupAssets>,
+    config: Res<DungeonConfig>,
+    labyrinth: Res<Labyrinth>,
+    pickups: Query<Entity, With<Pickup>>,
+) {
+    for entity in pickups.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    for (&cell, contents) in labyrinth.cells.iter() {
+        for item in &contents.items {
+            // Lay the item flat on the floor, half a cell below the eye level
+            let translation = cell_center(config.size, cell) - Vec3::Y * config.size * 0.49;
+            commands.spawn((
+                PbrBundle {
+                    mesh: assets.mesh.clone(),
+                    material: assets.material.clone(),
+                    transform: Transform::from_translation(translation),
+                    ..default()
+                },
+                GridPosition(cell.0, cell.1),
+                Name::new(format!("pickup_{}", item.id)),
+                Pickup(item.clone()),
+                DUNGEON_CAMERA_LAYER,
+                PlayingEntity,
+            ));
+        }
+    }
+}
+
+/// Picks up every item on the player's cell when the interact key is pressed.
+pub fn pick_up(
+    keyboard_input: Res<Input<KeyCode>>,
+    settings: Res<Settings>,
+    mut inventory: ResMut<Inventory>,
+    mut labyrinth: ResMut<Labyrinth>,
+    mut messages: EventWriter<GameMessage>,
+    data: GameData,
+    player: Query<&GridPosition, With<Player>>,
+) {
+    if !keyboard_input.just_pressed(settings.keys.key(Action::Interact)) {
+        return;
+    }
+    let (Ok(position), Some(items)) = (player.get_single(), data.items()) else {
+        return;
+    };
+    // Only borrowed mutably once something is taken, a change rebuilds the floor's content
+    let coordinates = (position.0, position.1);
+    if labyrinth
+        .cells
+        .get(&coordinates)
+        .map_or(true, |cell| cell.items.is_empty())
+    {
+        return;
+    }
+    if inventory.is_full() {
+        messages.send(GameMessage::warning("The inventory is full"));
+        return;
+    }
+    let Some(cell) = labyrinth.cells.get_mut(&coordinates) else {
+        return;
+    };
+    while let Some(item) = cell.items.pop() {
+        let name = items.name(&item);
+        match inventory.add(item) {
+            Ok(_) => messages.send(GameMessage::success(format!("Picked up {name}"))),
+            Err(item) => {
+                messages.send(GameMessage::warning("The inventory is full"));
+                cell.items.push(item);
+                break;
+            }
+        }
+    }
+}
+
+/// Puts the items dropped by monsters in the inventory, or on the floor when it is full.
+fn store_loot(
+    mut loot: EventReader<LootDropped>,
+    mut inventory: ResMut<Inventory>,
+    mut labyrinth: ResMut<Labyrinth>,
+    mut messages: EventWriter<GameMessage>,
+    data: GameData,
+    player: Query<&GridPosition, With<Player>>,
+) {
+    let Some(items) = data.items() else {
+        return;
+    };
+    for drop in loot.read() {
+        for item in &drop.items {
+            let name = items.name(item);
+            match inventory.add(item.clone()) {
+                Ok(_) => messages.send(GameMessage::success(format!("Found {name}"))),
+                Err(item) => {
+                    messages.send(GameMessage::warning(format!(
+                        "No room for {name}, it was left on the floor"
+                    )));
+                    if let Some(cell) = player
+                        .get_single()
+                        .ok()
+                        .and_then(|position| labyrinth.cells.get_mut(&(position.0, position.1)))
+                    {
+                        cell.items.push(item);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::party::{character::Attributes, starting_members};
+
+    fn tables() -> (ClassTable, ItemDatabase) {
+        let classes = ron::from_str(include_str!("../../assets/data/party.classes.ron")).unwrap();
+        let items = ron::from_str(include_str!("../../assets/data/base.items.ron")).unwrap();
+        (classes, items)
+    }
+
+    fn party(classes: &ClassTable, items: &ItemDatabase) -> Party {
+        Party {
+            members: starting_members(classes, items, &Attributes::default()),
+            gold: 0,
+        }
+    }
+
+    const WEAPON: ItemLocation = ItemLocation::Equipment {
+        member: 0,
+        slot: EquipSlot::Weapon,
+    };
+
+    #[test]
+    fn a_full_inventory_gives_the_item_back() {
+        let mut inventory = Inventory::default();
+        for index in 0..INVENTORY_SIZE {
+            assert_eq!(inventory.add(Item::new("healing_herb")), Ok(index));
+        }
+        assert!(inventory.is_full());
+        assert_eq!(
+            inventory.add(Item::new("short_sword")),
+            Err(Item::new("short_sword"))
+        );
+        // A freed slot is filled first
+        inventory.slots[3] = None;
+        assert_eq!(inventory.add(Item::new("short_sword")), Ok(3));
+    }
+
+    #[test]
+    fn moving_gear_equips_and_swaps_it() {
+        let (classes, items) = tables();
+        let mut party = party(&classes, &items);
+        let mut inventory = Inventory::default();
+        inventory.add(Item::new("short_sword")).unwrap();
+        inventory.add(Item::new("rusty_dagger")).unwrap();
+        let attack = party.members[0].stats().attack;
+
+        let from = ItemLocation::Inventory(0);
+        move_item(&mut inventory, &mut party, &classes, &items, from, WEAPON).unwrap();
+        assert_eq!(inventory.slots[0], None);
+        assert_eq!(
+            item_at(&inventory, &party, WEAPON),
+            Some(&Item::new("short_sword"))
+        );
+        assert_eq!(party.members[0].stats().attack, attack + 4);
+
+        let from = ItemLocation::Inventory(1);
+        move_item(&mut inventory, &mut party, &classes, &items, from, WEAPON).unwrap();
+        assert_eq!(inventory.slots[1], Some(Item::new("short_sword")));
+        assert_eq!(
+            item_at(&inventory, &party, WEAPON),
+            Some(&Item::new("rusty_dagger"))
+        );
+    }
+
+    #[test]
+    fn items_only_go_where_they_fit() {
+        let (classes, items) = tables();
+        let mut party = party(&classes, &items);
+        let mut inventory = Inventory::default();
+        inventory.add(Item::new("healing_herb")).unwrap();
+        let herb = ItemLocation::Inventory(0);
+        let empty = ItemLocation::Inventory(5);
+        assert_eq!(
+            move_item(&mut inventory, &mut party, &classes, &items, herb, WEAPON),
+            Err("the item does not go there")
+        );
+        assert_eq!(
+            move_item(&mut inventory, &mut party, &classes, &items, empty, herb),
+            Err("there is nothing to move")
+        );
+        assert_eq!(inventory.slots[0], Some(Item::new("healing_herb")));
+        assert_eq!(item_at(&inventory, &party, WEAPON), None);
+    }
+
+    #[test]
+    fn a_consumable_heals_the_most_hurt_member() {
+        let (classes, items) = tables();
+        let mut party = party(&classes, &items);
+        let mut inventory = Inventory::default();
+        inventory.add(Item::new("healing_herb")).unwrap();
+        inventory.add(Item::new("short_sword")).unwrap();
+        party.members[0].hp -= 2;
+        party.members[1].hp -= 8;
+        let max_hp = party.members[1].stats().max_hp;
+
+        let message = use_item(&mut inventory, &mut party, &items, 0).unwrap();
+        assert_eq!(
+            message,
+            format!("{} used a Healing herb", party.members[1].name)
+        );
+        assert_eq!(party.members[1].hp, max_hp);
+        assert_eq!(inventory.slots[0], None);
+
+        // Gear cannot be used
+        assert_eq!(use_item(&mut inventory, &mut party, &items, 1), None);
+        assert_eq!(inventory.slots[1], Some(Item::new("short_sword")));
+    }
+}
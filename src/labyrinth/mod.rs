@@ -1,11 +1,28 @@
 use bevy::{
     core_pipeline::clear_color::ClearColorConfig,
     prelude::*,
-    render::{camera::RenderTarget, view::RenderLayers},
+    render::{
+        camera::{RenderTarget, ScalingMode},
+        view::RenderLayers,
+    },
+};
+use bevy_ecs_ldtk::{
+    ldtk::{FieldInstance, FieldValue},
+    EntityInstance, LdtkPlugin, LdtkWorldBundle, LevelSelection,
+};
+
+use crate::{
+    dungeon::{
+        camera3d::CameraDirection,
+        feature::{Feature, FeatureKind},
+        labyrinth::{CurrentFloor, Labyrinth},
+        Position,
+    },
+    ui::HUDRenderViews,
+    GameState, PlayingEntity,
 };
-use bevy_ecs_ldtk::{LdtkPlugin, LdtkWorldBundle, LevelSelection};
 
-use crate::{ui::HUDRenderViews, GameState};
+pub mod project;
 
 pub struct DungeonLabyrinthPlugin;
 
@@ -19,6 +36,8 @@ impl Plugin for DungeonLabyrinthPlugin {
                     // Setup
                     setup_minimap.run_if(resource_added::<HUDRenderViews>()),
                     // Update
+                    author_features,
+                    draw_walls.after(author_features),
                 )
                     .run_if(in_state(GameState::Playing)),
             );
@@ -34,6 +53,9 @@ pub const LABYRINTH_LAYER: RenderLayers = RenderLayers::layer(0);
 #[derive(Component)]
 pub struct Minimap;
 
+/// Height of the minimap at a scale of 1, in pixels of the LDtk level
+const MINIMAP_HEIGHT: f32 = 241.;
+
 pub fn setup_minimap(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
@@ -42,13 +64,20 @@ pub fn setup_minimap(
 ) {
     if let Ok(window) = window.get_single() {
         let mut camera = Camera2dBundle::default();
+        // Shows the same part of the level whatever the size the render target is given
+        camera.projection.scaling_mode = ScalingMode::FixedVertical(MINIMAP_HEIGHT);
         camera.projection.scale = 2.;
         camera.transform.translation.x += window.resolution.height() / 4.0;
         camera.transform.translation.y += window.resolution.width() / 4.0;
         camera.camera.order = 2;
         camera.camera.target = RenderTarget::Image(config.minimap_handle.clone());
         camera.camera_2d.clear_color = ClearColorConfig::Custom(Color::BLACK);
-        commands.spawn((camera, LabyrinthCamera2D, UiCameraConfig { show_ui: false }));
+        commands.spawn((
+            camera,
+            LabyrinthCamera2D,
+            UiCameraConfig { show_ui: false },
+            PlayingEntity,
+        ));
 
         commands.spawn((
             LdtkWorldBundle {
@@ -56,7 +85,185 @@ pub fn setup_minimap(
                 ..Default::default()
             },
             Minimap,
+            PlayingEntity,
             //LABYRINTH_LAYER,
         ));
     }
 }
+
+#[derive(Component)]
+struct MinimapWall;
+
+/// Draws the walls of the labyrinth over the LDtk level, so that the minimap follows the walls
+/// opened or closed while playing. Cells are laid out around the `Entrance` entity.
+fn draw_walls(
+    mut commands: Commands,
+    labyrinth: Res<Labyrinth>,
+    instances: Query<(&EntityInstance, Ref<GlobalTransform>)>,
+    walls: Query<Entity, With<MinimapWall>>,
+) {
+    let Some((_, entrance)) = instances
+        .iter()
+        .find(|(instance, _)| instance.identifier == "Entrance")
+    else {
+        return;
+    };
+    if !labyrinth.is_changed() && !entrance.is_changed() {
+        return;
+    }
+    for entity in walls.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    let origin = entrance.translation().truncate();
+    let cell = CELL_PIXELS as f32;
+    let thickness = 4.;
+    for (&(x, z), walls) in labyrinth.cells.iter() {
+        let center = origin + Vec2::new(x as f32, z as f32) * cell;
+        for (position, exists) in walls.walls {
+            let (offset, size) = match position {
+                Position::Center => (Vec2::Y, Vec2::new(cell, thickness)),
+                Position::Back => (Vec2::NEG_Y, Vec2::new(cell, thickness)),
+                Position::Right => (Vec2::X, Vec2::new(thickness, cell)),
+                Position::Left => (Vec2::NEG_X, Vec2::new(thickness, cell)),
+                Position::Floor | Position::Ceiling => continue,
+            };
+            if !exists {
+                continue;
+            }
+            commands.spawn((
+                SpriteBundle {
+                    sprite: Sprite {
+                        color: Color::rgb(0.85, 0.8, 0.6),
+                        custom_size: Some(size),
+                        ..default()
+                    },
+                    transform: Transform::from_translation(
+                        (center + offset * cell / 2.).extend(10.),
+                    ),
+                    ..default()
+                },
+                MinimapWall,
+                PlayingEntity,
+            ));
+        }
+    }
+}
+
+/// Size of a labyrinth cell in the LDtk level, in pixels
+const CELL_PIXELS: i32 = 64;
+/// Size of a cell of the entity layer, which point fields are counted in
+const ENTITY_GRID_PIXELS: i32 = 16;
+
+/// Places the features authored as LDtk entities in the first floor. Cells are counted from the
+/// `Entrance` entity, which stands on `(0, 0)`.
+///
+/// Entities are named after the [`FeatureKind`]s. `Spikes` has a `damage` int, `Teleporter` a
+/// `to` point and `PressurePlate` a `door` point and a `side` string (North, East...). Any of
+/// them can be `hidden`, with a `difficulty` int.
+fn author_features(
+    mut labyrinth: ResMut<Labyrinth>,
+    floor: Res<CurrentFloor>,
+    added: Query<&EntityInstance, Added<EntityInstance>>,
+    instances: Query<&EntityInstance>,
+) {
+    if floor.0 != 1 || added.is_empty() {
+        return;
+    }
+    let Some(entrance) = instances
+        .iter()
+        .find(|instance| instance.identifier == "Entrance")
+    else {
+        return;
+    };
+    let to_cell = |px: IVec2| {
+        let offset = (px - entrance.px) / CELL_PIXELS;
+        // LDtk counts rows downwards, the labyrinth northwards
+        (offset.x, -offset.y)
+    };
+    let grid_to_cell = |grid: IVec2| to_cell(grid * ENTITY_GRID_PIXELS);
+
+    for instance in added.iter() {
+        let Some(feature) = feature_from_ldtk(instance, grid_to_cell) else {
+            continue;
+        };
+        let cell = to_cell(instance.px);
+        if let FeatureKind::Teleporter { to } = feature.kind {
+            if !labyrinth.cells.contains_key(&to) {
+                warn!("Teleporter at {cell:?} leads outside the labyrinth, to {to:?}");
+                continue;
+            }
+        }
+        match labyrinth.cells.get_mut(&cell) {
+            Some(cell) => cell.feature = Some(feature),
+            None => warn!(
+                "{} authored outside the labyrinth at {cell:?}",
+                instance.identifier
+            ),
+        }
+    }
+}
+
+/// Reads a feature from an LDtk entity, `to_cell` turning its point fields into cells.
+fn feature_from_ldtk(
+    instance: &EntityInstance,
+    to_cell: impl Fn(IVec2) -> (i32, i32),
+) -> Option<Feature> {
+    let fields = &instance.field_instances;
+    let kind = match instance.identifier.as_str() {
+        "Spikes" => FeatureKind::Spikes {
+            damage: int_field(fields, "damage").unwrap_or(3),
+        },
+        "Pit" => FeatureKind::Pit,
+        "Stairs" => FeatureKind::Stairs,
+        "Teleporter" => FeatureKind::Teleporter {
+            to: to_cell(point_field(fields, "to")?),
+        },
+        "Spinner" => FeatureKind::Spinner,
+        "PressurePlate" => FeatureKind::PressurePlate {
+            door: to_cell(point_field(fields, "door")?),
+            side: match string_field(fields, "side")?.as_str() {
+                "North" => CameraDirection::North,
+                "East" => CameraDirection::East,
+                "South" => CameraDirection::South,
+                "West" => CameraDirection::West,
+                _ => return None,
+            },
+        },
+        _ => return None,
+    };
+    let hidden = matches!(field(fields, "hidden"), Some(FieldValue::Bool(true)));
+    Some(if hidden {
+        Feature::hidden(kind, int_field(fields, "difficulty").unwrap_or(10))
+    } else {
+        Feature::new(kind)
+    })
+}
+
+fn field<'a>(fields: &'a [FieldInstance], name: &str) -> Option<&'a FieldValue> {
+    fields
+        .iter()
+        .find(|field| field.identifier == name)
+        .map(|field| &field.value)
+}
+
+fn int_field(fields: &[FieldInstance], name: &str) -> Option<i32> {
+    match field(fields, name)? {
+        FieldValue::Int(value) => *value,
+        _ => None,
+    }
+}
+
+fn point_field(fields: &[FieldInstance], name: &str) -> Option<IVec2> {
+    match field(fields, name)? {
+        FieldValue::Point(value) => *value,
+        _ => None,
+    }
+}
+
+fn string_field(fields: &[FieldInstance], name: &str) -> Option<String> {
+    match field(fields, name)? {
+        FieldValue::String(value) | FieldValue::Enum(value) => value.clone(),
+        _ => None,
+    }
+}
@@ -0,0 +1,436 @@
+// Labyrinths as LDtk projects, so that generated or edited floors can be polished in LDtk.
+//
+// A project is written from a template, `insectivore.ldtk`, whose definitions are kept: floor
+// cells are `1` in the `IntGrid` layer, and the `Entities` layer holds the `Entrance`, a `Wall`
+// for every wall standing between two cells, and the features named after their kind. Walls
+// across the level are 64x1, walls up and down it are 1x64.
+//
+// Themes, decorations and missing floors or ceilings have no place in LDtk and are left out.
+
+use bevy::{prelude::IVec2, utils::HashMap};
+use bevy_ecs_ldtk::EntityInstance;
+use rand::Rng;
+use serde_json::{json, Value};
+
+use super::{feature_from_ldtk, CELL_PIXELS, ENTITY_GRID_PIXELS};
+use crate::dungeon::{
+    camera3d::CameraDirection,
+    feature::{Feature, FeatureKind},
+    format::{check_teleporters, FormatError},
+    labyrinth::{Cell, Labyrinth},
+};
+
+const FLOOR: i64 = 1;
+const ENTITY_SIZE: i32 = 32;
+/// Entities stand in the middle of their cell, like the template's `Entrance`
+const ENTITY_OFFSET: i32 = (CELL_PIXELS - ENTITY_SIZE) / 2;
+
+/// Turns `template` into a project holding `labyrinth` as its only level.
+pub fn to_ldtk(labyrinth: &Labyrinth, template: &str) -> Result<String, FormatError> {
+    let mut project: Value = serde_json::from_str(template).map_err(FormatError::Json)?;
+    let level = project["levels"]
+        .get(0)
+        .cloned()
+        .ok_or(FormatError::Shape("the template has no level"))?;
+    let entrance = find_entity(&level, "Entrance")
+        .cloned()
+        .ok_or(FormatError::Shape("the template has no Entrance"))?;
+
+    let (min_x, max_z, width, height) = bounds(labyrinth);
+    // Rows are counted downwards in LDtk, northwards in the labyrinth
+    let to_grid = |(x, z): (i32, i32)| (x - min_x, max_z - z);
+    let px = |cell: (i32, i32)| {
+        let (column, row) = to_grid(cell);
+        IVec2::new(column, row) * CELL_PIXELS
+    };
+
+    let mut entities = vec![];
+    let mut place = |instance: Value, px: IVec2| {
+        entities.push(with_position(instance, px, &level));
+    };
+
+    let mut entrance = entrance;
+    entrance["iid"] = json!(iid());
+    place(entrance, px(labyrinth.entrance) + ENTITY_OFFSET);
+
+    let mut cells: Vec<_> = labyrinth.cells.iter().collect();
+    cells.sort_by_key(|(coordinates, _)| **coordinates);
+    for &(&coordinates, cell) in &cells {
+        for direction in [CameraDirection::North, CameraDirection::East] {
+            let (dx, dz) = direction.offset();
+            let neighbour = (coordinates.0 + dx, coordinates.1 + dz);
+            if !labyrinth.cells.contains_key(&neighbour)
+                || labyrinth.can_move(coordinates, direction)
+            {
+                continue;
+            }
+            let (wall, at) = if direction == CameraDirection::North {
+                (wall(&mut project, CELL_PIXELS, 1), px(coordinates))
+            } else {
+                (wall(&mut project, 1, CELL_PIXELS), px(neighbour))
+            };
+            place(wall, at);
+        }
+        if let Some(feature) = &cell.feature {
+            let to_point = |cell: (i32, i32)| {
+                let at = (px(cell) + ENTITY_OFFSET) / ENTITY_GRID_PIXELS;
+                json!({ "cx": at.x, "cy": at.y })
+            };
+            let instance = feature_entity(&mut project, feature, to_point);
+            place(instance, px(coordinates) + ENTITY_OFFSET);
+        }
+    }
+
+    let mut floor = vec![0; (width * height) as usize];
+    for &coordinates in labyrinth.cells.keys() {
+        let (column, row) = to_grid(coordinates);
+        floor[(row * width + column) as usize] = FLOOR;
+    }
+
+    let mut level = level;
+    level["pxWid"] = json!(width * CELL_PIXELS);
+    level["pxHei"] = json!(height * CELL_PIXELS);
+    level["__neighbours"] = json!([]);
+    for layer in level["layerInstances"]
+        .as_array_mut()
+        .ok_or(FormatError::Shape("the template level has no layers"))?
+    {
+        let grid_size = layer["__gridSize"].as_i64().unwrap_or(CELL_PIXELS as i64) as i32;
+        layer["__cWid"] = json!(width * CELL_PIXELS / grid_size);
+        layer["__cHei"] = json!(height * CELL_PIXELS / grid_size);
+        // Painted tiles would no longer match the cells
+        layer["gridTiles"] = json!([]);
+        layer["autoLayerTiles"] = json!([]);
+        match layer["__identifier"].as_str() {
+            Some("IntGrid") => layer["intGridCsv"] = json!(floor),
+            Some("Entities") => layer["entityInstances"] = json!(entities),
+            _ => {}
+        }
+    }
+    project["levels"] = json!([level]);
+    project["toc"] = json!([]);
+
+    serde_json::to_string_pretty(&project).map_err(FormatError::Json)
+}
+
+/// Reads the first level of an LDtk project. Cells are counted from the `Entrance`, which stands
+/// on `(0, 0)`.
+pub fn parse_ldtk(text: &str) -> Result<Labyrinth, FormatError> {
+    let project: Value = serde_json::from_str(text).map_err(FormatError::Json)?;
+    let level = project["levels"]
+        .get(0)
+        .ok_or(FormatError::Shape("the project has no level"))?;
+    let layer = |identifier: &str| {
+        level["layerInstances"]
+            .as_array()
+            .and_then(|layers| {
+                layers
+                    .iter()
+                    .find(|layer| layer["__identifier"] == identifier)
+            })
+            .ok_or(FormatError::Shape("the level is missing a layer"))
+    };
+    let int_grid = layer("IntGrid")?;
+    let instances: Vec<EntityInstance> =
+        serde_json::from_value(layer("Entities")?["entityInstances"].clone())
+            .map_err(FormatError::Json)?;
+
+    let entrance = instances
+        .iter()
+        .find(|instance| instance.identifier == "Entrance")
+        .ok_or(FormatError::Shape("the level has no Entrance"))?;
+    let grid = |px: IVec2| IVec2::new(px.x.div_euclid(CELL_PIXELS), px.y.div_euclid(CELL_PIXELS));
+    let origin = grid(entrance.px);
+    let to_cell = |px: IVec2| {
+        let offset = grid(px) - origin;
+        (offset.x, -offset.y)
+    };
+
+    let mut labyrinth = Labyrinth {
+        cells: HashMap::default(),
+        entrance: (0, 0),
+    };
+    let width = int_grid["__cWid"].as_i64().unwrap_or(1).max(1);
+    for (index, value) in int_grid["intGridCsv"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .enumerate()
+    {
+        if value.as_i64() == Some(FLOOR) {
+            let at = IVec2::new((index as i64 % width) as i32, (index as i64 / width) as i32);
+            labyrinth
+                .cells
+                .insert(to_cell(at * CELL_PIXELS), Cell::closed());
+        }
+    }
+
+    // Neighbours are open to each other unless a wall stands between them
+    let coordinates: Vec<_> = labyrinth.cells.keys().copied().collect();
+    for cell in coordinates {
+        for direction in [CameraDirection::North, CameraDirection::East] {
+            let (dx, dz) = direction.offset();
+            if labyrinth.cells.contains_key(&(cell.0 + dx, cell.1 + dz)) {
+                labyrinth.set_wall(cell, direction, false);
+            }
+        }
+    }
+
+    let grid_to_cell = |grid: IVec2| to_cell(grid * ENTITY_GRID_PIXELS);
+    for instance in &instances {
+        if instance.identifier == "Wall" {
+            let direction = if instance.width >= instance.height {
+                CameraDirection::North
+            } else {
+                CameraDirection::West
+            };
+            labyrinth.set_wall(to_cell(instance.px), direction, true);
+            continue;
+        }
+        if let Some(feature) = feature_from_ldtk(instance, grid_to_cell) {
+            if let Some(cell) = labyrinth.cells.get_mut(&to_cell(instance.px)) {
+                cell.feature = Some(feature);
+            }
+        }
+    }
+    check_teleporters(&labyrinth)?;
+    Ok(labyrinth)
+}
+
+/// `min_x`, `max_z`, then the width and height of the labyrinth, in cells.
+fn bounds(labyrinth: &Labyrinth) -> (i32, i32, i32, i32) {
+    let cells = labyrinth
+        .cells
+        .keys()
+        .chain(std::iter::once(&labyrinth.entrance));
+    let (mut min_x, mut max_x, mut min_z, mut max_z) = (i32::MAX, i32::MIN, i32::MAX, i32::MIN);
+    for &(x, z) in cells {
+        min_x = min_x.min(x);
+        max_x = max_x.max(x);
+        min_z = min_z.min(z);
+        max_z = max_z.max(z);
+    }
+    (min_x, max_z, max_x - min_x + 1, max_z - min_z + 1)
+}
+
+fn find_entity<'a>(level: &'a Value, identifier: &str) -> Option<&'a Value> {
+    level["layerInstances"]
+        .as_array()?
+        .iter()
+        .flat_map(|layer| layer["entityInstances"].as_array().into_iter().flatten())
+        .find(|instance| instance["__identifier"] == identifier)
+}
+
+fn with_position(mut instance: Value, px: IVec2, level: &Value) -> Value {
+    let grid = px / ENTITY_GRID_PIXELS;
+    instance["px"] = json!([px.x, px.y]);
+    instance["__grid"] = json!([grid.x, grid.y]);
+    instance["__worldX"] = json!(level["worldX"].as_i64().unwrap_or(0) + px.x as i64);
+    instance["__worldY"] = json!(level["worldY"].as_i64().unwrap_or(0) + px.y as i64);
+    instance
+}
+
+fn wall(project: &mut Value, width: i32, height: i32) -> Value {
+    let definition = entity_definition(project, "Wall", &[]);
+    json!({
+        "__identifier": "Wall",
+        "__pivot": [0, 0],
+        "__tags": [],
+        "__tile": null,
+        "__smartColor": definition["color"],
+        "iid": iid(),
+        "width": width,
+        "height": height,
+        "defUid": definition["uid"],
+        "fieldInstances": [],
+    })
+}
+
+/// An entity for `feature`, adding its definition to the project when missing.
+fn feature_entity(
+    project: &mut Value,
+    feature: &Feature,
+    to_point: impl Fn((i32, i32)) -> Value,
+) -> Value {
+    let identifier = match feature.kind {
+        FeatureKind::Spikes { .. } => "Spikes",
+        FeatureKind::Pit => "Pit",
+        FeatureKind::Stairs => "Stairs",
+        FeatureKind::Teleporter { .. } => "Teleporter",
+        FeatureKind::Spinner => "Spinner",
+        FeatureKind::PressurePlate { .. } => "PressurePlate",
+    };
+    let mut fields = match &feature.kind {
+        FeatureKind::Spikes { damage } => vec![("damage", "Int", json!(damage))],
+        FeatureKind::Teleporter { to } => vec![("to", "Point", to_point(*to))],
+        FeatureKind::PressurePlate { door, side } => vec![
+            ("door", "Point", to_point(*door)),
+            ("side", "String", json!(format!("{side:?}"))),
+        ],
+        FeatureKind::Pit | FeatureKind::Stairs | FeatureKind::Spinner => vec![],
+    };
+    fields.push(("hidden", "Bool", json!(feature.hidden)));
+    fields.push(("difficulty", "Int", json!(feature.difficulty)));
+
+    let definition = entity_definition(project, identifier, &fields);
+    let field_instances: Vec<Value> = fields
+        .iter()
+        .map(|(name, kind, value)| {
+            let uid = definition["fieldDefs"]
+                .as_array()
+                .and_then(|defs| defs.iter().find(|def| def["identifier"] == *name))
+                .map_or(json!(null), |def| def["uid"].clone());
+            json!({
+                "__identifier": name,
+                "__type": kind,
+                "__value": value,
+                "__tile": null,
+                "defUid": uid,
+                "realEditorValues": [editor_value(kind, value)],
+            })
+        })
+        .collect();
+
+    json!({
+        "__identifier": identifier,
+        "__pivot": [0, 0],
+        "__tags": [],
+        "__tile": null,
+        "__smartColor": definition["color"],
+        "iid": iid(),
+        "width": ENTITY_SIZE,
+        "height": ENTITY_SIZE,
+        "defUid": definition["uid"],
+        "fieldInstances": field_instances,
+    })
+}
+
+/// The definition of the `identifier` entity, created with `fields` if the project has none.
+fn entity_definition(
+    project: &mut Value,
+    identifier: &str,
+    fields: &[(&str, &str, Value)],
+) -> Value {
+    let existing = project["defs"]["entities"]
+        .as_array()
+        .and_then(|defs| defs.iter().find(|def| def["identifier"] == identifier));
+    if let Some(definition) = existing {
+        return definition.clone();
+    }
+
+    let mut next_uid = || {
+        let uid = project["nextUid"].as_i64().unwrap_or(1000);
+        project["nextUid"] = json!(uid + 1);
+        uid
+    };
+    let uid = next_uid();
+    let field_defs: Vec<Value> = fields
+        .iter()
+        .map(|(name, kind, _)| {
+            json!({
+                "identifier": name,
+                "__type": kind,
+                "uid": next_uid(),
+                "type": format!("F_{kind}"),
+                "isArray": false,
+                "canBeNull": *kind == "Point",
+                "editorDisplayMode": "Hidden",
+                "editorDisplayPos": "Above",
+                "editorLinkStyle": "StraightArrow",
+                "editorAlwaysShow": false,
+                "editorShowInWorld": true,
+                "editorCutLongValues": true,
+                "useForSmartColor": false,
+                "exportToToc": false,
+                "searchable": false,
+                "symmetricalRef": false,
+                "autoChainRef": true,
+                "allowOutOfLevelRef": true,
+                "allowedRefs": "OnlySame",
+                "allowedRefTags": [],
+            })
+        })
+        .collect();
+    let definition = json!({
+        "identifier": identifier,
+        "uid": uid,
+        "tags": [],
+        "exportToToc": false,
+        "allowOutOfBounds": false,
+        "doc": null,
+        "width": ENTITY_SIZE,
+        "height": ENTITY_SIZE,
+        "resizableX": false,
+        "resizableY": false,
+        "keepAspectRatio": false,
+        "tileOpacity": 1,
+        "fillOpacity": 0.5,
+        "lineOpacity": 1,
+        "hollow": false,
+        "color": "#94B0C2",
+        "renderMode": "Rectangle",
+        "showName": true,
+        "tilesetId": null,
+        "tileRenderMode": "FitInside",
+        "tileRect": null,
+        "nineSliceBorders": [],
+        "maxCount": 0,
+        "limitScope": "PerLevel",
+        "limitBehavior": "MoveLastOne",
+        "pivotX": 0,
+        "pivotY": 0,
+        "fieldDefs": field_defs,
+    });
+    if let Some(defs) = project["defs"]["entities"].as_array_mut() {
+        defs.push(definition.clone());
+    }
+    definition
+}
+
+/// How LDtk itself stores a field value, which it reads instead of `__value`.
+fn editor_value(kind: &str, value: &Value) -> Value {
+    match kind {
+        "Int" => json!({ "id": "V_Int", "params": [value] }),
+        "Bool" => json!({ "id": "V_Bool", "params": [value] }),
+        "Point" => json!({
+            "id": "V_String",
+            "params": [format!("{},{}", value["cx"], value["cy"])],
+        }),
+        _ => json!({ "id": "V_String", "params": [value] }),
+    }
+}
+
+/// A random instance identifier, LDtk only wants them unique.
+fn iid() -> String {
+    let mut rng = rand::thread_rng();
+    format!(
+        "{:08x}-{:04x}-{:04x}-{:04x}-{:012x}",
+        rng.gen::<u32>(),
+        rng.gen::<u16>(),
+        rng.gen::<u16>(),
+        rng.gen::<u16>(),
+        rng.gen::<u64>() & 0xffff_ffff_ffff
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dungeon::{generate::generate, labyrinth::Theme};
+
+    const TEMPLATE: &str = include_str!("../../assets/insectivore.ldtk");
+
+    #[test]
+    fn an_exported_floor_imports_back() {
+        for seed in 0..5 {
+            let mut floor = generate(seed, 2 + seed as u32);
+            // What LDtk has no place for
+            for cell in floor.cells.values_mut() {
+                cell.theme = Theme::default();
+                cell.decoration = None;
+            }
+            let imported = parse_ldtk(&to_ldtk(&floor, TEMPLATE).unwrap()).unwrap();
+            assert_eq!(imported, floor, "seed {seed}");
+        }
+    }
+}
@@ -1,19 +1,42 @@
 #![allow(clippy::type_complexity)]
 
 mod audio;
+pub mod combat;
 mod dungeon;
+mod idle;
+mod inventory;
 mod labyrinth;
 mod loading;
+pub mod loot;
 mod menu;
+mod message;
+mod meta;
+mod offline;
+mod party;
+mod save;
+mod settings;
+mod storage;
 mod ui;
 
 use crate::audio::InternalAudioPlugin;
+use crate::combat::CombatPlugin;
+use crate::inventory::InventoryPlugin;
 use crate::loading::LoadingPlugin;
+use crate::loot::LootPlugin;
 use crate::menu::MenuPlugin;
+use crate::message::MessagePlugin;
+use crate::meta::MetaPlugin;
+use crate::offline::OfflineProgressPlugin;
+use crate::party::PartyPlugin;
+use crate::save::SavePlugin;
+use crate::settings::SettingsPlugin;
+
+pub use crate::inventory::item;
 
 use bevy::app::App;
 use bevy::prelude::*;
 use dungeon::DungeonPlugin;
+use idle::IdlePlugin;
 use labyrinth::DungeonLabyrinthPlugin;
 use ui::UIPlugin;
 
@@ -26,20 +49,61 @@ enum GameState {
     Playing,
     // Here the menu is drawn and waiting for player interaction
     Menu,
+    // Summary of the progress made while the game was closed, shown before playing
+    AwaySummary,
+    // Permanent upgrades and prestige, reached from the menu
+    Upgrades,
+    // Choosing the seed, difficulty and save slot of a new run
+    NewGame,
+    // List of the save slots that can be loaded
+    LoadGame,
+    // Who made the game and its assets
+    Credits,
+    // The party was defeated, the run is over
+    GameOver,
+}
+
+#[derive(States, Default, Clone, Eq, PartialEq, Debug, Hash)]
+enum PlayingState {
+    // The player moves freely through the dungeon
+    #[default]
+    Exploring,
+    // An encounter is being resolved, dungeon movement is suspended
+    Combat,
+    // The labyrinth is being edited, the player flies through walls
+    Editor,
+    // The pause menu is open and time stands still
+    Paused,
 }
 
 pub struct GamePlugin;
 
 impl Plugin for GamePlugin {
     fn build(&self, app: &mut App) {
-        app.add_state::<GameState>().add_plugins((
-            LoadingPlugin,
-            MenuPlugin,
-            InternalAudioPlugin,
-            DungeonPlugin,
-            DungeonLabyrinthPlugin,
-            UIPlugin,
-        ));
+        app.add_state::<GameState>()
+            .add_state::<PlayingState>()
+            .add_plugins((
+                LoadingPlugin,
+                MenuPlugin,
+                InternalAudioPlugin,
+                DungeonPlugin,
+                DungeonLabyrinthPlugin,
+                UIPlugin,
+                CombatPlugin,
+                PartyPlugin,
+                InventoryPlugin,
+                LootPlugin,
+                MetaPlugin,
+                IdlePlugin,
+                OfflineProgressPlugin,
+                SavePlugin,
+                MessagePlugin,
+            ))
+            .add_plugins(SettingsPlugin)
+            .add_systems(
+                OnExit(GameState::Playing),
+                (despawn_playing_entities, reset_playing_state),
+            );
 
         #[cfg(debug_assertions)]
         {
@@ -47,3 +111,138 @@ impl Plugin for GamePlugin {
         }
     }
 }
+
+/// Put on the top-level entities spawned for `GameState::Playing`, they are despawned along with
+/// their children when leaving it so that the game can be entered again from the menu.
+#[derive(Component)]
+pub struct PlayingEntity;
+
+fn despawn_playing_entities(mut commands: Commands, entities: Query<Entity, With<PlayingEntity>>) {
+    info!("despawning {} playing entities", entities.iter().len());
+    for entity in entities.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+/// Leaving the game while paused or fighting must not leave it so for the next time.
+fn reset_playing_state(mut next_state: ResMut<NextState<PlayingState>>) {
+    next_state.set(PlayingState::Exploring);
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{asset::AssetPlugin, input::InputPlugin};
+
+    use super::*;
+    use crate::{
+        combat::{LootDropped, MonsterPlugin},
+        dungeon::{
+            camera3d::PlayerMoved,
+            config::ConfigPlugin,
+            feature::FeaturePlugin,
+            format::parse_ron,
+            labyrinth::{Labyrinth, LabyrinthPlugin},
+            script::{Script, ScriptPlugin},
+        },
+        inventory::item::ItemDatabase,
+        loading::LabyrinthAssets,
+        loot::table::LootTables,
+        message::GameMessage,
+        meta::{run::Run, upgrade::UpgradeTree},
+        party::{class::ClassTable, Party},
+        save::SaveLoaded,
+        settings::Settings,
+    };
+
+    /// The plugins spawning the content of a floor, without rendering, audio or UI.
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins((MinimalPlugins, AssetPlugin::default(), InputPlugin))
+            .init_asset::<Mesh>()
+            .init_asset::<StandardMaterial>()
+            .init_asset::<Labyrinth>()
+            .init_asset::<Script>()
+            .init_asset::<ClassTable>()
+            .init_asset::<ItemDatabase>()
+            .init_asset::<LootTables>()
+            .init_asset::<UpgradeTree>()
+            .add_state::<GameState>()
+            .add_state::<PlayingState>()
+            .add_event::<GameMessage>()
+            .add_event::<LootDropped>()
+            .add_event::<PlayerMoved>()
+            .add_event::<SaveLoaded>()
+            .init_resource::<Settings>()
+            .init_resource::<Party>()
+            .init_resource::<Run>()
+            .add_plugins((
+                ConfigPlugin,
+                LabyrinthPlugin,
+                FeaturePlugin,
+                ScriptPlugin,
+                InventoryPlugin,
+                MonsterPlugin,
+            ))
+            .add_systems(
+                OnExit(GameState::Playing),
+                (despawn_playing_entities, reset_playing_state),
+            );
+
+        let first_floor =
+            parse_ron(include_str!("../assets/labyrinths/floor1.labyrinth.ron")).unwrap();
+        let floor_1 = app
+            .world
+            .resource_mut::<Assets<Labyrinth>>()
+            .add(first_floor);
+        app.insert_resource(LabyrinthAssets { floor_1 });
+        app
+    }
+
+    fn go_to(app: &mut App, state: GameState) {
+        app.world.resource_mut::<NextState<GameState>>().set(state);
+        // States set while leaving apply on the next frame
+        app.update();
+        app.update();
+    }
+
+    /// Entities and resources in the world.
+    fn counts(app: &App) -> (u32, usize) {
+        let resources = app
+            .world
+            .storages()
+            .resources
+            .iter()
+            .filter(|(_, data)| data.is_present())
+            .count();
+        (app.world.entities().len(), resources)
+    }
+
+    #[test]
+    fn playing_can_be_entered_and_left_again() {
+        let mut app = app();
+        app.update();
+        go_to(&mut app, GameState::Menu);
+
+        let mut rounds = Vec::new();
+        for _ in 0..4 {
+            go_to(&mut app, GameState::Playing);
+            let inside = counts(&app);
+            assert!(inside.0 > 0, "the floor is empty");
+
+            app.world
+                .resource_mut::<NextState<PlayingState>>()
+                .set(PlayingState::Paused);
+            app.update();
+            go_to(&mut app, GameState::Menu);
+            assert_eq!(
+                *app.world.resource::<State<PlayingState>>().get(),
+                PlayingState::Exploring
+            );
+            rounds.push((inside, counts(&app)));
+        }
+        assert!(
+            rounds.windows(2).all(|pair| pair[0] == pair[1]),
+            "{rounds:?}"
+        );
+    }
+}
@@ -1,9 +1,17 @@
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*, utils::HashMap};
 use bevy_asset_loader::prelude::*;
 use bevy_common_assets::ron::RonAssetPlugin;
 use bevy_kira_audio::AudioSource;
 
-use crate::GameState;
+use crate::{
+    dungeon::{format::LabyrinthLoader, labyrinth::Labyrinth, script::Script},
+    inventory::item::ItemDatabase,
+    loot::table::LootTables,
+    meta::upgrade::UpgradeTree,
+    party::class::ClassTable,
+    ui::layout::Region,
+    GameState,
+};
 
 pub struct LoadingPlugin;
 
@@ -12,12 +20,21 @@ pub struct LoadingPlugin;
 /// If interested, take a look at <https://bevy-cheatbook.github.io/features/assets.html>
 impl Plugin for LoadingPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(RonAssetPlugin::<UIConfig>::new(&["ron"]))
-            .add_loading_state(
-                LoadingState::new(GameState::Loading).continue_to_state(GameState::Menu),
-            )
-            .add_collection_to_loading_state::<_, AudioAssets>(GameState::Loading)
-            .add_collection_to_loading_state::<_, TextureAssets>(GameState::Loading);
+        app.add_plugins((
+            RonAssetPlugin::<UIConfig>::new(&["ui.ron"]),
+            RonAssetPlugin::<ClassTable>::new(&["classes.ron"]),
+            RonAssetPlugin::<ItemDatabase>::new(&["items.ron"]),
+            RonAssetPlugin::<LootTables>::new(&["loot.ron"]),
+            RonAssetPlugin::<UpgradeTree>::new(&["upgrades.ron"]),
+            RonAssetPlugin::<Script>::new(&["script.ron"]),
+        ))
+        .init_asset::<Labyrinth>()
+        .init_asset_loader::<LabyrinthLoader>()
+        .add_loading_state(LoadingState::new(GameState::Loading).continue_to_state(GameState::Menu))
+        .add_collection_to_loading_state::<_, AudioAssets>(GameState::Loading)
+        .add_collection_to_loading_state::<_, TextureAssets>(GameState::Loading)
+        .add_collection_to_loading_state::<_, DataAssets>(GameState::Loading)
+        .add_collection_to_loading_state::<_, LabyrinthAssets>(GameState::Loading);
     }
 }
 
@@ -42,15 +59,128 @@ pub struct TextureAssets {
     pub ceilling: Handle<Image>,
     #[asset(path = "textures/HUD.png")]
     pub hud: Handle<Image>,
-    #[asset(path = "textures/HUD_config.ron")]
+    #[asset(path = "textures/HUD_config.ui.ron")]
     pub hud_config: Handle<UIConfig>,
 }
 
+/// Game rules and balance tables, each data file type uses its own double extension.
+#[derive(AssetCollection, Resource)]
+pub struct DataAssets {
+    #[asset(path = "data/party.classes.ron")]
+    pub classes: Handle<ClassTable>,
+    #[asset(path = "data/base.items.ron")]
+    pub items: Handle<ItemDatabase>,
+    #[asset(path = "data/base.loot.ron")]
+    pub loot: Handle<LootTables>,
+    #[asset(path = "data/base.upgrades.ron")]
+    pub upgrades: Handle<UpgradeTree>,
+    #[asset(path = "data/floor1.script.ron")]
+    pub script: Handle<Script>,
+}
+
+/// Hand-made floors, either `.labyrinth` drawings or `.labyrinth.ron` files.
+#[derive(AssetCollection, Resource)]
+pub struct LabyrinthAssets {
+    #[asset(path = "labyrinths/floor1.labyrinth.ron")]
+    pub floor_1: Handle<Labyrinth>,
+}
+
+impl LabyrinthAssets {
+    pub fn first_floor(&self, assets: &Assets<Labyrinth>) -> Labyrinth {
+        assets
+            .get(&self.floor_1)
+            .cloned()
+            .expect("the first floor is loaded with the other assets")
+    }
+}
+
+/// Read access to the tables of [`DataAssets`], which are only available once loaded.
+#[derive(SystemParam)]
+pub struct GameData<'w> {
+    assets: Option<Res<'w, DataAssets>>,
+    classes: Res<'w, Assets<ClassTable>>,
+    items: Res<'w, Assets<ItemDatabase>>,
+    loot: Res<'w, Assets<LootTables>>,
+    upgrades: Res<'w, Assets<UpgradeTree>>,
+    scripts: Res<'w, Assets<Script>>,
+}
+
+impl GameData<'_> {
+    pub fn classes(&self) -> Option<&ClassTable> {
+        self.classes.get(self.assets.as_ref()?.classes.id())
+    }
+
+    pub fn items(&self) -> Option<&ItemDatabase> {
+        self.items.get(self.assets.as_ref()?.items.id())
+    }
+
+    pub fn loot(&self) -> Option<&LootTables> {
+        self.loot.get(self.assets.as_ref()?.loot.id())
+    }
+
+    pub fn upgrades(&self) -> Option<&UpgradeTree> {
+        self.upgrades.get(self.assets.as_ref()?.upgrades.id())
+    }
+
+    pub fn script(&self) -> Option<&Script> {
+        self.scripts.get(self.assets.as_ref()?.script.id())
+    }
+}
+
+/// Layout of the HUD, in pixels of the HUD image.
 #[derive(serde::Deserialize, bevy::asset::Asset, bevy::reflect::TypePath)]
 pub struct UIConfig {
     pub size: (f32, f32),
-    pub dongeon_view_margin: (f32, f32),
-    pub dungeon_view_size: (f32, f32),
-    pub minimap_margin: (f32, f32),
-    pub minimap_size: (f32, f32),
+    /// Rectangles of the HUD image the views and panels are drawn over, by name
+    pub regions: HashMap<String, Region>,
+}
+
+impl UIConfig {
+    /// Regions the HUD is built from
+    pub const REGIONS: [&'static str; 7] = [
+        "dungeon_view",
+        "minimap",
+        "combat_log",
+        "party",
+        "inventory",
+        "message_log",
+        "compass",
+    ];
+
+    /// Checks that every region is there and fits in the HUD image.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.size.0 <= 0. || self.size.1 <= 0. {
+            return Err(format!("the HUD size {:?} must be positive", self.size));
+        }
+        for name in Self::REGIONS {
+            let region = self
+                .regions
+                .get(name)
+                .ok_or_else(|| format!("the {name} region is missing"))?;
+            if region.width < 1. || region.height < 1. {
+                return Err(format!("the {name} region is empty"));
+            }
+            if region.x < 0.
+                || region.y < 0.
+                || region.x + region.width > self.size.0
+                || region.y + region.height > self.size.1
+            {
+                return Err(format!("the {name} region goes past the HUD image"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The region called `name`, or the whole HUD if the config has none.
+    pub fn region(&self, name: &str) -> Region {
+        self.regions.get(name).copied().unwrap_or_else(|| {
+            warn!("no HUD region named {name}");
+            Region {
+                x: 0.,
+                y: 0.,
+                width: self.size.0,
+                height: self.size.1,
+            }
+        })
+    }
 }
@@ -0,0 +1,58 @@
+pub mod table;
+
+use bevy::prelude::*;
+use rand::{rngs::StdRng, SeedableRng};
+
+use crate::{
+    combat::{CombatLog, LootDropped, MonsterDefeated},
+    dungeon::labyrinth::CurrentFloor,
+    loading::GameData,
+    meta::Meta,
+};
+
+pub struct LootPlugin;
+
+/// This plugin rolls the loot table of every monster defeated and sends the result as
+/// [`LootDropped`]. The rules live in [`table`].
+impl Plugin for LootPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LootRng::from_seed(rand::random()))
+            .add_systems(Update, roll_loot);
+    }
+}
+
+/// Randomness used for every loot roll, logging its seed makes a run reproducible.
+#[derive(Resource)]
+pub struct LootRng(pub StdRng);
+
+impl LootRng {
+    pub fn from_seed(seed: u64) -> Self {
+        info!("loot seed: {seed}");
+        LootRng(StdRng::seed_from_u64(seed))
+    }
+}
+
+fn roll_loot(
+    mut defeated: EventReader<MonsterDefeated>,
+    mut loot: EventWriter<LootDropped>,
+    mut rng: ResMut<LootRng>,
+    mut log: ResMut<CombatLog>,
+    floor: Res<CurrentFloor>,
+    meta: Res<Meta>,
+    data: GameData,
+) {
+    let (Some(tables), Some(upgrades)) = (data.loot(), data.upgrades()) else {
+        return;
+    };
+    let luck = meta.loot_luck(upgrades);
+    for MonsterDefeated(monster) in defeated.read() {
+        let rolled = tables.roll(monster.loot, floor.0, luck, &mut rng.0);
+        let gold = monster.gold + rolled.gold;
+        log.push(format!("{} dropped {} gold", monster.name, gold));
+        loot.send(LootDropped {
+            gold,
+            rare: tables.is_notable(&rolled),
+            items: rolled.drops.into_iter().map(|drop| drop.item).collect(),
+        });
+    }
+}
@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use bevy::{asset::Asset, reflect::TypePath};
+use rand::{distributions::WeightedIndex, prelude::*};
+use serde::Deserialize;
+
+use crate::inventory::item::{Affix, Bonus, Item};
+
+/// Nested tables deeper than this are skipped, which also guards against tables referencing
+/// each other.
+const MAX_NESTING: u32 = 8;
+
+/// Every loot table along with the rarity tiers and affixes, loaded from `data/base.loot.ron`.
+///
+/// Rolling does not touch the ECS and only uses the given RNG, so a roll can be replayed from its
+/// seed.
+#[derive(Deserialize, Asset, TypePath)]
+pub struct LootTables {
+    pub tables: HashMap<String, LootTable>,
+    /// From the most common to the rarest
+    pub rarities: Vec<Rarity>,
+    pub prefixes: Vec<AffixDef>,
+    pub suffixes: Vec<AffixDef>,
+}
+
+#[derive(Deserialize)]
+pub struct LootTable {
+    /// Dropped every time, on top of the rolls
+    #[serde(default)]
+    pub guaranteed: Vec<Entry>,
+    /// Number of picks among `entries`
+    #[serde(default = "one")]
+    pub rolls: u32,
+    /// Entries with their weight
+    #[serde(default)]
+    pub entries: Vec<(u32, Entry)>,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub enum Entry {
+    Nothing,
+    /// Between the two amounts, inclusive
+    Gold(u32, u32),
+    /// An item that never gets a rarity nor affixes, like consumables and keys
+    Item(String),
+    /// A piece of equipment with a random rarity and affixes
+    Gear(String),
+    /// Rolls another table
+    Table(String),
+}
+
+#[derive(Deserialize)]
+pub struct Rarity {
+    pub name: String,
+    pub weight: f32,
+    /// Added to the weight for each floor below the first, rare tiers get more likely deeper
+    #[serde(default)]
+    pub weight_per_floor: f32,
+    /// 0 for none, 1 for a prefix or a suffix, 2 for both
+    pub affixes: u32,
+    /// Worth telling the player about, auto-play stops on these
+    #[serde(default)]
+    pub notable: bool,
+}
+
+#[derive(Deserialize)]
+pub struct AffixDef {
+    pub name: String,
+    pub weight: u32,
+    /// Bonus on the first floor
+    pub bonus: Bonus,
+    /// Share of the bonus added for each floor below the first
+    pub per_floor: f32,
+}
+
+/// Conditions of a roll.
+#[derive(Clone, Copy)]
+struct Roll {
+    floor: u32,
+    luck: f32,
+}
+
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Loot {
+    pub gold: u32,
+    pub drops: Vec<LootDrop>,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct LootDrop {
+    pub item: Item,
+    /// Index in [`LootTables::rarities`], plain items have none
+    pub rarity: Option<usize>,
+}
+
+impl LootTables {
+    /// Rolls a table for a fight or chest on the given floor, starting at 1. `luck` scales the
+    /// weight of every rarity above the most common one, 0.5 makes them 50% more likely.
+    pub fn roll(&self, table: &str, floor: u32, luck: f32, rng: &mut impl Rng) -> Loot {
+        let mut loot = Loot::default();
+        let roll = Roll { floor, luck };
+        self.roll_into(table, roll, rng, &mut loot, 0);
+        loot
+    }
+
+    pub fn is_notable(&self, loot: &Loot) -> bool {
+        loot.drops.iter().any(|drop| {
+            drop.rarity
+                .and_then(|rarity| self.rarities.get(rarity))
+                .map_or(false, |rarity| rarity.notable)
+        })
+    }
+
+    /// Names of the tables referenced by `Table` entries that do not exist.
+    pub fn missing_tables(&self) -> Vec<&str> {
+        let mut missing: Vec<&str> = self
+            .tables
+            .values()
+            .flat_map(|table| {
+                table
+                    .guaranteed
+                    .iter()
+                    .chain(table.entries.iter().map(|(_, e)| e))
+            })
+            .filter_map(|entry| match entry {
+                Entry::Table(name) if !self.tables.contains_key(name) => Some(name.as_str()),
+                _ => None,
+            })
+            .collect();
+        missing.sort();
+        missing.dedup();
+        missing
+    }
+
+    fn roll_into(&self, name: &str, roll: Roll, rng: &mut impl Rng, loot: &mut Loot, nesting: u32) {
+        if nesting > MAX_NESTING {
+            return;
+        }
+        let Some(table) = self.tables.get(name) else {
+            return;
+        };
+        for entry in &table.guaranteed {
+            self.apply(entry, roll, rng, loot, nesting);
+        }
+        let Ok(weights) = WeightedIndex::new(table.entries.iter().map(|(weight, _)| *weight))
+        else {
+            return;
+        };
+        for _ in 0..table.rolls {
+            let (_, entry) = &table.entries[weights.sample(rng)];
+            self.apply(entry, roll, rng, loot, nesting);
+        }
+    }
+
+    fn apply(&self, entry: &Entry, roll: Roll, rng: &mut impl Rng, loot: &mut Loot, nesting: u32) {
+        match entry {
+            Entry::Nothing => {}
+            Entry::Gold(min, max) => loot.gold += rng.gen_range(*min..=*max.max(min)),
+            Entry::Item(id) => loot.drops.push(LootDrop {
+                item: Item::new(id.as_str()),
+                rarity: None,
+            }),
+            Entry::Gear(id) => loot.drops.push(self.roll_gear(id, roll, rng)),
+            Entry::Table(name) => self.roll_into(name, roll, rng, loot, nesting + 1),
+        }
+    }
+
+    fn roll_gear(&self, id: &str, roll: Roll, rng: &mut impl Rng) -> LootDrop {
+        let depth = roll.floor.saturating_sub(1) as f32;
+        let rarity = WeightedIndex::new(self.rarities.iter().enumerate().map(|(tier, rarity)| {
+            let luck = if tier > 0 { 1. + roll.luck } else { 1. };
+            ((rarity.weight + rarity.weight_per_floor * depth) * luck).max(0.)
+        }))
+        .ok()
+        .map(|weights| weights.sample(rng));
+
+        let mut item = Item::new(id);
+        let (prefix, suffix) = match rarity.map_or(0, |rarity| self.rarities[rarity].affixes) {
+            0 => (false, false),
+            1 => {
+                let prefix = rng.gen_bool(0.5);
+                (prefix, !prefix)
+            }
+            _ => (true, true),
+        };
+        if prefix {
+            item.prefix = roll_affix(&self.prefixes, depth, rng);
+        }
+        if suffix {
+            item.suffix = roll_affix(&self.suffixes, depth, rng);
+        }
+        LootDrop { item, rarity }
+    }
+}
+
+fn roll_affix(affixes: &[AffixDef], depth: f32, rng: &mut impl Rng) -> Option<Affix> {
+    let affix = affixes.choose_weighted(rng, |affix| affix.weight).ok()?;
+    Some(Affix {
+        name: affix.name.clone(),
+        bonus: affix.bonus.scaled(1. + affix.per_floor * depth),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `tables` along with a single rarity giving both affixes, and one affix of each kind.
+    fn tables(tables: &str) -> LootTables {
+        ron::from_str(&format!(
+            r#"(
+                tables: {{ {tables} }},
+                rarities: [(name: "Magic", weight: 1.0, affixes: 2)],
+                prefixes: [(name: "Sharp", weight: 1, bonus: (attack: 2), per_floor: 0.5)],
+                suffixes: [(name: "of the Bear", weight: 1, bonus: (max_hp: 10), per_floor: 0.1)],
+            )"#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn the_same_seed_gives_the_same_loot() {
+        let tables: LootTables =
+            ron::from_str(include_str!("../../assets/data/base.loot.ron")).unwrap();
+        for seed in 0..50 {
+            let roll = |seed| tables.roll("mantis_queen", 4, 0.5, &mut StdRng::seed_from_u64(seed));
+            assert_eq!(roll(seed), roll(seed), "seed {seed}");
+        }
+    }
+
+    #[test]
+    fn guaranteed_entries_always_drop() {
+        let tables = tables(
+            r#""chest": (
+                guaranteed: [Gold(5, 5), Item("rusty_key")],
+                entries: [(1, Nothing)],
+            )"#,
+        );
+        for seed in 0..20 {
+            let loot = tables.roll("chest", 1, 0., &mut StdRng::seed_from_u64(seed));
+            assert_eq!(loot.gold, 5);
+            assert_eq!(
+                loot.drops,
+                vec![LootDrop {
+                    item: Item::new("rusty_key"),
+                    rarity: None,
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn a_table_rolling_itself_stops_nesting() {
+        let tables = tables(r#""loop": (guaranteed: [Item("coin"), Table("loop")], rolls: 0)"#);
+        let loot = tables.roll("loop", 1, 0., &mut StdRng::seed_from_u64(0));
+        assert_eq!(loot.drops.len(), MAX_NESTING as usize + 1);
+    }
+
+    #[test]
+    fn affixes_grow_deeper_down() {
+        let tables = tables(r#""gear": (guaranteed: [Gear("short_sword")], rolls: 0)"#);
+        let gear = |floor| {
+            let loot = tables.roll("gear", floor, 0., &mut StdRng::seed_from_u64(0));
+            assert_eq!(loot.drops[0].rarity, Some(0));
+            loot.drops[0].item.clone()
+        };
+        let first = gear(1);
+        assert_eq!(first.prefix.unwrap().bonus.attack, 2);
+        assert_eq!(first.suffix.unwrap().bonus.max_hp, 10);
+        let fifth = gear(5);
+        assert_eq!(fifth.prefix.unwrap().bonus.attack, 6);
+        assert_eq!(fifth.suffix.unwrap().bonus.max_hp, 14);
+    }
+}
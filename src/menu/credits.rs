@@ -0,0 +1,166 @@
+use bevy::prelude::*;
+
+use super::{focus::CancelButton, spawn_button, ChangeState, OpenLink};
+use crate::GameState;
+
+pub struct CreditsPlugin;
+
+/// This plugin shows the credits, reached from the main menu. They are written in
+/// `credits/CREDITS.md`, which is built into the game so that it is shown on the web as well.
+impl Plugin for CreditsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Credits), setup_credits)
+            .add_systems(OnExit(GameState::Credits), cleanup_credits);
+    }
+}
+
+const CREDITS: &str = include_str!("../../credits/CREDITS.md");
+
+#[derive(Component)]
+struct CreditsMarker;
+
+/// A line of the credits, `level` being the number of `#` of a heading and 0 for plain text.
+#[derive(Clone, PartialEq, Eq, Debug)]
+struct CreditLine {
+    level: usize,
+    text: String,
+}
+
+/// Turns the markdown of the credits into lines of plain text. Only what the credits use is
+/// understood: headings, list items and links, which keep their text.
+fn credit_lines(markdown: &str) -> Vec<CreditLine> {
+    markdown
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            let level = line.chars().take_while(|c| *c == '#').count();
+            let line = line[level..].trim_start();
+            let text = match line.strip_prefix("* ").or_else(|| line.strip_prefix("- ")) {
+                Some(item) => format!("• {}", strip_links(item)),
+                None => strip_links(line),
+            };
+            CreditLine { level, text }
+        })
+        .collect()
+}
+
+/// Replaces every `[text](url)` by its text.
+fn strip_links(line: &str) -> String {
+    let mut text = String::new();
+    let mut rest = line;
+    while let Some(start) = rest.find('[') {
+        let Some((label, after)) = rest[start + 1..].split_once("](") else {
+            break;
+        };
+        let Some(end) = after.find(')') else {
+            break;
+        };
+        text.push_str(&rest[..start]);
+        text.push_str(label);
+        rest = &after[end + 1..];
+    }
+    text.push_str(rest);
+    text
+}
+
+fn font_size(level: usize) -> f32 {
+    match level {
+        0 => 22.0,
+        1 => 48.0,
+        _ => 32.0,
+    }
+}
+
+fn setup_credits(mut commands: Commands) {
+    commands.spawn((Camera2dBundle::default(), CreditsMarker));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            CreditsMarker,
+            Name::new("credits"),
+        ))
+        .with_children(|children| {
+            for line in credit_lines(CREDITS) {
+                let margin = if line.level > 0 { 12.0 } else { 2.0 };
+                children.spawn(
+                    TextBundle::from_section(
+                        line.text,
+                        TextStyle {
+                            font_size: font_size(line.level),
+                            color: Color::rgb(0.9, 0.9, 0.9),
+                            ..default()
+                        },
+                    )
+                    .with_style(Style {
+                        margin: UiRect::vertical(Val::Px(margin)),
+                        ..default()
+                    }),
+                );
+            }
+            children.spawn(NodeBundle::default()).with_children(|row| {
+                spawn_button(
+                    row,
+                    "Made with Bevy",
+                    320.0,
+                    OpenLink("https://bevyengine.org"),
+                );
+                spawn_button(
+                    row,
+                    "Back",
+                    140.0,
+                    (ChangeState(GameState::Menu), CancelButton),
+                );
+            });
+        });
+}
+
+fn cleanup_credits(mut commands: Commands, credits: Query<Entity, With<CreditsMarker>>) {
+    for entity in credits.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(level: usize, text: &str) -> CreditLine {
+        CreditLine {
+            level,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn headings_items_and_links_become_plain_text() {
+        let markdown = "# Credits\n\n## Music\n* [Song](https://example.com) by Someone\n- Sounds\nMade with [Bevy](https://bevyengine.org).";
+        assert_eq!(
+            credit_lines(markdown),
+            vec![
+                line(1, "Credits"),
+                line(2, "Music"),
+                line(0, "• Song by Someone"),
+                line(0, "• Sounds"),
+                line(0, "Made with Bevy."),
+            ]
+        );
+    }
+
+    #[test]
+    fn broken_links_are_kept_as_they_are() {
+        assert_eq!(strip_links("[a](b) and [c](d)"), "a and c");
+        assert_eq!(strip_links("[not a link] here"), "[not a link] here");
+        assert_eq!(strip_links("[open](url"), "[open](url");
+    }
+}
@@ -0,0 +1,279 @@
+use std::cmp::Ordering;
+
+use bevy::prelude::*;
+
+use super::ButtonColors;
+
+pub struct FocusPlugin;
+
+/// This plugin moves a focus between the buttons with the arrow keys, Tab or the D-pad, and
+/// presses the focused one with Enter or the A button, so that menus can be used without a mouse.
+/// Hovering a button with the mouse focuses it as well.
+///
+/// Only the buttons of the topmost layer of the UI can be focused, layers being set with
+/// `ZIndex::Global` on an ancestor: the pause menu over the HUD, the settings over the pause menu.
+impl Plugin for FocusPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (
+                focus_on_hover,
+                navigate,
+                activate,
+                color_buttons,
+                outline_focus,
+            )
+                .chain()
+                .in_set(FocusSet),
+        );
+    }
+}
+
+/// Systems reading the keys first, like key bindings, run before this set and clear them.
+#[derive(SystemSet, Clone, PartialEq, Eq, Hash, Debug)]
+pub struct FocusSet;
+
+/// The button that Enter or the A button presses.
+#[derive(Component)]
+pub struct Focused;
+
+/// Pressed by the B button or Backspace, to leave a menu.
+#[derive(Component)]
+pub struct CancelButton;
+
+/// Color of the border of a focused button that has no [`ButtonColors`], like the item slots.
+const FOCUS_OUTLINE: Color = Color::rgb(0.95, 0.8, 0.35);
+
+/// Where the focus goes from the focused button.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Step {
+    /// To the nearest button in this direction, y going down as in the UI
+    Toward(Vec2),
+    /// To the next button in reading order, or the previous one for `Next(false)`
+    Next(bool),
+}
+
+/// The button `step` leads to from `from`, among `buttons` positioned at their center.
+/// Without a focused button, the first one in reading order is focused.
+pub fn next_focus(from: Option<Entity>, step: Step, buttons: &[(Entity, Vec2)]) -> Option<Entity> {
+    let mut ordered = buttons.to_vec();
+    ordered.sort_by(|(_, a), (_, b)| reading_order(*a, *b));
+    let Some(current) =
+        from.and_then(|from| ordered.iter().position(|(entity, _)| *entity == from))
+    else {
+        return ordered.first().map(|(entity, _)| *entity);
+    };
+    let origin = ordered[current].1;
+    match step {
+        Step::Next(forward) => {
+            let count = ordered.len();
+            let index = if forward {
+                (current + 1) % count
+            } else {
+                (current + count - 1) % count
+            };
+            Some(ordered[index].0)
+        }
+        Step::Toward(direction) => ordered
+            .iter()
+            .filter_map(|(entity, position)| {
+                let offset = *position - origin;
+                let along = offset.dot(direction);
+                // Buttons mostly beside the direction are left to the other directions
+                let across = offset.perp_dot(direction).abs();
+                (along > 0.5 && across <= along * 2.).then_some((*entity, along + across * 2.))
+            })
+            .min_by(|(_, a), (_, b)| a.total_cmp(b))
+            .map(|(entity, _)| entity),
+    }
+}
+
+/// Top to bottom, then left to right for buttons on the same line.
+fn reading_order(a: Vec2, b: Vec2) -> Ordering {
+    if (a.y - b.y).abs() > 8. {
+        a.y.total_cmp(&b.y)
+    } else {
+        a.x.total_cmp(&b.x)
+    }
+}
+
+fn focus_on_hover(
+    mut commands: Commands,
+    hovered: Query<(Entity, &Interaction), (Changed<Interaction>, With<Button>)>,
+    focused: Query<Entity, With<Focused>>,
+) {
+    for (entity, interaction) in hovered.iter() {
+        if *interaction == Interaction::Hovered {
+            for previous in focused.iter() {
+                commands.entity(previous).remove::<Focused>();
+            }
+            commands.entity(entity).insert(Focused);
+        }
+    }
+}
+
+/// The layer of an entity, from the nearest ancestor placed with `ZIndex::Global`.
+fn layer(entity: Entity, nodes: &Query<(Option<&Parent>, Option<&ZIndex>)>) -> i32 {
+    let mut current = Some(entity);
+    while let Some(entity) = current {
+        let Ok((parent, z_index)) = nodes.get(entity) else {
+            break;
+        };
+        if let Some(ZIndex::Global(z)) = z_index {
+            return *z;
+        }
+        current = parent.map(|parent| parent.get());
+    }
+    0
+}
+
+/// The buttons that can be focused, with their center on screen.
+fn focusable(
+    buttons: &Query<(Entity, &Node, &GlobalTransform, &InheritedVisibility), With<Button>>,
+    nodes: &Query<(Option<&Parent>, Option<&ZIndex>)>,
+) -> Vec<(Entity, Vec2)> {
+    let shown: Vec<(Entity, Vec2, i32)> = buttons
+        .iter()
+        // Nodes not displayed are not laid out
+        .filter(|(_, node, _, visibility)| visibility.get() && node.size() != Vec2::ZERO)
+        .map(|(entity, _, transform, _)| {
+            (
+                entity,
+                transform.translation().truncate(),
+                layer(entity, nodes),
+            )
+        })
+        .collect();
+    let top = shown.iter().map(|(_, _, layer)| *layer).max().unwrap_or(0);
+    shown
+        .into_iter()
+        .filter(|(_, _, layer)| *layer == top)
+        .map(|(entity, position, _)| (entity, position))
+        .collect()
+}
+
+fn just_pressed_pad(
+    gamepads: &Gamepads,
+    gamepad_buttons: &Input<GamepadButton>,
+    button: GamepadButtonType,
+) -> bool {
+    gamepads
+        .iter()
+        .any(|gamepad| gamepad_buttons.just_pressed(GamepadButton::new(gamepad, button)))
+}
+
+fn navigate(
+    mut commands: Commands,
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    buttons: Query<(Entity, &Node, &GlobalTransform, &InheritedVisibility), With<Button>>,
+    nodes: Query<(Option<&Parent>, Option<&ZIndex>)>,
+    focused: Query<Entity, With<Focused>>,
+) {
+    let pad = |button| just_pressed_pad(&gamepads, &gamepad_buttons, button);
+    let step = if keyboard_input.just_pressed(KeyCode::Up) || pad(GamepadButtonType::DPadUp) {
+        Step::Toward(Vec2::NEG_Y)
+    } else if keyboard_input.just_pressed(KeyCode::Down) || pad(GamepadButtonType::DPadDown) {
+        Step::Toward(Vec2::Y)
+    } else if keyboard_input.just_pressed(KeyCode::Left) || pad(GamepadButtonType::DPadLeft) {
+        Step::Toward(Vec2::NEG_X)
+    } else if keyboard_input.just_pressed(KeyCode::Right) || pad(GamepadButtonType::DPadRight) {
+        Step::Toward(Vec2::X)
+    } else if keyboard_input.just_pressed(KeyCode::Tab) {
+        Step::Next(!keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]))
+    } else {
+        return;
+    };
+
+    let candidates = focusable(&buttons, &nodes);
+    let current = focused
+        .iter()
+        .find(|entity| candidates.iter().any(|(candidate, _)| candidate == entity));
+    let Some(next) = next_focus(current, step, &candidates) else {
+        return;
+    };
+    // Also drops the focus left behind in a layer that was covered since
+    for entity in focused.iter() {
+        commands.entity(entity).remove::<Focused>();
+    }
+    commands.entity(next).insert(Focused);
+}
+
+/// Presses the focused button, or the cancel button of the top layer, as a click would. The
+/// button is released on the next frame.
+#[allow(clippy::too_many_arguments)]
+fn activate(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    buttons: Query<(Entity, &Node, &GlobalTransform, &InheritedVisibility), With<Button>>,
+    nodes: Query<(Option<&Parent>, Option<&ZIndex>)>,
+    focused: Query<Entity, With<Focused>>,
+    cancel: Query<(), With<CancelButton>>,
+    mut interactions: Query<&mut Interaction>,
+    mut pressed: Local<Option<Entity>>,
+) {
+    if let Some(entity) = pressed.take() {
+        if let Ok(mut interaction) = interactions.get_mut(entity) {
+            *interaction = Interaction::None;
+        }
+    }
+
+    let pad = |button| just_pressed_pad(&gamepads, &gamepad_buttons, button);
+    let confirm = keyboard_input.just_pressed(KeyCode::Return) || pad(GamepadButtonType::South);
+    let back = keyboard_input.just_pressed(KeyCode::Back) || pad(GamepadButtonType::East);
+    if !confirm && !back {
+        return;
+    }
+    let candidates = focusable(&buttons, &nodes);
+    let target = candidates.iter().map(|(entity, _)| *entity).find(|entity| {
+        if confirm {
+            focused.contains(*entity)
+        } else {
+            cancel.contains(*entity)
+        }
+    });
+    if let Some(entity) = target {
+        if let Ok(mut interaction) = interactions.get_mut(entity) {
+            *interaction = Interaction::Pressed;
+            *pressed = Some(entity);
+        }
+    }
+}
+
+fn color_buttons(
+    mut buttons: Query<(
+        &Interaction,
+        Option<&Focused>,
+        &ButtonColors,
+        &mut BackgroundColor,
+    )>,
+) {
+    for (interaction, focused, colors, mut background) in buttons.iter_mut() {
+        let color = match interaction {
+            Interaction::Pressed | Interaction::Hovered => colors.hovered,
+            Interaction::None if focused.is_some() => colors.focused,
+            Interaction::None => colors.normal,
+        };
+        if background.0 != color {
+            background.0 = color;
+        }
+    }
+}
+
+/// Buttons that color themselves, like the item slots, show the focus with their border.
+fn outline_focus(
+    mut buttons: Query<(Option<&Focused>, &mut BorderColor), (With<Button>, Without<ButtonColors>)>,
+) {
+    for (focused, mut border) in buttons.iter_mut() {
+        let color = if focused.is_some() {
+            FOCUS_OUTLINE
+        } else {
+            Color::NONE
+        };
+        if border.0 != color {
+            border.0 = color;
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use bevy::prelude::*;
+
+use super::{focus::CancelButton, spawn_button, ChangeState};
+use crate::{
+    dungeon::labyrinth::CurrentFloor,
+    save::{delete_slot, SaveSlot},
+    GameState,
+};
+
+pub struct GameOverPlugin;
+
+/// This plugin ends the run when the party is defeated: its save slot is emptied, so that it
+/// cannot be continued, and the player goes back to the menu or starts a new game. Permanent
+/// upgrades are kept.
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::GameOver), (forget_run, setup_game_over))
+            .add_systems(OnExit(GameState::GameOver), cleanup_game_over);
+    }
+}
+
+#[derive(Component)]
+struct GameOverMarker;
+
+/// Runs after the autosave made when leaving `GameState::Playing`.
+fn forget_run(slot: Option<Res<SaveSlot>>) {
+    let Some(slot) = slot else {
+        return;
+    };
+    match delete_slot(slot.0) {
+        Ok(()) => info!("run over, slot {} emptied", slot.0),
+        Err(error) => warn!("Failed to empty slot {}: {error}", slot.0),
+    }
+}
+
+fn setup_game_over(mut commands: Commands, floor: Res<CurrentFloor>) {
+    commands.spawn((Camera2dBundle::default(), GameOverMarker));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            GameOverMarker,
+            Name::new("game_over"),
+        ))
+        .with_children(|children| {
+            children.spawn(TextBundle::from_section(
+                "Game Over",
+                TextStyle {
+                    font_size: 60.0,
+                    color: Color::rgb(0.9, 0.3, 0.3),
+                    ..default()
+                },
+            ));
+            children.spawn(TextBundle::from_section(
+                format!("The party fell on floor {}", floor.0),
+                TextStyle {
+                    font_size: 28.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..default()
+                },
+            ));
+            spawn_button(children, "New Game", 280.0, ChangeState(GameState::NewGame));
+            spawn_button(
+                children,
+                "Main menu",
+                280.0,
+                (ChangeState(GameState::Menu), CancelButton),
+            );
+        });
+}
+
+fn cleanup_game_over(mut commands: Commands, screen: Query<Entity, With<GameOverMarker>>) {
+    for entity in screen.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,94 @@
+use bevy::prelude::*;
+
+use super::{focus::CancelButton, spawn_button, ChangeState};
+use crate::{
+    save::{read_slot, LoadSlot, SaveError, SLOT_COUNT},
+    GameState,
+};
+
+pub struct LoadMenuPlugin;
+
+/// This plugin lists the save slots, reached from the main menu. Loading is done by the save
+/// plugin from the [`LoadSlot`] buttons.
+impl Plugin for LoadMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::LoadGame), setup_load_menu)
+            .add_systems(OnExit(GameState::LoadGame), cleanup_load_menu);
+    }
+}
+
+#[derive(Component)]
+struct LoadMenuMarker;
+
+fn setup_load_menu(mut commands: Commands) {
+    commands.spawn((Camera2dBundle::default(), LoadMenuMarker));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            LoadMenuMarker,
+            Name::new("load_menu"),
+        ))
+        .with_children(|children| {
+            children.spawn(TextBundle::from_section(
+                "Load",
+                TextStyle {
+                    font_size: 48.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..default()
+                },
+            ));
+            for slot in 1..=SLOT_COUNT {
+                match read_slot(slot) {
+                    Ok(save) => spawn_button(
+                        children,
+                        &format!("Slot {slot} - floor {}", save.floor),
+                        320.0,
+                        LoadSlot(slot),
+                    ),
+                    Err(SaveError::Missing) => {
+                        children.spawn(TextBundle::from_section(
+                            format!("Slot {slot}: empty"),
+                            TextStyle {
+                                font_size: 20.0,
+                                color: Color::rgb(0.6, 0.6, 0.6),
+                                ..default()
+                            },
+                        ));
+                    }
+                    // Keep broken or too recent saves visible, but not loadable
+                    Err(error) => {
+                        children.spawn(TextBundle::from_section(
+                            format!("Slot {slot}: {error}"),
+                            TextStyle {
+                                font_size: 20.0,
+                                color: Color::rgb(0.9, 0.3, 0.3),
+                                ..default()
+                            },
+                        ));
+                    }
+                }
+            }
+            spawn_button(
+                children,
+                "Back",
+                140.0,
+                (ChangeState(GameState::Menu), CancelButton),
+            );
+        });
+}
+
+fn cleanup_load_menu(mut commands: Commands, menu: Query<Entity, With<LoadMenuMarker>>) {
+    for entity in menu.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,248 @@
+mod credits;
+pub mod focus;
+mod game_over;
+mod load;
+mod new_game;
+mod pause;
+mod settings;
+
+use crate::loading::TextureAssets;
+use crate::save::{read_slot, LoadSlot, SLOT_COUNT};
+use crate::{GameState, PlayingState};
+use bevy::prelude::*;
+
+use self::{
+    credits::CreditsPlugin,
+    focus::FocusPlugin,
+    game_over::GameOverPlugin,
+    load::LoadMenuPlugin,
+    new_game::NewGamePlugin,
+    pause::PausePlugin,
+    settings::{OpenSettings, SettingsMenuPlugin},
+};
+
+pub struct MenuPlugin;
+
+/// This plugin is responsible for the main menu, which leads to a new game, the latest save, the
+/// other saves, the upgrades, the settings and the credits.
+/// The menu is only drawn during the State `GameState::Menu` and is removed when that state is
+/// exited.
+impl Plugin for MenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            FocusPlugin,
+            PausePlugin,
+            SettingsMenuPlugin,
+            NewGamePlugin,
+            LoadMenuPlugin,
+            CreditsPlugin,
+            GameOverPlugin,
+        ))
+        .add_systems(OnEnter(GameState::Menu), setup_menu)
+        .add_systems(
+            Update,
+            click_play_button.run_if(
+                in_state(GameState::Menu)
+                    .or_else(in_state(GameState::AwaySummary))
+                    .or_else(in_state(GameState::Upgrades))
+                    .or_else(in_state(GameState::NewGame))
+                    .or_else(in_state(GameState::LoadGame))
+                    .or_else(in_state(GameState::Credits))
+                    .or_else(in_state(GameState::GameOver))
+                    .or_else(in_state(PlayingState::Paused)),
+            ),
+        )
+        .add_systems(OnExit(GameState::Menu), cleanup_menu);
+    }
+}
+
+#[derive(Component)]
+pub struct ButtonColors {
+    pub normal: Color,
+    pub hovered: Color,
+    /// Selected with the keyboard or a gamepad
+    pub focused: Color,
+}
+
+impl Default for ButtonColors {
+    fn default() -> Self {
+        ButtonColors {
+            normal: Color::rgb(0.15, 0.15, 0.15),
+            hovered: Color::rgb(0.25, 0.25, 0.25),
+            focused: Color::rgb(0.35, 0.3, 0.15),
+        }
+    }
+}
+
+#[derive(Component)]
+struct Menu;
+
+#[derive(Component)]
+struct MenuStateMarker;
+
+fn setup_menu(mut commands: Commands, _textures: Res<TextureAssets>) {
+    info!("menu setup");
+    // Broken saves are left to the load screen, which tells what is wrong with them
+    let latest = (1..=SLOT_COUNT)
+        .filter_map(|slot| read_slot(slot).ok().map(|save| (slot, save)))
+        .max_by_key(|(_, save)| save.saved_at);
+    commands.spawn((Camera2dBundle::default(), MenuStateMarker));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            Menu,
+            MenuStateMarker,
+            Name::new("menu"),
+        ))
+        .with_children(|children| {
+            children.spawn(
+                TextBundle::from_section(
+                    "Insectivore",
+                    TextStyle {
+                        font_size: 72.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    margin: UiRect::bottom(Val::Px(30.0)),
+                    ..default()
+                }),
+            );
+            if let Some((slot, save)) = latest {
+                spawn_button(
+                    children,
+                    &format!("Continue - floor {}", save.floor),
+                    320.0,
+                    LoadSlot(slot),
+                );
+            }
+            spawn_button(children, "New Game", 320.0, ChangeState(GameState::NewGame));
+            spawn_button(children, "Load", 320.0, ChangeState(GameState::LoadGame));
+            spawn_button(
+                children,
+                "Upgrades",
+                320.0,
+                ChangeState(GameState::Upgrades),
+            );
+            spawn_button(children, "Settings", 320.0, OpenSettings);
+            spawn_button(children, "Credits", 320.0, ChangeState(GameState::Credits));
+        });
+}
+
+/// Spawns a menu button with the default [`ButtonColors`], `action` tells what it does.
+pub fn spawn_button(parent: &mut ChildBuilder, label: &str, width: f32, action: impl Bundle) {
+    let button_colors = ButtonColors::default();
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(width),
+                    height: Val::Px(50.0),
+                    margin: UiRect::all(Val::Px(5.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..Default::default()
+                },
+                background_color: button_colors.normal.into(),
+                ..Default::default()
+            },
+            button_colors,
+            action,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..default()
+                },
+            ));
+        });
+}
+
+/// Spawns a button smaller than the menu ones, for lines of options like the settings.
+pub fn spawn_small_button(parent: &mut ChildBuilder, label: &str, width: f32, action: impl Bundle) {
+    let button_colors = ButtonColors::default();
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width: Val::Px(width),
+                    height: Val::Px(36.0),
+                    margin: UiRect::all(Val::Px(3.0)),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: button_colors.normal.into(),
+                ..default()
+            },
+            button_colors,
+            action,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    font_size: 24.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..default()
+                },
+            ));
+        });
+}
+
+/// Put on buttons that move to another [`GameState`], or [`PlayingState`] from within the game.
+#[derive(Component)]
+pub struct ChangeState<S: States = GameState>(pub S);
+
+/// Put on buttons that open a web page in the browser.
+#[derive(Component)]
+struct OpenLink(&'static str);
+
+fn click_play_button(
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_playing_state: ResMut<NextState<PlayingState>>,
+    interaction_query: Query<
+        (
+            &Interaction,
+            Option<&ChangeState>,
+            Option<&ChangeState<PlayingState>>,
+            Option<&OpenLink>,
+        ),
+        (Changed<Interaction>, With<Button>),
+    >,
+) {
+    for (interaction, change_state, change_playing_state, open_link) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if let Some(state) = change_state {
+            next_state.set(state.0.clone());
+        } else if let Some(state) = change_playing_state {
+            next_playing_state.set(state.0.clone());
+        } else if let Some(link) = open_link {
+            if let Err(error) = webbrowser::open(link.0) {
+                warn!("Failed to open link {error:?}");
+            }
+        }
+    }
+}
+
+fn cleanup_menu(mut commands: Commands, menu: Query<Entity, With<MenuStateMarker>>) {
+    for entity in menu.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,310 @@
+use bevy::prelude::*;
+
+use super::{
+    focus::{CancelButton, FocusSet},
+    spawn_button, spawn_small_button, ChangeState,
+};
+use crate::{
+    dungeon::labyrinth::Labyrinth,
+    loading::{GameData, LabyrinthAssets},
+    loot::LootRng,
+    meta::{
+        restart_run,
+        run::{Difficulty, Run},
+        Meta,
+    },
+    party::{starting_members, Party},
+    save::{read_slot, SaveError, SaveSlot, SLOT_COUNT},
+    GameState,
+};
+
+pub struct NewGamePlugin;
+
+/// This plugin shows the options of a new run, reached from the main menu: its difficulty, the
+/// save slot it goes to and the seed of its generated floors, typed in with the digit keys.
+impl Plugin for NewGamePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::NewGame), setup_new_game)
+            .add_systems(
+                Update,
+                (
+                    // Backspace deletes a digit rather than leaving the screen
+                    type_seed.before(FocusSet),
+                    change_option,
+                    start_game,
+                    update_texts.run_if(resource_exists_and_changed::<NewGameOptions>()),
+                )
+                    .run_if(in_state(GameState::NewGame)),
+            )
+            .add_systems(OnExit(GameState::NewGame), cleanup_new_game);
+    }
+}
+
+/// A seed has at most this many digits, so that it always fits in a `u64`.
+const MAX_SEED_DIGITS: usize = 18;
+
+#[derive(Resource)]
+struct NewGameOptions {
+    /// Digits typed by the player, a random seed is used when there are none
+    seed: String,
+    difficulty: Difficulty,
+    slot: u8,
+}
+
+impl NewGameOptions {
+    fn run_seed(&self) -> u64 {
+        self.seed.parse().unwrap_or_else(|_| rand::random())
+    }
+}
+
+fn random_seed() -> String {
+    (rand::random::<u64>() % 1_000_000_000).to_string()
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum NewGameOption {
+    Difficulty,
+    Slot,
+    Seed,
+}
+
+/// Steps an option up or down, a step of the seed rolls a new one.
+#[derive(Component)]
+struct ChangeOption(NewGameOption, i32);
+
+#[derive(Component)]
+struct OptionText(NewGameOption);
+
+#[derive(Component)]
+struct StartGame;
+
+#[derive(Component)]
+struct NewGameMarker;
+
+/// What starting a new game in `slot` does to the save already there.
+fn describe_slot(slot: u8) -> String {
+    match read_slot(slot) {
+        Ok(save) => format!("{slot} - overwrites floor {}", save.floor),
+        Err(SaveError::Missing) => format!("{slot} - empty"),
+        Err(_) => format!("{slot} - unreadable"),
+    }
+}
+
+fn text_style() -> TextStyle {
+    TextStyle {
+        font_size: 24.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+        ..default()
+    }
+}
+
+fn setup_new_game(mut commands: Commands) {
+    // Empty slots are offered first, so that no run is overwritten by accident
+    let slot = (1..=SLOT_COUNT)
+        .find(|slot| matches!(read_slot(*slot), Err(SaveError::Missing)))
+        .unwrap_or(1);
+    commands.insert_resource(NewGameOptions {
+        seed: random_seed(),
+        difficulty: Difficulty::Normal,
+        slot,
+    });
+    commands.spawn((Camera2dBundle::default(), NewGameMarker));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                ..default()
+            },
+            NewGameMarker,
+            Name::new("new_game"),
+        ))
+        .with_children(|children| {
+            children.spawn(TextBundle::from_section(
+                "New Game",
+                TextStyle {
+                    font_size: 48.0,
+                    ..text_style()
+                },
+            ));
+            spawn_option_row(children, "Difficulty", NewGameOption::Difficulty);
+            spawn_option_row(children, "Save slot", NewGameOption::Slot);
+            spawn_option_row(children, "Seed", NewGameOption::Seed);
+            children.spawn(TextBundle::from_section(
+                "Type digits to choose the seed",
+                TextStyle {
+                    font_size: 18.0,
+                    color: Color::rgb(0.6, 0.6, 0.6),
+                    ..default()
+                },
+            ));
+            children.spawn(NodeBundle::default()).with_children(|row| {
+                spawn_button(row, "Start", 180.0, StartGame);
+                spawn_button(
+                    row,
+                    "Back",
+                    180.0,
+                    (ChangeState(GameState::Menu), CancelButton),
+                );
+            });
+        });
+}
+
+/// The name of the option, then its value between a button to lower it and one to raise it. The
+/// seed has a single button rolling a new one instead.
+fn spawn_option_row(parent: &mut ChildBuilder, name: &str, option: NewGameOption) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(560.0),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceBetween,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|row| {
+            row.spawn(TextBundle::from_section(name, text_style()));
+            row.spawn(NodeBundle::default()).with_children(|value| {
+                if option != NewGameOption::Seed {
+                    spawn_small_button(value, "<", 36.0, ChangeOption(option, -1));
+                }
+                value.spawn((
+                    TextBundle::from_section("", text_style())
+                        .with_text_alignment(TextAlignment::Center)
+                        .with_style(Style {
+                            width: Val::Px(260.0),
+                            ..default()
+                        }),
+                    OptionText(option),
+                ));
+                if option == NewGameOption::Seed {
+                    spawn_small_button(value, "Random", 100.0, ChangeOption(option, 1));
+                } else {
+                    spawn_small_button(value, ">", 36.0, ChangeOption(option, 1));
+                }
+            });
+        });
+}
+
+fn digit(key: KeyCode) -> Option<char> {
+    let digit = match key {
+        KeyCode::Key0 | KeyCode::Numpad0 => '0',
+        KeyCode::Key1 | KeyCode::Numpad1 => '1',
+        KeyCode::Key2 | KeyCode::Numpad2 => '2',
+        KeyCode::Key3 | KeyCode::Numpad3 => '3',
+        KeyCode::Key4 | KeyCode::Numpad4 => '4',
+        KeyCode::Key5 | KeyCode::Numpad5 => '5',
+        KeyCode::Key6 | KeyCode::Numpad6 => '6',
+        KeyCode::Key7 | KeyCode::Numpad7 => '7',
+        KeyCode::Key8 | KeyCode::Numpad8 => '8',
+        KeyCode::Key9 | KeyCode::Numpad9 => '9',
+        _ => return None,
+    };
+    Some(digit)
+}
+
+fn type_seed(mut keyboard_input: ResMut<Input<KeyCode>>, mut options: ResMut<NewGameOptions>) {
+    let digits: Vec<char> = keyboard_input
+        .get_just_pressed()
+        .filter_map(|key| digit(*key))
+        .collect();
+    for digit in digits {
+        if options.seed.len() < MAX_SEED_DIGITS {
+            options.seed.push(digit);
+        }
+    }
+    if keyboard_input.just_pressed(KeyCode::Back) && !options.seed.is_empty() {
+        options.seed.pop();
+        keyboard_input.clear_just_pressed(KeyCode::Back);
+    }
+}
+
+fn change_option(
+    mut options: ResMut<NewGameOptions>,
+    interaction_query: Query<(&Interaction, &ChangeOption), Changed<Interaction>>,
+) {
+    for (interaction, ChangeOption(option, steps)) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match option {
+            NewGameOption::Difficulty => options.difficulty = options.difficulty.cycle(*steps),
+            NewGameOption::Slot => {
+                let index = (options.slot as i32 - 1 + steps).rem_euclid(SLOT_COUNT as i32);
+                options.slot = index as u8 + 1;
+            }
+            NewGameOption::Seed => options.seed = random_seed(),
+        }
+    }
+}
+
+fn update_texts(options: Res<NewGameOptions>, mut texts: Query<(&mut Text, &OptionText)>) {
+    for (mut text, OptionText(option)) in texts.iter_mut() {
+        text.sections[0].value = match option {
+            NewGameOption::Difficulty => options.difficulty.name().to_string(),
+            NewGameOption::Slot => describe_slot(options.slot),
+            NewGameOption::Seed if options.seed.is_empty() => "Random".to_string(),
+            NewGameOption::Seed => options.seed.clone(),
+        };
+    }
+}
+
+/// Starts a run from floor 1 with the chosen options, keeping the permanent upgrades.
+#[allow(clippy::too_many_arguments)]
+fn start_game(
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut party: ResMut<Party>,
+    options: Res<NewGameOptions>,
+    meta: Res<Meta>,
+    data: GameData,
+    labyrinths: Res<LabyrinthAssets>,
+    floors: Res<Assets<Labyrinth>>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<StartGame>)>,
+) {
+    if !interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        return;
+    }
+    let (Some(tree), Some(classes), Some(items)) = (data.upgrades(), data.classes(), data.items())
+    else {
+        return;
+    };
+    let run = Run {
+        seed: options.run_seed(),
+        difficulty: options.difficulty,
+        deepest_floor: 1,
+    };
+    info!(
+        "new game in slot {}, seed {}, {} difficulty",
+        options.slot,
+        run.seed,
+        run.difficulty.name()
+    );
+    commands.insert_resource(run);
+    commands.insert_resource(LootRng::from_seed(run.seed));
+    restart_run(
+        &mut commands,
+        &mut party,
+        starting_members(classes, items, &meta.starting_attributes(tree)),
+        labyrinths.first_floor(&floors),
+    );
+    commands.insert_resource(SaveSlot(options.slot));
+    next_state.set(GameState::Playing);
+}
+
+fn cleanup_new_game(mut commands: Commands, screen: Query<Entity, With<NewGameMarker>>) {
+    commands.remove_resource::<NewGameOptions>();
+    for entity in screen.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,163 @@
+use bevy::prelude::*;
+
+use super::{
+    focus::CancelButton,
+    settings::{settings_open, OpenSettings},
+    spawn_button, ChangeState,
+};
+use crate::{
+    save::{read_slot, LoadSlot, SaveRequest, SLOT_COUNT},
+    GameState, PlayingState,
+};
+
+pub struct PausePlugin;
+
+/// This plugin pauses the game with Escape or the Start button of a gamepad. Virtual time stands
+/// still while paused, which freezes everything animated, and the dungeon stops listening to
+/// input since it only does so while exploring or fighting.
+impl Plugin for PausePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PausedFrom>()
+            .add_systems(
+                Update,
+                toggle_pause
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(
+                        in_state(PlayingState::Exploring)
+                            .or_else(in_state(PlayingState::Combat))
+                            .or_else(in_state(PlayingState::Paused)),
+                    )
+                    // Escape closes the settings first
+                    .run_if(not(settings_open)),
+            )
+            .add_systems(
+                Update,
+                click_save_button
+                    .run_if(in_state(GameState::Playing))
+                    .run_if(in_state(PlayingState::Paused)),
+            )
+            .add_systems(OnEnter(PlayingState::Paused), (setup_pause_menu, stop_time))
+            .add_systems(
+                OnExit(PlayingState::Paused),
+                (cleanup_pause_menu, start_time),
+            );
+    }
+}
+
+/// The state to go back to when resuming.
+#[derive(Resource, Default)]
+struct PausedFrom(PlayingState);
+
+#[derive(Component)]
+struct PauseMenu;
+
+#[derive(Component)]
+struct SaveButton;
+
+fn toggle_pause(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepads: Res<Gamepads>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    state: Res<State<PlayingState>>,
+    mut next_state: ResMut<NextState<PlayingState>>,
+    mut paused_from: ResMut<PausedFrom>,
+) {
+    let start = gamepads.iter().any(|gamepad| {
+        gamepad_buttons.just_pressed(GamepadButton::new(gamepad, GamepadButtonType::Start))
+    });
+    if !keyboard_input.just_pressed(KeyCode::Escape) && !start {
+        return;
+    }
+    if *state.get() == PlayingState::Paused {
+        next_state.set(paused_from.0.clone());
+    } else {
+        paused_from.0 = state.get().clone();
+        next_state.set(PlayingState::Paused);
+    }
+}
+
+fn stop_time(mut time: ResMut<Time<Virtual>>) {
+    time.pause();
+}
+
+fn start_time(mut time: ResMut<Time<Virtual>>) {
+    time.unpause();
+}
+
+fn setup_pause_menu(mut commands: Commands, paused_from: Res<PausedFrom>) {
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.7).into(),
+                focus_policy: bevy::ui::FocusPolicy::Block,
+                // Over the whole HUD
+                z_index: ZIndex::Global(10),
+                ..default()
+            },
+            PauseMenu,
+            Name::new("pause_menu"),
+        ))
+        .with_children(|children| {
+            children.spawn(TextBundle::from_section(
+                "Paused",
+                TextStyle {
+                    font_size: 60.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                    ..default()
+                },
+            ));
+            spawn_button(
+                children,
+                "Resume",
+                220.0,
+                (ChangeState(paused_from.0.clone()), CancelButton),
+            );
+            spawn_button(children, "Save", 220.0, SaveButton);
+            spawn_button(children, "Settings", 220.0, OpenSettings);
+            for slot in 1..=SLOT_COUNT {
+                if let Ok(save) = read_slot(slot) {
+                    spawn_button(
+                        children,
+                        &format!("Load {slot} - floor {}", save.floor),
+                        320.0,
+                        LoadSlot(slot),
+                    );
+                }
+            }
+            spawn_button(
+                children,
+                "Quit to menu",
+                280.0,
+                ChangeState(GameState::Menu),
+            );
+        });
+}
+
+fn click_save_button(
+    mut requests: EventWriter<SaveRequest>,
+    mut next_state: ResMut<NextState<PlayingState>>,
+    paused_from: Res<PausedFrom>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<SaveButton>)>,
+) {
+    for interaction in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            requests.send(SaveRequest);
+            next_state.set(paused_from.0.clone());
+        }
+    }
+}
+
+fn cleanup_pause_menu(mut commands: Commands, menu: Query<Entity, With<PauseMenu>>) {
+    for entity in menu.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
@@ -0,0 +1,283 @@
+use bevy::prelude::*;
+
+use super::{
+    focus::{CancelButton, FocusSet},
+    spawn_button, spawn_small_button,
+};
+use crate::{
+    settings::{Action, Setting, Settings},
+    GameState, PlayingState,
+};
+
+pub struct SettingsMenuPlugin;
+
+/// This plugin opens the settings over the main menu or the pause menu, from any button with
+/// [`OpenSettings`]. Changes apply right away, Escape or the Back button close the settings.
+impl Plugin for SettingsMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Rebinding>()
+            .add_systems(
+                Update,
+                (
+                    open_settings.run_if(not(settings_open)),
+                    (
+                        adjust_setting,
+                        // A key bound from the keyboard must not press the button it was bound
+                        // from again
+                        (settings_keys.before(FocusSet), start_rebinding).chain(),
+                        update_texts,
+                    )
+                        .run_if(settings_open),
+                ),
+            )
+            .add_systems(OnExit(GameState::Menu), close_settings)
+            .add_systems(OnExit(PlayingState::Paused), close_settings);
+    }
+}
+
+#[derive(Component)]
+pub struct OpenSettings;
+
+#[derive(Component)]
+struct SettingsMenu;
+
+#[derive(Component)]
+struct CloseSettings;
+
+/// Steps a setting up or down.
+#[derive(Component)]
+struct AdjustSetting(Setting, i32);
+
+#[derive(Component)]
+struct SettingText(Setting);
+
+#[derive(Component)]
+struct RebindButton(Action);
+
+#[derive(Component)]
+struct BindingText(Action);
+
+/// The action waiting for a key, once its button was pressed.
+#[derive(Resource, Default)]
+struct Rebinding(Option<Action>);
+
+/// Run condition for what happens while the settings are shown.
+pub fn settings_open(menu: Query<(), With<SettingsMenu>>) -> bool {
+    !menu.is_empty()
+}
+
+fn open_settings(
+    mut commands: Commands,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<OpenSettings>)>,
+) {
+    if interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        spawn_settings(&mut commands);
+    }
+}
+
+fn spawn_settings(commands: &mut Commands) {
+    let column_node = NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            ..default()
+        },
+        ..default()
+    };
+
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    position_type: PositionType::Absolute,
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0., 0., 0., 0.9).into(),
+                focus_policy: bevy::ui::FocusPolicy::Block,
+                // Over the pause menu
+                z_index: ZIndex::Global(20),
+                ..default()
+            },
+            SettingsMenu,
+            Name::new("settings_menu"),
+        ))
+        .with_children(|children| {
+            children.spawn(TextBundle::from_section(
+                "Settings",
+                TextStyle {
+                    font_size: 48.0,
+                    ..text_style()
+                },
+            ));
+            children
+                .spawn(NodeBundle {
+                    style: Style {
+                        column_gap: Val::Px(40.0),
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|columns| {
+                    columns.spawn(column_node.clone()).with_children(|column| {
+                        for setting in Setting::ALL {
+                            spawn_setting_row(column, setting);
+                        }
+                    });
+                    columns.spawn(column_node.clone()).with_children(|column| {
+                        for action in Action::ALL {
+                            spawn_binding_row(column, action);
+                        }
+                    });
+                });
+            spawn_button(children, "Back", 140.0, (CloseSettings, CancelButton));
+        });
+}
+
+fn text_style() -> TextStyle {
+    TextStyle {
+        font_size: 24.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+        ..default()
+    }
+}
+
+fn row_node(width: f32) -> NodeBundle {
+    NodeBundle {
+        style: Style {
+            width: Val::Px(width),
+            align_items: AlignItems::Center,
+            justify_content: JustifyContent::SpaceBetween,
+            ..default()
+        },
+        ..default()
+    }
+}
+
+/// The name of the setting, then its value between a button to lower it and one to raise it.
+fn spawn_setting_row(column: &mut ChildBuilder, setting: Setting) {
+    column.spawn(row_node(420.0)).with_children(|row| {
+        row.spawn(TextBundle::from_section(setting.name(), text_style()));
+        row.spawn(NodeBundle::default()).with_children(|value| {
+            spawn_small_button(value, "<", 36.0, AdjustSetting(setting, -1));
+            value.spawn((
+                TextBundle::from_section("", text_style())
+                    .with_text_alignment(TextAlignment::Center)
+                    .with_style(Style {
+                        width: Val::Px(90.0),
+                        ..default()
+                    }),
+                SettingText(setting),
+            ));
+            spawn_small_button(value, ">", 36.0, AdjustSetting(setting, 1));
+        });
+    });
+}
+
+/// The name of the action, then a button showing its key that waits for a new one when pressed.
+fn spawn_binding_row(column: &mut ChildBuilder, action: Action) {
+    column.spawn(row_node(320.0)).with_children(|row| {
+        row.spawn(TextBundle::from_section(action.name(), text_style()));
+        spawn_small_button(row, "", 140.0, (RebindButton(action), BindingText(action)));
+    });
+}
+
+fn adjust_setting(
+    mut settings: ResMut<Settings>,
+    interaction_query: Query<(&Interaction, &AdjustSetting), Changed<Interaction>>,
+) {
+    for (interaction, AdjustSetting(setting, steps)) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            settings.adjust(*setting, *steps);
+        }
+    }
+}
+
+fn start_rebinding(
+    mut rebinding: ResMut<Rebinding>,
+    interaction_query: Query<(&Interaction, &RebindButton), Changed<Interaction>>,
+) {
+    for (interaction, RebindButton(action)) in &interaction_query {
+        if *interaction == Interaction::Pressed {
+            rebinding.0 = Some(*action);
+        }
+    }
+}
+
+/// Binds the next key pressed while rebinding, Escape cancels it. Otherwise Escape closes the
+/// settings, as does the Back button.
+fn settings_keys(
+    mut commands: Commands,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+    mut rebinding: ResMut<Rebinding>,
+    mut settings: ResMut<Settings>,
+    menu: Query<Entity, With<SettingsMenu>>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<CloseSettings>)>,
+) {
+    let escape = keyboard_input.just_pressed(KeyCode::Escape);
+    if let Some(action) = rebinding.0 {
+        if escape {
+            rebinding.0 = None;
+        } else if let Some(&key) = keyboard_input.get_just_pressed().next() {
+            settings.keys.bind(action, key);
+            rebinding.0 = None;
+            // Enter or the arrows are meant for the binding, not for moving the focus
+            keyboard_input.clear_just_pressed(key);
+        }
+        return;
+    }
+    let back = interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed);
+    if escape || back {
+        for entity in menu.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+    }
+}
+
+fn update_texts(
+    settings: Res<Settings>,
+    rebinding: Res<Rebinding>,
+    added: Query<(), Added<SettingsMenu>>,
+    mut setting_texts: Query<(&mut Text, &SettingText)>,
+    binding_buttons: Query<(&BindingText, &Children)>,
+    mut texts: Query<&mut Text, Without<SettingText>>,
+) {
+    // Texts are spawned empty, they are filled as soon as the settings open
+    if !settings.is_changed() && !rebinding.is_changed() && added.is_empty() {
+        return;
+    }
+    for (mut text, SettingText(setting)) in setting_texts.iter_mut() {
+        text.sections[0].value = settings.describe(*setting);
+    }
+    for (BindingText(action), children) in binding_buttons.iter() {
+        let label = if rebinding.0 == Some(*action) {
+            "Press a key".to_string()
+        } else {
+            format!("{:?}", settings.keys.key(*action))
+        };
+        for &child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
+
+fn close_settings(
+    mut commands: Commands,
+    mut rebinding: ResMut<Rebinding>,
+    menu: Query<Entity, With<SettingsMenu>>,
+) {
+    rebinding.0 = None;
+    for entity in menu.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
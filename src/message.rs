@@ -0,0 +1,152 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+pub struct MessagePlugin;
+
+/// This plugin keeps the history of the [`GameMessage`]s sent by the other plugins, for the HUD
+/// to show.
+impl Plugin for MessagePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MessageLog>()
+            .add_event::<GameMessage>()
+            .add_systems(Update, record_messages);
+    }
+}
+
+/// Something that happened in the game and that the player should read about.
+#[derive(Event, Clone, PartialEq, Debug)]
+pub struct GameMessage {
+    pub text: String,
+    pub severity: Severity,
+    /// Also pops up over the dungeon view for a moment
+    pub toast: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Severity {
+    Info,
+    /// Good news, like loot or a level up
+    Success,
+    Warning,
+    /// The party got hurt or is in trouble
+    Danger,
+}
+
+impl GameMessage {
+    pub fn new(severity: Severity, text: impl Into<String>) -> Self {
+        GameMessage {
+            text: text.into(),
+            severity,
+            toast: false,
+        }
+    }
+
+    pub fn info(text: impl Into<String>) -> Self {
+        Self::new(Severity::Info, text)
+    }
+
+    pub fn success(text: impl Into<String>) -> Self {
+        Self::new(Severity::Success, text)
+    }
+
+    pub fn warning(text: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, text)
+    }
+
+    pub fn danger(text: impl Into<String>) -> Self {
+        Self::new(Severity::Danger, text)
+    }
+
+    pub fn toast(self) -> Self {
+        GameMessage {
+            toast: true,
+            ..self
+        }
+    }
+}
+
+impl Severity {
+    pub fn color(&self) -> Color {
+        match self {
+            Severity::Info => Color::rgb(0.85, 0.85, 0.85),
+            Severity::Success => Color::rgb(0.45, 0.85, 0.4),
+            Severity::Warning => Color::rgb(0.95, 0.75, 0.25),
+            Severity::Danger => Color::rgb(0.95, 0.3, 0.3),
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct LoggedMessage {
+    /// Seconds since the game started
+    pub time: f32,
+    pub text: String,
+    pub severity: Severity,
+}
+
+impl LoggedMessage {
+    /// The text prefixed with the time as `[mm:ss]`.
+    pub fn timestamped(&self) -> String {
+        let seconds = self.time as u32;
+        format!("[{:02}:{:02}] {}", seconds / 60, seconds % 60, self.text)
+    }
+}
+
+/// Every message received, most recent last, and how far back the player scrolled.
+#[derive(Resource, Default)]
+pub struct MessageLog {
+    entries: VecDeque<LoggedMessage>,
+    /// Number of the most recent messages scrolled past
+    scroll: usize,
+}
+
+impl MessageLog {
+    const CAPACITY: usize = 200;
+
+    pub fn push(&mut self, time: f32, message: &GameMessage) {
+        if self.entries.len() == Self::CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(LoggedMessage {
+            time,
+            text: message.text.clone(),
+            severity: message.severity,
+        });
+        // Keeps the same messages in view while scrolled back
+        if self.scroll > 0 {
+            self.scroll = (self.scroll + 1).min(self.entries.len() - 1);
+        }
+    }
+
+    /// Scrolls back into older messages for a positive `lines`, forward otherwise.
+    pub fn scroll(&mut self, lines: i32) {
+        let max = self.entries.len().saturating_sub(1) as i32;
+        self.scroll = (self.scroll as i32 + lines).clamp(0, max) as usize;
+    }
+
+    pub fn catch_up(&mut self) {
+        self.scroll = 0;
+    }
+
+    pub fn scrolled_back(&self) -> bool {
+        self.scroll > 0
+    }
+
+    /// The `count` messages in view, oldest first.
+    pub fn visible(&self, count: usize) -> impl Iterator<Item = &LoggedMessage> {
+        let end = self.entries.len() - self.scroll;
+        self.entries.range(end.saturating_sub(count)..end)
+    }
+}
+
+fn record_messages(
+    mut messages: EventReader<GameMessage>,
+    mut log: ResMut<MessageLog>,
+    time: Res<Time>,
+) {
+    for message in messages.read() {
+        info!("{}", message.text);
+        log.push(time.elapsed_seconds(), message);
+    }
+}
@@ -0,0 +1,332 @@
+pub mod run;
+mod screen;
+pub mod upgrade;
+
+use std::collections::BTreeMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{DefeatedMonsters, MonsterDefeated},
+    dungeon::{
+        labyrinth::{CurrentFloor, Explored, FloorChanged, Labyrinth},
+        script::ScriptState,
+        torch::Torch,
+    },
+    idle::AutoPlay,
+    inventory::Inventory,
+    loading::GameData,
+    party::{
+        character::{Attributes, Character},
+        Party,
+    },
+    storage,
+};
+
+use self::{
+    run::Run,
+    screen::UpgradeScreenPlugin,
+    upgrade::{Effect, UpgradeDef, UpgradeTree},
+};
+
+pub struct MetaPlugin;
+
+/// This plugin handles what carries over between runs: essence earned while playing is spent on
+/// permanent upgrades, and a prestige starts a new run in exchange for an essence multiplier.
+///
+/// Upgrades are shared by every save slot, they are stored on their own in `meta.json` and written
+/// whenever they change.
+impl Plugin for MetaPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(UpgradeScreenPlugin)
+            .insert_resource(read_meta())
+            .init_resource::<Run>()
+            .add_systems(
+                Update,
+                (
+                    earn_essence,
+                    apply_upgrades,
+                    write_meta
+                        .run_if(resource_changed::<Meta>().and_then(not(resource_added::<Meta>()))),
+                ),
+            );
+    }
+}
+
+const META_KEY: &str = "meta.json";
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Meta {
+    pub essence: u32,
+    /// Level bought of each upgrade, by id
+    pub upgrades: BTreeMap<String, u32>,
+    /// Number of prestiges done
+    pub prestige: u32,
+    /// Applied to the essence earned
+    pub multiplier: f32,
+}
+
+impl Default for Meta {
+    fn default() -> Self {
+        Meta {
+            essence: 0,
+            upgrades: BTreeMap::new(),
+            prestige: 0,
+            multiplier: 1.,
+        }
+    }
+}
+
+impl Meta {
+    pub fn level(&self, id: &str) -> u32 {
+        self.upgrades.get(id).copied().unwrap_or(0)
+    }
+
+    /// Price of the next level of an upgrade.
+    pub fn cost(&self, upgrade: &UpgradeDef) -> u32 {
+        let level = self.level(&upgrade.id) as i32;
+        (upgrade.cost as f32 * upgrade.cost_growth.powi(level)).round() as u32
+    }
+
+    /// Why the next level of an upgrade cannot be bought, if it cannot.
+    pub fn blocker(&self, upgrade: &UpgradeDef) -> Option<String> {
+        if self.level(&upgrade.id) >= upgrade.max_level {
+            return Some("maxed".to_string());
+        }
+        if let Some((required, level)) = &upgrade.requires {
+            if self.level(required) < *level {
+                return Some(format!("needs {required} {level}"));
+            }
+        }
+        if self.essence < self.cost(upgrade) {
+            return Some("not enough essence".to_string());
+        }
+        None
+    }
+
+    pub fn buy(&mut self, upgrade: &UpgradeDef) -> bool {
+        if self.blocker(upgrade).is_some() {
+            return false;
+        }
+        self.essence -= self.cost(upgrade);
+        *self.upgrades.entry(upgrade.id.clone()).or_default() += 1;
+        true
+    }
+
+    /// Sum of the numeric effects of every level bought.
+    fn total(&self, tree: &UpgradeTree, value: impl Fn(&Effect) -> Option<f32>) -> f32 {
+        tree.upgrades
+            .iter()
+            .filter_map(|upgrade| Some(value(&upgrade.effect)? * self.level(&upgrade.id) as f32))
+            .sum()
+    }
+
+    pub fn explore_speed(&self, tree: &UpgradeTree) -> f32 {
+        self.total(tree, |effect| match effect {
+            Effect::ExploreSpeed(speed) => Some(*speed),
+            _ => None,
+        })
+    }
+
+    pub fn torch_bonus(&self, tree: &UpgradeTree) -> f32 {
+        self.total(tree, |effect| match effect {
+            Effect::TorchDuration(seconds) => Some(*seconds),
+            _ => None,
+        })
+    }
+
+    pub fn loot_luck(&self, tree: &UpgradeTree) -> f32 {
+        self.total(tree, |effect| match effect {
+            Effect::LootLuck(luck) => Some(*luck),
+            _ => None,
+        })
+    }
+
+    pub fn starting_attributes(&self, tree: &UpgradeTree) -> Attributes {
+        let mut attributes = Attributes::default();
+        for upgrade in &tree.upgrades {
+            if let Effect::StartingAttributes(bonus) = &upgrade.effect {
+                for _ in 0..self.level(&upgrade.id) {
+                    attributes.add(bonus);
+                }
+            }
+        }
+        attributes
+    }
+
+    /// Multiplier a prestige would give after `run`.
+    pub fn next_multiplier(&self, tree: &UpgradeTree, run: &Run) -> f32 {
+        self.multiplier
+            + run.deepest_floor.saturating_sub(1) as f32 * tree.prestige.multiplier_per_floor
+    }
+
+    pub fn can_prestige(&self, tree: &UpgradeTree, run: &Run) -> bool {
+        run.deepest_floor >= tree.prestige.min_floor
+    }
+
+    /// Trades the depth reached in `run` for a better multiplier, the rest of the run is reset by
+    /// the caller.
+    pub fn prestige(&mut self, tree: &UpgradeTree, run: &mut Run) -> bool {
+        if !self.can_prestige(tree, run) {
+            return false;
+        }
+        self.multiplier = self.next_multiplier(tree, run);
+        self.prestige += 1;
+        run.deepest_floor = 1;
+        true
+    }
+
+    fn earn(&mut self, amount: u32) {
+        self.essence += (amount as f32 * self.multiplier).round() as u32;
+    }
+}
+
+/// Upgrades bought in earlier runs, none on the first launch or when the file is unreadable.
+fn read_meta() -> Meta {
+    let Some(json) = storage::read(META_KEY) else {
+        return Meta::default();
+    };
+    serde_json::from_str(&json).unwrap_or_else(|error| {
+        warn!("Failed to read the upgrades, starting without them: {error}");
+        Meta::default()
+    })
+}
+
+fn write_meta(meta: Res<Meta>) {
+    let json = match serde_json::to_string_pretty(&*meta) {
+        Ok(json) => json,
+        Err(error) => return warn!("Failed to save the upgrades: {error}"),
+    };
+    if let Err(error) = storage::write(META_KEY, &json) {
+        warn!("Failed to save the upgrades: {error}");
+    }
+}
+
+/// Starts the party over from floor 1 with empty pockets, for a prestige or a new game.
+pub fn restart_run(
+    commands: &mut Commands,
+    party: &mut Party,
+    members: Vec<Character>,
+    first_floor: Labyrinth,
+) {
+    *party = Party { members, gold: 0 };
+    commands.insert_resource(Inventory::default());
+    commands.insert_resource(CurrentFloor::default());
+    commands.insert_resource(Explored::entrance_of(&first_floor));
+    commands.insert_resource(first_floor);
+    commands.insert_resource(ScriptState::default());
+    commands.insert_resource(DefeatedMonsters::default());
+}
+
+fn earn_essence(
+    mut meta: ResMut<Meta>,
+    mut run: ResMut<Run>,
+    mut defeated: EventReader<MonsterDefeated>,
+    mut floors: EventReader<FloorChanged>,
+    data: GameData,
+) {
+    let Some(tree) = data.upgrades() else {
+        return;
+    };
+    for _ in defeated.read() {
+        meta.earn(tree.essence.per_monster);
+    }
+    for FloorChanged(floor) in floors.read() {
+        run.deepest_floor = run.deepest_floor.max(*floor);
+        meta.earn(tree.essence.per_floor * floor);
+    }
+}
+
+/// Keeps the upgrades that tune other plugins in effect.
+fn apply_upgrades(
+    meta: Res<Meta>,
+    data: GameData,
+    mut auto_play: ResMut<AutoPlay>,
+    mut torch: ResMut<Torch>,
+    mut applied: Local<bool>,
+) {
+    let Some(tree) = data.upgrades() else {
+        return;
+    };
+    if *applied && !meta.is_changed() {
+        return;
+    }
+    *applied = true;
+    auto_play.step = AutoPlay::BASE_STEP.div_f32(1. + meta.explore_speed(tree));
+    torch.set_duration(Torch::BASE_DURATION + meta.torch_bonus(tree));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{run::Difficulty, *};
+
+    fn tree() -> UpgradeTree {
+        ron::from_str(include_str!("../../assets/data/base.upgrades.ron")).unwrap()
+    }
+
+    fn run(deepest_floor: u32) -> Run {
+        Run {
+            seed: 1,
+            difficulty: Difficulty::Normal,
+            deepest_floor,
+        }
+    }
+
+    #[test]
+    fn each_level_costs_more() {
+        let tree = tree();
+        let quick_feet = tree.get("quick_feet").unwrap();
+        let mut meta = Meta {
+            essence: 30,
+            ..default()
+        };
+        assert_eq!(meta.cost(quick_feet), 10);
+        assert!(meta.buy(quick_feet));
+        assert_eq!(meta.cost(quick_feet), 16);
+        assert!(meta.buy(quick_feet));
+        assert_eq!((meta.level("quick_feet"), meta.essence), (2, 4));
+        assert_eq!(meta.cost(quick_feet), 26);
+        assert!(!meta.buy(quick_feet));
+        assert_eq!(meta.blocker(quick_feet).unwrap(), "not enough essence");
+        assert_eq!((meta.level("quick_feet"), meta.essence), (2, 4));
+    }
+
+    #[test]
+    fn upgrades_need_their_requirements_and_stop_at_their_max() {
+        let tree = tree();
+        let scavenger = tree.get("scavenger").unwrap();
+        let mut meta = Meta {
+            essence: 10_000,
+            ..default()
+        };
+        assert!(!meta.buy(scavenger));
+        assert_eq!(meta.blocker(scavenger).unwrap(), "needs quick_feet 2");
+        meta.upgrades.insert("quick_feet".to_string(), 2);
+        for _ in 0..scavenger.max_level {
+            assert!(meta.buy(scavenger));
+        }
+        assert!(!meta.buy(scavenger));
+        assert_eq!(meta.blocker(scavenger).unwrap(), "maxed");
+    }
+
+    #[test]
+    fn prestige_pays_for_the_depth_of_the_run() {
+        let tree = tree();
+        let mut meta = Meta::default();
+        let mut shallow = run(2);
+        assert!(!meta.prestige(&tree, &mut shallow));
+        assert_eq!((meta.prestige, meta.multiplier), (0, 1.));
+
+        let mut deep = run(5);
+        assert_eq!(meta.next_multiplier(&tree, &deep), 2.);
+        assert!(meta.prestige(&tree, &mut deep));
+        assert_eq!((meta.prestige, meta.multiplier), (1, 2.));
+        // The same run cannot be paid for again
+        assert_eq!(deep.deepest_floor, 1);
+        assert!(!meta.prestige(&tree, &mut deep));
+
+        meta.earn(10);
+        assert_eq!(meta.essence, 20);
+    }
+}
@@ -0,0 +1,67 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The options the current run was started with from the New Game screen.
+#[derive(Resource, Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Run {
+    /// Generated floors come from this seed, the same seed gives the same floors
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    /// Deepest floor reached, which a prestige pays for
+    pub deepest_floor: u32,
+}
+
+impl Default for Run {
+    fn default() -> Self {
+        Run {
+            seed: rand::random(),
+            difficulty: Difficulty::Normal,
+            deepest_floor: 1,
+        }
+    }
+}
+
+impl Run {
+    /// Seed of the generated `floor`, different for every floor of the run.
+    pub fn floor_seed(&self, floor: u32) -> u64 {
+        self.seed ^ (floor as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    /// Applied to the stats of the monsters, along with their experience and gold.
+    pub fn monster_scale(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.,
+            Difficulty::Hard => 1.35,
+        }
+    }
+
+    /// The difficulty `steps` away in [`Difficulty::ALL`], wrapping around.
+    pub fn cycle(&self, steps: i32) -> Difficulty {
+        let count = Self::ALL.len() as i32;
+        let index = Self::ALL
+            .iter()
+            .position(|difficulty| difficulty == self)
+            .unwrap_or(0) as i32;
+        Self::ALL[(index + steps).rem_euclid(count) as usize]
+    }
+}
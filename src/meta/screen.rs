@@ -0,0 +1,241 @@
+use bevy::prelude::*;
+
+use super::{restart_run, run::Run, Meta};
+use crate::{
+    dungeon::labyrinth::Labyrinth,
+    loading::{GameData, LabyrinthAssets},
+    menu::{focus::CancelButton, spawn_button, ChangeState},
+    party::{starting_members, Party},
+    save::{delete_slot, SaveSlot},
+    GameState,
+};
+
+pub struct UpgradeScreenPlugin;
+
+/// The upgrades screen is reached from the menu, it lists the upgrade tree and offers to
+/// prestige once the current run is deep enough, which starts the next run right away.
+impl Plugin for UpgradeScreenPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Upgrades), setup_upgrades)
+            .add_systems(
+                Update,
+                (buy_upgrade, prestige, update_upgrades)
+                    .chain()
+                    .run_if(in_state(GameState::Upgrades)),
+            )
+            .add_systems(OnExit(GameState::Upgrades), cleanup_upgrades);
+    }
+}
+
+#[derive(Component)]
+struct UpgradesMarker;
+
+#[derive(Component)]
+struct EssenceText;
+
+/// Describes the upgrade at this index of the tree.
+#[derive(Component)]
+struct UpgradeText(usize);
+
+#[derive(Component)]
+struct BuyUpgrade(usize);
+
+#[derive(Component)]
+struct PrestigeText;
+
+#[derive(Component)]
+struct PrestigeButton;
+
+fn setup_upgrades(mut commands: Commands, data: GameData) {
+    let Some(tree) = data.upgrades() else {
+        return;
+    };
+    let text_style = TextStyle {
+        font_size: 24.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+        ..default()
+    };
+
+    commands.spawn((Camera2dBundle::default(), UpgradesMarker));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                ..default()
+            },
+            UpgradesMarker,
+            Name::new("upgrades"),
+        ))
+        .with_children(|children| {
+            children.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 36.0,
+                        ..text_style.clone()
+                    },
+                ),
+                EssenceText,
+            ));
+            for index in 0..tree.upgrades.len() {
+                children
+                    .spawn(NodeBundle {
+                        style: Style {
+                            width: Val::Px(760.0),
+                            align_items: AlignItems::Center,
+                            justify_content: JustifyContent::SpaceBetween,
+                            ..default()
+                        },
+                        ..default()
+                    })
+                    .with_children(|row| {
+                        row.spawn((
+                            TextBundle::from_section("", text_style.clone()),
+                            UpgradeText(index),
+                        ));
+                        spawn_button(row, "Buy", 120.0, BuyUpgrade(index));
+                    });
+            }
+            children.spawn((
+                TextBundle::from_section("", text_style.clone()),
+                PrestigeText,
+            ));
+            spawn_button(children, "Prestige", 200.0, PrestigeButton);
+            spawn_button(
+                children,
+                "Back",
+                140.0,
+                (ChangeState(GameState::Menu), CancelButton),
+            );
+        });
+}
+
+fn update_upgrades(
+    meta: Res<Meta>,
+    run: Res<Run>,
+    data: GameData,
+    spawned: Query<(), Added<EssenceText>>,
+    mut texts: ParamSet<(
+        Query<&mut Text, With<EssenceText>>,
+        Query<(&mut Text, &UpgradeText)>,
+        Query<&mut Text, With<PrestigeText>>,
+    )>,
+) {
+    let Some(tree) = data.upgrades() else {
+        return;
+    };
+    // Texts are spawned empty, they are filled as soon as the screen opens
+    if !meta.is_changed() && !run.is_changed() && spawned.is_empty() {
+        return;
+    }
+
+    for mut text in texts.p0().iter_mut() {
+        text.sections[0].value = format!("Essence: {} (x{:.2})", meta.essence, meta.multiplier);
+    }
+    for (mut text, UpgradeText(index)) in texts.p1().iter_mut() {
+        let Some(upgrade) = tree.upgrades.get(*index) else {
+            continue;
+        };
+        let level = meta.level(&upgrade.id);
+        let status = match meta.blocker(upgrade) {
+            Some(reason) => reason,
+            None => format!("{} essence", meta.cost(upgrade)),
+        };
+        text.sections[0].value =
+            format!("{} {level}/{} - {status}", upgrade.name, upgrade.max_level);
+    }
+    for mut text in texts.p2().iter_mut() {
+        text.sections[0].value = if meta.can_prestige(tree, &run) {
+            format!(
+                "Prestige: restart from floor 1 for a x{:.2} multiplier",
+                meta.next_multiplier(tree, &run)
+            )
+        } else {
+            format!(
+                "Prestige: reach floor {} first (deepest: {})",
+                tree.prestige.min_floor, run.deepest_floor
+            )
+        };
+    }
+}
+
+fn buy_upgrade(
+    mut meta: ResMut<Meta>,
+    data: GameData,
+    interaction_query: Query<(&Interaction, &BuyUpgrade), Changed<Interaction>>,
+) {
+    let Some(tree) = data.upgrades() else {
+        return;
+    };
+    for (interaction, BuyUpgrade(index)) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        if let Some(upgrade) = tree.upgrades.get(*index) {
+            if meta.buy(upgrade) {
+                info!("bought {} level {}", upgrade.id, meta.level(&upgrade.id));
+            }
+        }
+    }
+}
+
+/// Resets the run: the party starts over from floor 1 with empty pockets. The save of the old run
+/// is emptied right away, so that the same depth cannot be paid for twice.
+#[allow(clippy::too_many_arguments)]
+fn prestige(
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut meta: ResMut<Meta>,
+    mut run: ResMut<Run>,
+    mut party: ResMut<Party>,
+    slot: Option<Res<SaveSlot>>,
+    data: GameData,
+    labyrinths: Res<LabyrinthAssets>,
+    floors: Res<Assets<Labyrinth>>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<PrestigeButton>)>,
+) {
+    let (Some(tree), Some(classes), Some(items)) = (data.upgrades(), data.classes(), data.items())
+    else {
+        return;
+    };
+    if !interaction_query
+        .iter()
+        .any(|interaction| *interaction == Interaction::Pressed)
+    {
+        return;
+    }
+    if !meta.prestige(tree, &mut run) {
+        return;
+    }
+
+    info!(
+        "prestige {}, multiplier x{}",
+        meta.prestige, meta.multiplier
+    );
+    restart_run(
+        &mut commands,
+        &mut party,
+        starting_members(classes, items, &meta.starting_attributes(tree)),
+        labyrinths.first_floor(&floors),
+    );
+    if let Some(slot) = slot {
+        if let Err(error) = delete_slot(slot.0) {
+            warn!("Failed to empty slot {}: {error}", slot.0);
+        }
+    }
+    next_state.set(GameState::Playing);
+}
+
+fn cleanup_upgrades(mut commands: Commands, screen: Query<Entity, With<UpgradesMarker>>) {
+    for entity in screen.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
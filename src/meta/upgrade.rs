@@ -0,0 +1,62 @@
+use bevy::{asset::Asset, reflect::TypePath};
+use serde::Deserialize;
+
+use crate::party::character::Attributes;
+
+/// Permanent upgrades and prestige rules, loaded from `data/base.upgrades.ron`.
+#[derive(Deserialize, Asset, TypePath)]
+pub struct UpgradeTree {
+    pub upgrades: Vec<UpgradeDef>,
+    pub essence: EssenceRule,
+    pub prestige: PrestigeRule,
+}
+
+impl UpgradeTree {
+    pub fn get(&self, id: &str) -> Option<&UpgradeDef> {
+        self.upgrades.iter().find(|upgrade| upgrade.id == id)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UpgradeDef {
+    pub id: String,
+    pub name: String,
+    /// Applied once per level bought
+    pub effect: Effect,
+    pub max_level: u32,
+    /// Price of the first level
+    pub cost: u32,
+    /// Each level costs this much more than the previous one
+    pub cost_growth: f32,
+    /// Another upgrade and the level it must have reached first
+    #[serde(default)]
+    pub requires: Option<(String, u32)>,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug)]
+pub enum Effect {
+    /// Share of the auto-play delay cut, 0.1 makes it 10% faster
+    ExploreSpeed(f32),
+    /// Added to the attributes of the characters of a new run
+    StartingAttributes(Attributes),
+    /// Seconds added to the torch
+    TorchDuration(f32),
+    /// Makes rare loot more likely, see [`crate::loot::table::LootTables::roll`]
+    LootLuck(f32),
+}
+
+/// Essence earned during a run, before the prestige multiplier.
+#[derive(Deserialize)]
+pub struct EssenceRule {
+    pub per_monster: u32,
+    /// Multiplied by the depth of the floor reached
+    pub per_floor: u32,
+}
+
+#[derive(Deserialize)]
+pub struct PrestigeRule {
+    /// Deepest floor needed to prestige
+    pub min_floor: u32,
+    /// Multiplier gained for each floor reached below the first
+    pub multiplier_per_floor: f32,
+}
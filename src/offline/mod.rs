@@ -0,0 +1,183 @@
+pub mod sim;
+
+use std::time::Duration;
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+
+use crate::{
+    combat::{CombatLog, ExperienceGained, LootDropped},
+    loading::GameData,
+    menu::{spawn_button, ChangeState},
+    meta::Meta,
+    party::Party,
+    storage, GameState,
+};
+
+use self::sim::{simulate, Expedition, OfflineReport};
+
+pub struct OfflineProgressPlugin;
+
+/// This plugin rewards the time spent away from a save. When a save is loaded from the menu, the
+/// time since it was written is simulated in bulk and summed up on a screen shown before
+/// `GameState::Playing`, see [`AwayRewards`].
+impl Plugin for OfflineProgressPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<OfflineConfig>()
+            .add_systems(OnEnter(GameState::AwaySummary), setup_summary)
+            .add_systems(
+                OnExit(GameState::AwaySummary),
+                (claim_offline_progress, cleanup_summary),
+            );
+    }
+}
+
+#[derive(Resource)]
+pub struct OfflineConfig {
+    /// Longest absence that is rewarded
+    pub cap: Duration,
+    /// Shorter absences are ignored
+    pub min_away: Duration,
+}
+
+impl Default for OfflineConfig {
+    fn default() -> Self {
+        OfflineConfig {
+            cap: Duration::from_secs(8 * 60 * 60),
+            min_away: Duration::from_secs(60),
+        }
+    }
+}
+
+/// What was earned while the game was closed, until it is claimed on the summary screen.
+#[derive(Resource)]
+pub struct OfflineProgress(pub OfflineReport);
+
+/// Computes the offline progress of a save being loaded.
+#[derive(SystemParam)]
+pub struct AwayRewards<'w> {
+    config: Res<'w, OfflineConfig>,
+    meta: Res<'w, Meta>,
+    data: GameData<'w>,
+}
+
+impl AwayRewards<'_> {
+    /// What `party` earned on `floor` since the save was written at `saved_at`, `None` after a
+    /// short absence. The run seed and the save time make it the same every time the save is
+    /// loaded at the same moment.
+    pub fn compute(
+        &self,
+        party: &Party,
+        floor: u32,
+        run_seed: u64,
+        saved_at: u64,
+    ) -> Option<OfflineProgress> {
+        let away = Duration::from_secs(storage::now().saturating_sub(saved_at));
+        if away < self.config.min_away {
+            return None;
+        }
+        let tables = self.data.loot()?;
+        let luck = self
+            .data
+            .upgrades()
+            .map_or(0., |tree| self.meta.loot_luck(tree));
+        let expedition = Expedition {
+            power: party.power(),
+            floor,
+            tables,
+            luck,
+        };
+        let report = simulate(run_seed ^ saved_at, away, self.config.cap, &expedition);
+        info!("simulated {:?} of offline progress", report.elapsed);
+        Some(OfflineProgress(report))
+    }
+}
+
+#[derive(Component)]
+struct AwaySummaryMarker;
+
+fn format_duration(duration: Duration) -> String {
+    let minutes = duration.as_secs() / 60;
+    format!("{}h {:02}m", minutes / 60, minutes % 60)
+}
+
+fn setup_summary(mut commands: Commands, progress: Res<OfflineProgress>, data: GameData) {
+    let report = &progress.0;
+    let text_style = TextStyle {
+        font_size: 28.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+        ..default()
+    };
+    let mut lines = vec![
+        format!("Fights won: {} / {}", report.victories, report.fights),
+        format!("Gold: +{}", report.gold),
+        format!("Experience: +{}", report.experience),
+        format!("Floors cleared: {}", report.floors_cleared),
+    ];
+    if !report.loot.is_empty() {
+        if let Some(items) = data.items() {
+            let names: Vec<String> = report.loot.iter().map(|item| items.name(item)).collect();
+            lines.push(format!("Loot: {}", names.join(", ")));
+        }
+    }
+
+    commands.spawn((Camera2dBundle::default(), AwaySummaryMarker));
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(8.0),
+                    ..default()
+                },
+                ..default()
+            },
+            AwaySummaryMarker,
+            Name::new("away_summary"),
+        ))
+        .with_children(|children| {
+            children.spawn(TextBundle::from_section(
+                format!("While you were away ({})", format_duration(report.elapsed)),
+                TextStyle {
+                    font_size: 40.0,
+                    ..text_style.clone()
+                },
+            ));
+            for line in lines {
+                children.spawn(TextBundle::from_section(line, text_style.clone()));
+            }
+
+            spawn_button(children, "Continue", 180.0, ChangeState(GameState::Playing));
+        });
+}
+
+fn claim_offline_progress(
+    mut commands: Commands,
+    mut experience: EventWriter<ExperienceGained>,
+    mut loot: EventWriter<LootDropped>,
+    mut log: ResMut<CombatLog>,
+    progress: Res<OfflineProgress>,
+) {
+    let report = &progress.0;
+    experience.send(ExperienceGained(report.experience));
+    // The items go to the inventory like any other loot
+    loot.send(LootDropped {
+        gold: report.gold,
+        items: report.loot.clone(),
+        rare: false,
+    });
+    log.push(format!(
+        "While away: {} fights won, {} floor(s) cleared",
+        report.victories, report.floors_cleared
+    ));
+    commands.remove_resource::<OfflineProgress>();
+}
+
+fn cleanup_summary(mut commands: Commands, summary: Query<Entity, With<AwaySummaryMarker>>) {
+    for entity in summary.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
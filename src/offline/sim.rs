@@ -0,0 +1,168 @@
+use std::time::Duration;
+
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+
+use crate::{inventory::item::Item, loot::table::LootTables};
+
+/// In-game time one offline fight takes.
+pub const FIGHT_DURATION: Duration = Duration::from_secs(60);
+/// Fights to win before the party moves down to the next floor.
+pub const FIGHTS_PER_FLOOR: u32 = 10;
+/// Items kept from the fights, the party cannot carry more back anyway.
+pub const MAX_ITEMS: usize = 12;
+
+/// Loot tables of the monsters met while away, bosses are left out.
+const TABLES: [&str; 2] = ["beetle", "mantis"];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct OfflineReport {
+    /// Time actually simulated, after the cap is applied
+    pub elapsed: Duration,
+    pub fights: u32,
+    pub victories: u32,
+    pub gold: u32,
+    pub experience: u32,
+    pub floors_cleared: u32,
+    pub loot: Vec<Item>,
+}
+
+/// What the party brings back from its time away, see [`simulate`].
+pub struct Expedition<'a> {
+    /// Fighting strength of the party, from [`crate::party::Party::power`]
+    pub power: f32,
+    /// Floor the party was on when the game was saved
+    pub floor: u32,
+    pub tables: &'a LootTables,
+    /// Added to the chances of rare drops, from the upgrades
+    pub luck: f32,
+}
+
+/// Estimates what the party earns in `elapsed` time, capped to `cap`. Every fight won rolls the
+/// loot table of a monster of the floor.
+///
+/// Deeper floors are harder and more rewarding. The result only depends on the arguments, so the
+/// same seed and duration always give the same report.
+pub fn simulate(
+    seed: u64,
+    elapsed: Duration,
+    cap: Duration,
+    expedition: &Expedition,
+) -> OfflineReport {
+    let elapsed = elapsed.min(cap);
+    let power = expedition.power.max(0.);
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut report = OfflineReport {
+        elapsed,
+        ..Default::default()
+    };
+
+    let mut floor = expedition.floor.max(1);
+    let mut wins_on_floor = 0;
+    for _ in 0..elapsed.as_secs() / FIGHT_DURATION.as_secs() {
+        report.fights += 1;
+        let difficulty = 20. * floor as f32;
+        if !rng.gen_bool((power / (power + difficulty)) as f64) {
+            continue;
+        }
+
+        report.victories += 1;
+        report.experience += 10 * floor;
+        let table = TABLES.choose(&mut rng).unwrap();
+        let loot = expedition
+            .tables
+            .roll(table, floor, expedition.luck, &mut rng);
+        report.gold += rng.gen_range(3..=8) * floor + loot.gold;
+        for drop in loot.drops {
+            if report.loot.len() < MAX_ITEMS {
+                report.loot.push(drop.item);
+            }
+        }
+
+        wins_on_floor += 1;
+        if wins_on_floor == FIGHTS_PER_FLOOR {
+            wins_on_floor = 0;
+            floor += 1;
+            report.floors_cleared += 1;
+        }
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(60 * 60);
+
+    fn tables() -> LootTables {
+        ron::from_str(include_str!("../../assets/data/base.loot.ron")).unwrap()
+    }
+
+    fn run(seed: u64, elapsed: Duration, power: f32, tables: &LootTables) -> OfflineReport {
+        let expedition = Expedition {
+            power,
+            floor: 1,
+            tables,
+            luck: 0.,
+        };
+        simulate(seed, elapsed, 8 * HOUR, &expedition)
+    }
+
+    #[test]
+    fn same_seed_and_duration_give_the_same_report() {
+        let tables = tables();
+        for seed in 0..20 {
+            assert_eq!(
+                run(seed, 3 * HOUR, 80., &tables),
+                run(seed, 3 * HOUR, 80., &tables)
+            );
+        }
+    }
+
+    #[test]
+    fn time_away_is_capped() {
+        let tables = tables();
+        let report = run(1, 100 * HOUR, 80., &tables);
+        assert_eq!(report.elapsed, 8 * HOUR);
+        assert_eq!(report.fights, 8 * 60);
+    }
+
+    #[test]
+    fn a_powerless_party_wins_nothing() {
+        let report = run(2, 2 * HOUR, 0., &tables());
+        assert_eq!(report.fights, 120);
+        assert_eq!(report.victories, 0);
+        assert_eq!(report.gold, 0);
+        assert!(report.loot.is_empty());
+    }
+
+    #[test]
+    fn victories_bring_rewards_and_floors() {
+        let report = run(3, 8 * HOUR, 10_000., &tables());
+        assert!(report.victories > 400);
+        assert!(report.experience >= report.victories * 10);
+        assert_eq!(report.floors_cleared, report.victories / FIGHTS_PER_FLOOR);
+        assert!(!report.loot.is_empty());
+        assert!(report.loot.len() <= MAX_ITEMS);
+    }
+
+    #[test]
+    fn loot_comes_from_the_tables() {
+        let tables = tables();
+        let report = run(4, 8 * HOUR, 10_000., &tables);
+        let known = [
+            "healing_herb",
+            "mana_dew",
+            "rusty_dagger",
+            "short_sword",
+            "oak_staff",
+            "leather_armour",
+            "chitin_mail",
+            "lucky_charm",
+            "amber_ring",
+        ];
+        for item in &report.loot {
+            assert!(known.contains(&item.id.as_str()), "{}", item.id);
+        }
+    }
+}
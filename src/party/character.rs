@@ -0,0 +1,201 @@
+use serde::{Deserialize, Serialize};
+
+use super::class::{ClassDef, ClassTable};
+use crate::{
+    combat::sim::{Combatant, Side, Stats},
+    inventory::item::{Equipment, ItemDatabase},
+};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Attribute {
+    Strength,
+    Dexterity,
+    Intelligence,
+    Vitality,
+}
+
+impl Attribute {
+    pub const ALL: [Attribute; 4] = [
+        Attribute::Strength,
+        Attribute::Dexterity,
+        Attribute::Intelligence,
+        Attribute::Vitality,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Attribute::Strength => "STR",
+            Attribute::Dexterity => "DEX",
+            Attribute::Intelligence => "INT",
+            Attribute::Vitality => "VIT",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct Attributes {
+    pub strength: i32,
+    pub dexterity: i32,
+    pub intelligence: i32,
+    pub vitality: i32,
+}
+
+impl Attributes {
+    pub fn get(&self, attribute: Attribute) -> i32 {
+        match attribute {
+            Attribute::Strength => self.strength,
+            Attribute::Dexterity => self.dexterity,
+            Attribute::Intelligence => self.intelligence,
+            Attribute::Vitality => self.vitality,
+        }
+    }
+
+    fn get_mut(&mut self, attribute: Attribute) -> &mut i32 {
+        match attribute {
+            Attribute::Strength => &mut self.strength,
+            Attribute::Dexterity => &mut self.dexterity,
+            Attribute::Intelligence => &mut self.intelligence,
+            Attribute::Vitality => &mut self.vitality,
+        }
+    }
+
+    pub fn add(&mut self, other: &Attributes) {
+        for attribute in Attribute::ALL {
+            *self.get_mut(attribute) += other.get(attribute);
+        }
+    }
+}
+
+/// A member of the party.
+///
+/// Only what the player built up is saved, the fighting stats are derived from the class table
+/// and the equipment by [`Character::refresh`] so that balance changes apply to existing saves.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct Character {
+    pub name: String,
+    pub class: String,
+    pub level: u32,
+    /// Experience earned towards the next level
+    pub experience: u32,
+    pub attributes: Attributes,
+    /// Attribute points left to spend from previous level ups
+    pub unspent_points: u32,
+    pub equipment: Equipment,
+    pub hp: i32,
+    pub mp: i32,
+    #[serde(skip)]
+    derived: Stats,
+}
+
+impl Character {
+    pub fn new(name: impl Into<String>, class: &ClassDef, items: &ItemDatabase) -> Self {
+        let mut character = Character {
+            name: name.into(),
+            class: class.name.clone(),
+            level: 1,
+            experience: 0,
+            attributes: class.attributes,
+            unspent_points: 0,
+            equipment: Equipment::default(),
+            hp: 0,
+            mp: 0,
+            derived: Stats::default(),
+        };
+        character.refresh(class, items);
+        character.restore();
+        character
+    }
+
+    /// Recomputes the fighting stats from the class, level, attributes and equipment.
+    pub fn refresh(&mut self, class: &ClassDef, items: &ItemDatabase) {
+        let levels = self.level.saturating_sub(1) as i32;
+        let attributes = &self.attributes;
+        let max_hp = class.hp + class.hp_per_level * levels + attributes.vitality * 2;
+        let max_mp = class.mp + class.mp_per_level * levels + attributes.intelligence;
+        self.derived = Stats {
+            max_hp,
+            hp: max_hp,
+            max_mp,
+            mp: max_mp,
+            attack: attributes.strength,
+            defense: attributes.vitality / 2 + attributes.dexterity / 4,
+            magic: attributes.intelligence,
+            speed: attributes.dexterity,
+        };
+        for item in self.equipment.items() {
+            if let Some(def) = items.get(&item.id) {
+                def.bonus.apply(&mut self.derived);
+            }
+            for affix in item.affixes() {
+                affix.bonus.apply(&mut self.derived);
+            }
+        }
+        self.hp = self.hp.min(self.derived.max_hp);
+        self.mp = self.mp.min(self.derived.max_mp);
+    }
+
+    pub fn stats(&self) -> Stats {
+        Stats {
+            hp: self.hp,
+            mp: self.mp,
+            ..self.derived
+        }
+    }
+
+    pub fn combatant(&self) -> Combatant {
+        Combatant::new(self.name.clone(), Side::Party, self.stats())
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.hp > 0
+    }
+
+    pub fn health_ratio(&self) -> f32 {
+        self.hp as f32 / self.derived.max_hp.max(1) as f32
+    }
+
+    /// Back to full health and mana.
+    pub fn restore(&mut self) {
+        self.hp = self.derived.max_hp;
+        self.mp = self.derived.max_mp;
+    }
+
+    /// Adds experience and returns how many levels were gained.
+    pub fn gain_experience(
+        &mut self,
+        amount: u32,
+        table: &ClassTable,
+        items: &ItemDatabase,
+    ) -> u32 {
+        let Some(class) = table.class(&self.class) else {
+            return 0;
+        };
+        self.experience += amount;
+        let mut levels = 0;
+        while self.level < table.max_level
+            && self.experience >= table.experience.to_next(self.level)
+        {
+            self.experience -= table.experience.to_next(self.level);
+            self.level += 1;
+            self.attributes.add(&class.growth);
+            self.unspent_points += table.points_per_level;
+            levels += 1;
+        }
+        if levels > 0 {
+            self.refresh(class, items);
+            self.restore();
+        }
+        levels
+    }
+
+    /// Spends one of the points earned on level up.
+    pub fn raise(&mut self, attribute: Attribute, class: &ClassDef, items: &ItemDatabase) -> bool {
+        if self.unspent_points == 0 {
+            return false;
+        }
+        self.unspent_points -= 1;
+        *self.attributes.get_mut(attribute) += 1;
+        self.refresh(class, items);
+        true
+    }
+}
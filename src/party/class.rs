@@ -0,0 +1,48 @@
+use bevy::{asset::Asset, reflect::TypePath};
+use serde::Deserialize;
+
+use super::character::Attributes;
+
+/// Classes and growth rules, loaded from `data/party.classes.ron`.
+#[derive(Deserialize, Asset, TypePath)]
+pub struct ClassTable {
+    pub classes: Vec<ClassDef>,
+    pub experience: ExperienceCurve,
+    /// Attribute points handed out on each level up, spent by the player
+    pub points_per_level: u32,
+    pub max_level: u32,
+    /// Names and classes of the characters a new game starts with
+    pub starting_party: Vec<(String, String)>,
+}
+
+impl ClassTable {
+    pub fn class(&self, name: &str) -> Option<&ClassDef> {
+        self.classes.iter().find(|class| class.name == name)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ClassDef {
+    pub name: String,
+    /// Attributes at level 1
+    pub attributes: Attributes,
+    /// Attributes gained automatically on each level up
+    pub growth: Attributes,
+    pub hp: i32,
+    pub mp: i32,
+    pub hp_per_level: i32,
+    pub mp_per_level: i32,
+}
+
+/// Experience needed to go from level `n` to `n + 1` is `base * factor^(n - 1)`.
+#[derive(Deserialize)]
+pub struct ExperienceCurve {
+    pub base: u32,
+    pub factor: f32,
+}
+
+impl ExperienceCurve {
+    pub fn to_next(&self, level: u32) -> u32 {
+        (self.base as f32 * self.factor.powi(level.saturating_sub(1) as i32)).round() as u32
+    }
+}
@@ -0,0 +1,141 @@
+pub mod character;
+pub mod class;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::{sim::Combatant, ExperienceGained},
+    inventory::item::ItemDatabase,
+    loading::GameData,
+    message::GameMessage,
+    GameState,
+};
+
+use self::{
+    character::{Attributes, Character},
+    class::ClassTable,
+};
+
+pub struct PartyPlugin;
+
+/// This plugin owns the [`Party`]: it builds the starting characters from the class table and
+/// shares the experience earned in fights between them.
+impl Plugin for PartyPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Party>()
+            .add_systems(OnExit(GameState::Loading), create_party)
+            .add_systems(Update, gain_experience);
+    }
+}
+
+/// Most characters a party can hold.
+pub const MAX_PARTY_SIZE: usize = 4;
+
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug, Default)]
+pub struct Party {
+    pub members: Vec<Character>,
+    pub gold: u32,
+}
+
+impl Party {
+    pub fn combatants(&self) -> Vec<Combatant> {
+        self.members.iter().map(Character::combatant).collect()
+    }
+
+    /// Rough fighting strength of the party, used to estimate progress made without playing.
+    pub fn power(&self) -> f32 {
+        self.members
+            .iter()
+            .map(|member| {
+                let stats = member.stats();
+                (stats.attack + stats.defense + stats.magic + stats.speed) as f32
+                    + stats.max_hp as f32 / 5.
+            })
+            .sum()
+    }
+
+    /// Remaining hit points of the whole party, between 0 and 1.
+    pub fn health_ratio(&self) -> f32 {
+        let (hp, max_hp) = self.members.iter().fold((0, 0), |(hp, max_hp), member| {
+            (hp + member.hp, max_hp + member.stats().max_hp)
+        });
+        if max_hp == 0 {
+            return 0.;
+        }
+        hp as f32 / max_hp as f32
+    }
+
+    /// Recomputes the stats of every member, needed after loading a save.
+    pub fn refresh(&mut self, table: &ClassTable, items: &ItemDatabase) {
+        for member in self.members.iter_mut() {
+            match table.class(&member.class) {
+                Some(class) => member.refresh(class, items),
+                None => warn!("{} has an unknown class: {}", member.name, member.class),
+            }
+        }
+    }
+}
+
+/// Characters of a new run, as described by the class table. `bonus` is added to the attributes
+/// of each of them.
+pub fn starting_members(
+    table: &ClassTable,
+    items: &ItemDatabase,
+    bonus: &Attributes,
+) -> Vec<Character> {
+    table
+        .starting_party
+        .iter()
+        .take(MAX_PARTY_SIZE)
+        .filter_map(|(name, class)| match table.class(class) {
+            Some(class) => {
+                let mut character = Character::new(name.as_str(), class, items);
+                character.attributes.add(bonus);
+                character.refresh(class, items);
+                character.restore();
+                Some(character)
+            }
+            None => {
+                warn!("{name} has an unknown class: {class}");
+                None
+            }
+        })
+        .collect()
+}
+
+/// Builds the starting party, it is replaced when a save is loaded.
+pub fn create_party(mut party: ResMut<Party>, data: GameData) {
+    let (table, items) = (data.classes().unwrap(), data.items().unwrap());
+    party.members = starting_members(table, items, &Attributes::default());
+}
+
+/// Splits the experience earned between the members still standing.
+fn gain_experience(
+    mut experience: EventReader<ExperienceGained>,
+    mut party: ResMut<Party>,
+    mut messages: EventWriter<GameMessage>,
+    data: GameData,
+) {
+    let (Some(table), Some(items)) = (data.classes(), data.items()) else {
+        return;
+    };
+    for ExperienceGained(amount) in experience.read() {
+        let alive = party.members.iter().filter(|m| m.is_alive()).count() as u32;
+        if alive == 0 {
+            continue;
+        }
+        let share = (amount / alive).max(1);
+        for member in party.members.iter_mut().filter(|m| m.is_alive()) {
+            if member.gain_experience(share, table, items) > 0 {
+                messages.send(
+                    GameMessage::success(format!(
+                        "{} reached level {}!",
+                        member.name, member.level
+                    ))
+                    .toast(),
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use serde_json::{json, Value};
+
+use super::{SaveError, SAVE_VERSION};
+
+/// Upgrades a save by one version, in place.
+type Migration = fn(&mut Value) -> Result<(), SaveError>;
+
+/// `MIGRATIONS[n]` turns a version `n + 1` save into a version `n + 2` one. Append a step here
+/// whenever [`SAVE_VERSION`] is bumped, along with a fixture of the old version in
+/// `tests/fixtures`, and never edit the existing ones.
+const MIGRATIONS: [Migration; 3] = [v1_to_v2, v2_to_v3, v3_to_v4];
+
+/// Brings a raw save of any known version up to [`SAVE_VERSION`].
+pub fn upgrade(mut save: Value) -> Result<Value, SaveError> {
+    let mut version = version_of(&save)?;
+    if version == 0 || version > SAVE_VERSION {
+        return Err(SaveError::UnsupportedVersion(version));
+    }
+    while version < SAVE_VERSION {
+        MIGRATIONS[version as usize - 1](&mut save)?;
+        version += 1;
+        save["version"] = json!(version);
+    }
+    Ok(save)
+}
+
+fn version_of(save: &Value) -> Result<u32, SaveError> {
+    save.get("version")
+        .and_then(Value::as_u64)
+        .and_then(|version| u32::try_from(version).ok())
+        .ok_or(SaveError::Invalid("missing version"))
+}
+
+fn fields(save: &mut Value) -> Result<&mut serde_json::Map<String, Value>, SaveError> {
+    save.as_object_mut()
+        .ok_or(SaveError::Invalid("a save must be an object"))
+}
+
+/// v2 remembers the puzzles already solved on the floor, older saves start them over.
+fn v1_to_v2(save: &mut Value) -> Result<(), SaveError> {
+    fields(save)?.insert("script".into(), json!({ "spent": [], "unlocked": [] }));
+    Ok(())
+}
+
+/// v3 runs remember the deepest floor they reached, which older saves take to be the current one.
+fn v2_to_v3(save: &mut Value) -> Result<(), SaveError> {
+    let fields = fields(save)?;
+    let floor = fields.get("floor").cloned().unwrap_or(json!(1));
+    fields
+        .get_mut("run")
+        .and_then(Value::as_object_mut)
+        .ok_or(SaveError::Invalid("missing run"))?
+        .insert("deepest_floor".into(), floor);
+    Ok(())
+}
+
+/// v4 keeps the monsters defeated on the floor, older saves bring them back.
+fn v3_to_v4(save: &mut Value) -> Result<(), SaveError> {
+    fields(save)?.insert("defeated".into(), json!([]));
+    Ok(())
+}
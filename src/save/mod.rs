@@ -0,0 +1,547 @@
+mod migrate;
+
+use bevy::{app::AppExit, ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    combat::DefeatedMonsters,
+    dungeon::{
+        camera3d::{CameraDirection, GridPosition, Player},
+        cell_center,
+        config::DungeonConfig,
+        labyrinth::{CurrentFloor, Explored, FloorChanged, Labyrinth},
+        script::ScriptState,
+    },
+    idle::{AutoPlay, PlaySpeed, StopRules, Tactic},
+    inventory::Inventory,
+    loading::GameData,
+    meta::run::Run,
+    offline::AwayRewards,
+    party::Party,
+    storage, GameState, PlayingState,
+};
+
+pub struct SavePlugin;
+
+/// This plugin writes the game state to save slots and restores it when a slot is loaded from the
+/// menu or the pause menu. The game is saved automatically on floor change, when leaving
+/// `GameState::Playing` and when the app exits. F5 saves to the current slot.
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveRequest>()
+            .add_event::<SaveLoaded>()
+            .add_systems(
+                Update,
+                click_load_button.run_if(
+                    in_state(GameState::Menu)
+                        .or_else(in_state(GameState::LoadGame))
+                        .or_else(in_state(PlayingState::Paused)),
+                ),
+            )
+            .add_systems(
+                Update,
+                (handle_input, autosave_on_floor_change, restore_player)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                save_game
+                    .after(handle_input)
+                    .after(autosave_on_floor_change)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), autosave)
+            .add_systems(
+                Last,
+                autosave
+                    .run_if(on_event::<AppExit>())
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+/// Version written in new saves. Bump it whenever the format changes and add the matching step
+/// to the migrations, so that older saves keep loading.
+pub const SAVE_VERSION: u32 = 4;
+pub const SLOT_COUNT: u8 = 3;
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct SaveData {
+    pub version: u32,
+    /// Unix timestamp, in seconds
+    pub saved_at: u64,
+    pub floor: u32,
+    pub labyrinth: Labyrinth,
+    pub player: PlayerSave,
+    pub explored: Vec<(i32, i32)>,
+    pub settings: SettingsSave,
+    pub party: Party,
+    pub inventory: Inventory,
+    pub run: Run,
+    pub script: ScriptState,
+    pub defeated: DefeatedMonsters,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct PlayerSave {
+    pub position: (i32, i32),
+    pub direction: CameraDirection,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct SettingsSave {
+    pub speed: PlaySpeed,
+    pub tactic: Tactic,
+    pub stop_rules: StopRules,
+}
+
+#[derive(Debug)]
+pub enum SaveError {
+    Storage(storage::StorageError),
+    Format(serde_json::Error),
+    /// The save is valid JSON but not shaped like a save
+    Invalid(&'static str),
+    /// The save was written by a newer version of the game
+    UnsupportedVersion(u32),
+    Missing,
+    /// The classes and items a save refers to are not loaded yet
+    DataNotLoaded,
+}
+
+impl std::fmt::Display for SaveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SaveError::Storage(error) => write!(f, "could not access the save: {error}"),
+            SaveError::Format(error) => write!(f, "the save is corrupted: {error}"),
+            SaveError::Invalid(reason) => write!(f, "the save is corrupted: {reason}"),
+            SaveError::UnsupportedVersion(version) => write!(
+                f,
+                "the save comes from a newer version of the game (v{version}, supported up to v{SAVE_VERSION})"
+            ),
+            SaveError::Missing => write!(f, "there is no save in this slot"),
+            SaveError::DataNotLoaded => write!(f, "the game data is still loading"),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+fn slot_key(slot: u8) -> String {
+    format!("slot_{slot}.json")
+}
+
+pub fn write_slot(slot: u8, data: &SaveData) -> Result<(), SaveError> {
+    let json = serde_json::to_string_pretty(data).map_err(SaveError::Format)?;
+    storage::write(&slot_key(slot), &json).map_err(SaveError::Storage)
+}
+
+/// Empties `slot`, for runs that are over.
+pub fn delete_slot(slot: u8) -> Result<(), SaveError> {
+    storage::remove(&slot_key(slot)).map_err(SaveError::Storage)
+}
+
+pub fn read_slot(slot: u8) -> Result<SaveData, SaveError> {
+    let json = storage::read(&slot_key(slot)).ok_or(SaveError::Missing)?;
+    parse(&json)
+}
+
+/// Reads a save of any supported version, upgrading it to the current one.
+pub fn parse(json: &str) -> Result<SaveData, SaveError> {
+    let raw = serde_json::from_str(json).map_err(SaveError::Format)?;
+    serde_json::from_value(migrate::upgrade(raw)?).map_err(SaveError::Format)
+}
+
+/// Slot used by autosaves, the one the run was started in or loaded from. Nothing is saved
+/// until a slot is chosen, so that no slot gets overwritten behind the player's back.
+#[derive(Resource)]
+pub struct SaveSlot(pub u8);
+
+#[derive(Event)]
+pub struct SaveRequest;
+
+/// Sent when a save is loaded from the pause menu, for the content of the floor to be replaced.
+/// Unlike [`FloorChanged`], it earns nothing and saves nothing.
+#[derive(Event)]
+pub struct SaveLoaded;
+
+/// Put on menu buttons that load the given slot.
+#[derive(Component)]
+pub struct LoadSlot(pub u8);
+
+/// Player state of a loaded save, applied once the player has been spawned.
+#[derive(Resource)]
+struct PendingPlayer(PlayerSave);
+
+/// Everything that goes into a save.
+#[derive(SystemParam)]
+pub struct GameSnapshot<'w, 's> {
+    labyrinth: Res<'w, Labyrinth>,
+    explored: Res<'w, Explored>,
+    floor: Res<'w, CurrentFloor>,
+    auto_play: Res<'w, AutoPlay>,
+    party: Res<'w, Party>,
+    inventory: Res<'w, Inventory>,
+    script: Res<'w, ScriptState>,
+    defeated: Res<'w, DefeatedMonsters>,
+    run: Res<'w, Run>,
+    player: Query<'w, 's, (&'static GridPosition, &'static CameraDirection), With<Player>>,
+}
+
+impl GameSnapshot<'_, '_> {
+    pub fn capture(&self) -> SaveData {
+        let (position, direction) = self
+            .player
+            .get_single()
+            .map(|(position, direction)| ((position.0, position.1), *direction))
+            .unwrap_or_default();
+        let mut explored: Vec<(i32, i32)> = self.explored.0.iter().map(|p| (p.0, p.1)).collect();
+        explored.sort();
+
+        SaveData {
+            version: SAVE_VERSION,
+            saved_at: storage::now(),
+            floor: self.floor.0,
+            labyrinth: self.labyrinth.clone(),
+            player: PlayerSave {
+                position,
+                direction,
+            },
+            explored,
+            settings: SettingsSave {
+                speed: self.auto_play.speed,
+                tactic: self.auto_play.tactic,
+                stop_rules: self.auto_play.stop_rules,
+            },
+            party: self.party.clone(),
+            inventory: self.inventory.clone(),
+            run: *self.run,
+            script: self.script.clone(),
+            defeated: self.defeated.clone(),
+        }
+    }
+}
+
+fn handle_input(keyboard_input: Res<Input<KeyCode>>, mut requests: EventWriter<SaveRequest>) {
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        requests.send(SaveRequest);
+    }
+}
+
+fn autosave_on_floor_change(
+    mut floor_changes: EventReader<FloorChanged>,
+    mut requests: EventWriter<SaveRequest>,
+) {
+    if floor_changes.read().next().is_some() {
+        requests.send(SaveRequest);
+    }
+}
+
+fn save_game(
+    mut requests: EventReader<SaveRequest>,
+    slot: Option<Res<SaveSlot>>,
+    snapshot: GameSnapshot,
+) {
+    if requests.read().next().is_none() {
+        return;
+    }
+    let Some(slot) = slot else {
+        warn!("No save slot was chosen, the game was not saved");
+        return;
+    };
+    match write_slot(slot.0, &snapshot.capture()) {
+        Ok(()) => info!("saved to slot {}", slot.0),
+        Err(error) => warn!("Failed to save to slot {}: {error}", slot.0),
+    }
+}
+
+fn autosave(slot: Option<Res<SaveSlot>>, snapshot: GameSnapshot) {
+    let Some(slot) = slot else {
+        return;
+    };
+    if let Err(error) = write_slot(slot.0, &snapshot.capture()) {
+        warn!("Failed to autosave to slot {}: {error}", slot.0);
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub fn click_load_button(
+    mut commands: Commands,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut next_playing_state: ResMut<NextState<PlayingState>>,
+    mut loads: EventWriter<SaveLoaded>,
+    state: Res<State<GameState>>,
+    mut auto_play: ResMut<AutoPlay>,
+    mut party: ResMut<Party>,
+    game_data: GameData,
+    away_rewards: AwayRewards,
+    interaction_query: Query<(Entity, &Interaction, &LoadSlot), Changed<Interaction>>,
+    children: Query<&Children>,
+    mut texts: Query<&mut Text>,
+) {
+    for (button, interaction, LoadSlot(slot)) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let data = match load_slot(*slot, &game_data) {
+            Ok(data) => data,
+            Err(error) => {
+                warn!("Failed to load slot {slot}: {error}");
+                // Told on the button, like the load menu does for slots it cannot read
+                for child in children.get(button).into_iter().flatten() {
+                    if let Ok(mut text) = texts.get_mut(*child) {
+                        text.sections[0].value = format!("Slot {slot}: {error}");
+                        text.sections[0].style.font_size = 20.0;
+                        text.sections[0].style.color = Color::rgb(0.9, 0.3, 0.3);
+                    }
+                }
+                continue;
+            }
+        };
+
+        commands.insert_resource(data.labyrinth);
+        commands.insert_resource(CurrentFloor(data.floor));
+        commands.insert_resource(Explored(
+            data.explored
+                .into_iter()
+                .map(|(x, z)| GridPosition(x, z))
+                .collect(),
+        ));
+        commands.insert_resource(PendingPlayer(data.player));
+        commands.insert_resource(SaveSlot(*slot));
+        auto_play.speed = data.settings.speed;
+        auto_play.tactic = data.settings.tactic;
+        auto_play.stop_rules = data.settings.stop_rules;
+        *party = data.party;
+        commands.insert_resource(data.inventory);
+        commands.insert_resource(data.run);
+        commands.insert_resource(data.script);
+        commands.insert_resource(data.defeated);
+        if *state.get() == GameState::Playing {
+            // Loaded from the pause menu, the floor's monsters are replaced. Items, levers and
+            // markers follow the labyrinth and floor inserted above
+            loads.send(SaveLoaded);
+            next_state.set(GameState::Playing);
+        } else if let Some(progress) =
+            away_rewards.compute(&party, data.floor, data.run.seed, data.saved_at)
+        {
+            // Show what happened while the game was closed before jumping into the dungeon
+            commands.insert_resource(progress);
+            next_state.set(GameState::AwaySummary);
+        } else {
+            next_state.set(GameState::Playing);
+        }
+        next_playing_state.set(PlayingState::Exploring);
+    }
+}
+
+/// Reads `slot` and derives the fighting stats of its party, which are not saved.
+fn load_slot(slot: u8, game_data: &GameData) -> Result<SaveData, SaveError> {
+    let (Some(classes), Some(items)) = (game_data.classes(), game_data.items()) else {
+        return Err(SaveError::DataNotLoaded);
+    };
+    let mut data = read_slot(slot)?;
+    data.party.refresh(classes, items);
+    Ok(data)
+}
+
+fn restore_player(
+    mut commands: Commands,
+    pending: Option<Res<PendingPlayer>>,
+    config: Res<DungeonConfig>,
+    mut player: Query<(&mut Transform, &mut CameraDirection, &mut GridPosition), With<Player>>,
+) {
+    let Some(pending) = pending else {
+        return;
+    };
+    let Ok((mut transform, mut direction, mut position)) = player.get_single_mut() else {
+        return;
+    };
+    let save = pending.0;
+    *position = GridPosition(save.position.0, save.position.1);
+    *direction = save.direction;
+    transform.translation = cell_center(config.size, save.position);
+    transform.rotation = save.direction.rotation();
+    commands.remove_resource::<PendingPlayer>();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        dungeon::{
+            generate::generate,
+            labyrinth::Decoration,
+            script::{Script, Trigger},
+        },
+        idle::{PlaySpeed, StopRules, Tactic},
+        inventory::{
+            item::{Item, ItemDatabase},
+            INVENTORY_SIZE,
+        },
+        meta::run::{Difficulty, Run},
+        party::{character::Attributes, class::ClassTable, starting_members},
+    };
+
+    fn tables() -> (ClassTable, ItemDatabase) {
+        let classes = ron::from_str(include_str!("../../assets/data/party.classes.ron")).unwrap();
+        let items = ron::from_str(include_str!("../../assets/data/base.items.ron")).unwrap();
+        (classes, items)
+    }
+
+    fn party() -> Party {
+        let (classes, items) = tables();
+        let mut party = Party {
+            members: starting_members(&classes, &items, &Attributes::default()),
+            gold: 123,
+        };
+        party.members[0].hp -= 3;
+        party
+    }
+
+    fn save() -> SaveData {
+        let labyrinth = generate(7, 3);
+        let mut explored: Vec<(i32, i32)> = labyrinth.cells.keys().copied().take(6).collect();
+        explored.sort();
+        let mut inventory = Inventory::default();
+        inventory.add(Item::new("healing_herb")).unwrap();
+        let script: Script =
+            ron::from_str(include_str!("../../assets/data/floor1.script.ron")).unwrap();
+        let mut script_state = ScriptState::default();
+        script.fire(&Trigger::Enter((0, 1)), &mut script_state);
+        SaveData {
+            version: SAVE_VERSION,
+            saved_at: 1_700_000_000,
+            floor: 3,
+            player: PlayerSave {
+                position: explored[2],
+                direction: CameraDirection::East,
+            },
+            labyrinth,
+            explored,
+            settings: SettingsSave {
+                speed: PlaySpeed::default(),
+                tactic: Tactic::default(),
+                stop_rules: StopRules::default(),
+            },
+            party: party(),
+            inventory,
+            run: Run {
+                seed: 99,
+                difficulty: Difficulty::Hard,
+                deepest_floor: 4,
+            },
+            script: script_state,
+            defeated: DefeatedMonsters([(1, 0), (2, 3)].into()),
+        }
+    }
+
+    #[test]
+    fn a_save_reloads_identically() {
+        let save = save();
+        let json = serde_json::to_string_pretty(&save).unwrap();
+        let mut loaded = parse(&json).unwrap();
+        assert_eq!(loaded.labyrinth, save.labyrinth);
+        assert_eq!(loaded.explored, save.explored);
+        // Fighting stats are not saved, loading derives them again
+        let (classes, items) = tables();
+        loaded.party.refresh(&classes, &items);
+        assert_eq!(loaded, save);
+    }
+
+    #[test]
+    fn a_reloaded_save_is_written_the_same() {
+        let json = serde_json::to_string_pretty(&save()).unwrap();
+        let again = serde_json::to_string_pretty(&parse(&json).unwrap()).unwrap();
+        assert_eq!(json, again);
+    }
+
+    /// A save of every version released, oldest first.
+    const FIXTURES: [&str; 4] = [
+        include_str!("../../tests/fixtures/save_v1.json"),
+        include_str!("../../tests/fixtures/save_v2.json"),
+        include_str!("../../tests/fixtures/save_v3.json"),
+        include_str!("../../tests/fixtures/save_v4.json"),
+    ];
+
+    #[test]
+    fn released_saves_still_load() {
+        for fixture in FIXTURES {
+            let save = parse(fixture).unwrap();
+            assert_eq!(save.version, SAVE_VERSION);
+            assert_eq!(save.floor, 2);
+            assert_eq!(save.saved_at, 1_760_000_000);
+            assert_eq!(save.labyrinth.entrance, (0, 0));
+            assert!(save.labyrinth.can_move((0, 0), CameraDirection::North));
+            let cell = &save.labyrinth.cells[&(0, 1)];
+            assert_eq!(cell.items, vec![Item::new("rusty_key")]);
+            assert_eq!(cell.decoration, Some(Decoration::Bones));
+            assert!(cell.feature.as_ref().unwrap().hidden);
+            assert!(save.labyrinth.cells[&(0, 0)].items.is_empty());
+            assert_eq!(
+                save.player,
+                PlayerSave {
+                    position: (0, 1),
+                    direction: CameraDirection::South,
+                }
+            );
+            assert_eq!(save.explored, vec![(0, 0), (0, 1)]);
+            assert_eq!(save.settings.speed, PlaySpeed::X2);
+            assert_eq!(save.settings.tactic, Tactic::Cautious);
+            assert_eq!(save.settings.stop_rules.low_health, Some(0.5));
+            assert_eq!(save.party.gold, 57);
+            let fighter = &save.party.members[0];
+            assert_eq!((fighter.level, fighter.hp), (3, 21));
+            assert_eq!(fighter.equipment.weapon, Some(Item::new("short_sword")));
+            assert_eq!(save.inventory.slots[0], Some(Item::new("healing_herb")));
+            assert_eq!(save.inventory.slots.len(), INVENTORY_SIZE);
+            assert_eq!(
+                save.run,
+                Run {
+                    seed: 424_242,
+                    difficulty: Difficulty::Hard,
+                    deepest_floor: 2,
+                }
+            );
+        }
+    }
+
+    #[test]
+    fn migrated_saves_match_the_current_format() {
+        let [v1, v2, v3, current] = FIXTURES.map(|fixture| parse(fixture).unwrap());
+        // Version 1 did not keep the puzzles solved, they start over
+        assert_eq!(v1.script, ScriptState::default());
+        assert_eq!(
+            serde_json::to_value(&v2.script).unwrap(),
+            serde_json::json!({ "spent": [2], "unlocked": ["D1"] })
+        );
+        assert_eq!(
+            SaveData {
+                script: v2.script.clone(),
+                ..v1
+            },
+            v2
+        );
+        // Version 2 runs take the floor they are on as the deepest one
+        assert_eq!(v2, v3);
+        // Version 3 did not keep the monsters defeated, they come back
+        assert_eq!(v3.defeated, DefeatedMonsters::default());
+        assert_eq!(current.defeated.0, [(0, 1)].into());
+        assert_eq!(
+            SaveData {
+                defeated: current.defeated.clone(),
+                ..v3
+            },
+            current
+        );
+    }
+
+    #[test]
+    fn saves_from_a_newer_version_are_refused() {
+        let mut save: serde_json::Value = serde_json::from_str(FIXTURES[3]).unwrap();
+        save["version"] = serde_json::json!(SAVE_VERSION + 1);
+        assert!(matches!(
+            parse(&save.to_string()),
+            Err(SaveError::UnsupportedVersion(version)) if version == SAVE_VERSION + 1
+        ));
+    }
+}
@@ -0,0 +1,348 @@
+use bevy::{
+    prelude::*,
+    window::{PresentMode, PrimaryWindow, WindowMode},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{storage, ui::compass::CompassSettings};
+
+pub struct SettingsPlugin;
+
+/// This plugin reads the player's settings at startup and writes them back whenever they change.
+/// Each plugin applies the settings it cares about when they change, the window ones are applied
+/// here.
+impl Plugin for SettingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Settings>()
+            .add_systems(Startup, load_settings)
+            .add_systems(
+                Update,
+                (apply_window, save_settings).run_if(resource_changed::<Settings>()),
+            );
+    }
+}
+
+const SETTINGS_KEY: &str = "settings.ron";
+
+/// Options of the settings menu, kept from one session to the next.
+#[derive(Resource, Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct Settings {
+    /// Volumes go from 0 to 1, music and sound effects are scaled by the master volume
+    pub master_volume: f32,
+    pub music_volume: f32,
+    pub sfx_volume: f32,
+    pub fullscreen: bool,
+    pub vsync: bool,
+    /// Resolution of the 3D views relative to the screen pixels they cover, lower is faster
+    pub render_scale: f32,
+    /// Vertical field of view of the dungeon camera, in degrees
+    pub fov: f32,
+    /// How fast the view glides to the next cell or heading, 0 moves instantly
+    pub move_speed: f32,
+    pub compass: CompassSettings,
+    pub keys: KeyBindings,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            master_volume: 1.,
+            music_volume: 0.8,
+            sfx_volume: 0.8,
+            fullscreen: false,
+            vsync: true,
+            render_scale: 1.,
+            fov: 45.,
+            move_speed: 12.,
+            compass: CompassSettings::default(),
+            keys: KeyBindings::default(),
+        }
+    }
+}
+
+/// A line of the settings menu that is changed step by step, toggles flip whichever way.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Setting {
+    MasterVolume,
+    MusicVolume,
+    SfxVolume,
+    Fullscreen,
+    Vsync,
+    RenderScale,
+    Fov,
+    MoveSpeed,
+    ShowCompass,
+    ShowCoordinates,
+}
+
+impl Setting {
+    pub const ALL: [Setting; 10] = [
+        Setting::MasterVolume,
+        Setting::MusicVolume,
+        Setting::SfxVolume,
+        Setting::Fullscreen,
+        Setting::Vsync,
+        Setting::RenderScale,
+        Setting::Fov,
+        Setting::MoveSpeed,
+        Setting::ShowCompass,
+        Setting::ShowCoordinates,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Setting::MasterVolume => "Master volume",
+            Setting::MusicVolume => "Music volume",
+            Setting::SfxVolume => "Effects volume",
+            Setting::Fullscreen => "Fullscreen",
+            Setting::Vsync => "Vsync",
+            Setting::RenderScale => "Render scale",
+            Setting::Fov => "Field of view",
+            Setting::MoveSpeed => "Movement speed",
+            Setting::ShowCompass => "Compass",
+            Setting::ShowCoordinates => "Coordinates",
+        }
+    }
+}
+
+fn on_off(value: bool) -> String {
+    let text = if value { "On" } else { "Off" };
+    text.to_string()
+}
+
+impl Settings {
+    /// The value of `setting` as shown in the menu.
+    pub fn describe(&self, setting: Setting) -> String {
+        match setting {
+            Setting::MasterVolume => format!("{:.0}%", self.master_volume * 100.),
+            Setting::MusicVolume => format!("{:.0}%", self.music_volume * 100.),
+            Setting::SfxVolume => format!("{:.0}%", self.sfx_volume * 100.),
+            Setting::Fullscreen => on_off(self.fullscreen),
+            Setting::Vsync => on_off(self.vsync),
+            Setting::RenderScale => format!("{:.0}%", self.render_scale * 100.),
+            Setting::Fov => format!("{:.0}°", self.fov),
+            Setting::MoveSpeed if self.move_speed <= 0. => "Instant".to_string(),
+            Setting::MoveSpeed => format!("{:.0}", self.move_speed),
+            Setting::ShowCompass => on_off(self.compass.show_compass),
+            Setting::ShowCoordinates => on_off(self.compass.show_coordinates),
+        }
+    }
+
+    /// Moves `setting` by `steps` steps up or down, within its bounds.
+    pub fn adjust(&mut self, setting: Setting, steps: i32) {
+        let step = |value: &mut f32, size: f32, min: f32, max: f32| {
+            *value = (*value + size * steps as f32).clamp(min, max);
+            // Keeps the value on the steps despite the rounding errors piling up
+            *value = (*value / size).round() * size;
+        };
+        match setting {
+            Setting::MasterVolume => step(&mut self.master_volume, 0.1, 0., 1.),
+            Setting::MusicVolume => step(&mut self.music_volume, 0.1, 0., 1.),
+            Setting::SfxVolume => step(&mut self.sfx_volume, 0.1, 0., 1.),
+            Setting::Fullscreen => self.fullscreen = !self.fullscreen,
+            Setting::Vsync => self.vsync = !self.vsync,
+            Setting::RenderScale => step(&mut self.render_scale, 0.25, 0.25, 2.),
+            Setting::Fov => step(&mut self.fov, 5., 30., 110.),
+            Setting::MoveSpeed => step(&mut self.move_speed, 2., 0., 30.),
+            Setting::ShowCompass => self.compass.show_compass = !self.compass.show_compass,
+            Setting::ShowCoordinates => {
+                self.compass.show_coordinates = !self.compass.show_coordinates
+            }
+        }
+    }
+}
+
+/// What the player can do with a key of their choosing.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Forward,
+    Backward,
+    TurnLeft,
+    TurnRight,
+    /// Pull levers and pick up items
+    Interact,
+    /// Look for hidden features around the party
+    Search,
+}
+
+impl Action {
+    pub const ALL: [Action; 6] = [
+        Action::Forward,
+        Action::Backward,
+        Action::TurnLeft,
+        Action::TurnRight,
+        Action::Interact,
+        Action::Search,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Action::Forward => "Forward",
+            Action::Backward => "Backward",
+            Action::TurnLeft => "Turn left",
+            Action::TurnRight => "Turn right",
+            Action::Interact => "Interact",
+            Action::Search => "Search",
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default)]
+pub struct KeyBindings {
+    pub forward: KeyCode,
+    pub backward: KeyCode,
+    pub turn_left: KeyCode,
+    pub turn_right: KeyCode,
+    pub interact: KeyCode,
+    pub search: KeyCode,
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        KeyBindings {
+            forward: KeyCode::W,
+            backward: KeyCode::S,
+            turn_left: KeyCode::A,
+            turn_right: KeyCode::D,
+            interact: KeyCode::E,
+            search: KeyCode::R,
+        }
+    }
+}
+
+impl KeyBindings {
+    pub fn key(&self, action: Action) -> KeyCode {
+        match action {
+            Action::Forward => self.forward,
+            Action::Backward => self.backward,
+            Action::TurnLeft => self.turn_left,
+            Action::TurnRight => self.turn_right,
+            Action::Interact => self.interact,
+            Action::Search => self.search,
+        }
+    }
+
+    fn key_mut(&mut self, action: Action) -> &mut KeyCode {
+        match action {
+            Action::Forward => &mut self.forward,
+            Action::Backward => &mut self.backward,
+            Action::TurnLeft => &mut self.turn_left,
+            Action::TurnRight => &mut self.turn_right,
+            Action::Interact => &mut self.interact,
+            Action::Search => &mut self.search,
+        }
+    }
+
+    /// Binds `key` to `action`. An action that was using it gets the previous key of `action`, so
+    /// that no two actions share a key.
+    pub fn bind(&mut self, action: Action, key: KeyCode) {
+        let previous = self.key(action);
+        if let Some(other) = Action::ALL
+            .into_iter()
+            .find(|other| self.key(*other) == key)
+        {
+            *self.key_mut(other) = previous;
+        }
+        *self.key_mut(action) = key;
+    }
+}
+
+fn load_settings(mut commands: Commands) {
+    let Some(text) = storage::read(SETTINGS_KEY) else {
+        return;
+    };
+    match ron::from_str::<Settings>(&text) {
+        Ok(settings) => commands.insert_resource(settings),
+        Err(error) => warn!("Failed to read the settings, using the defaults: {error}"),
+    }
+}
+
+fn save_settings(settings: Res<Settings>) {
+    let result = ron::ser::to_string_pretty(&*settings, default())
+        .map_err(|error| error.to_string())
+        .and_then(|text| storage::write(SETTINGS_KEY, &text).map_err(|e| e.to_string()));
+    if let Err(error) = result {
+        warn!("Failed to save the settings: {error}");
+    }
+}
+
+fn apply_window(settings: Res<Settings>, mut windows: Query<&mut Window, With<PrimaryWindow>>) {
+    let Ok(mut window) = windows.get_single_mut() else {
+        return;
+    };
+    let mode = if settings.fullscreen {
+        WindowMode::BorderlessFullscreen
+    } else {
+        WindowMode::Windowed
+    };
+    let present_mode = if settings.vsync {
+        PresentMode::AutoVsync
+    } else {
+        PresentMode::AutoNoVsync
+    };
+    // Only touch the window when needed, since every change goes through to the OS
+    if window.mode != mode {
+        window.mode = mode;
+    }
+    if window.present_mode != present_mode {
+        window.present_mode = present_mode;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn binding_a_used_key_swaps_it() {
+        let mut keys = KeyBindings::default();
+        keys.bind(Action::Forward, KeyCode::S);
+        assert_eq!(keys.key(Action::Forward), KeyCode::S);
+        assert_eq!(keys.key(Action::Backward), KeyCode::W);
+
+        keys.bind(Action::Search, KeyCode::F);
+        assert_eq!(keys.key(Action::Search), KeyCode::F);
+        for action in Action::ALL {
+            let shared = Action::ALL
+                .into_iter()
+                .filter(|other| keys.key(*other) == keys.key(action))
+                .count();
+            assert_eq!(shared, 1, "{}", action.name());
+        }
+    }
+
+    #[test]
+    fn adjusting_stays_within_bounds() {
+        let mut settings = Settings::default();
+        settings.adjust(Setting::MasterVolume, 3);
+        assert_eq!(settings.master_volume, 1.);
+        settings.adjust(Setting::MasterVolume, -20);
+        assert_eq!(settings.master_volume, 0.);
+        settings.adjust(Setting::RenderScale, -10);
+        assert_eq!(settings.render_scale, 0.25);
+        settings.adjust(Setting::Fov, 100);
+        assert_eq!(settings.fov, 110.);
+        settings.adjust(Setting::MoveSpeed, -100);
+        assert_eq!(settings.describe(Setting::MoveSpeed), "Instant");
+    }
+
+    #[test]
+    fn adjusting_rounds_to_the_steps() {
+        let mut settings = Settings {
+            fov: 47.,
+            ..default()
+        };
+        settings.adjust(Setting::Fov, 1);
+        assert_eq!(settings.fov, 50.);
+
+        for _ in 0..7 {
+            settings.adjust(Setting::MusicVolume, -1);
+            settings.adjust(Setting::MusicVolume, 1);
+        }
+        assert_eq!(settings.describe(Setting::MusicVolume), "80%");
+        settings.adjust(Setting::MusicVolume, 1);
+        assert_eq!(settings.describe(Setting::MusicVolume), "90%");
+    }
+}
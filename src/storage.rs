@@ -0,0 +1,111 @@
+// Small key/value persistence layer: one file per key in the data directory of the platform on
+// desktop, `localStorage` on the web.
+// Keys are file names, extension included.
+
+use std::fmt;
+
+#[derive(Debug)]
+pub enum StorageError {
+    #[cfg(not(target_arch = "wasm32"))]
+    Io(std::io::Error),
+    /// The browser does not give access to `localStorage` (private mode, quota exceeded...)
+    #[cfg(target_arch = "wasm32")]
+    Unavailable,
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(not(target_arch = "wasm32"))]
+            StorageError::Io(error) => write!(f, "{error}"),
+            #[cfg(target_arch = "wasm32")]
+            StorageError::Unavailable => write!(f, "local storage is not available"),
+        }
+    }
+}
+
+impl std::error::Error for StorageError {}
+
+/// Seconds elapsed since the unix epoch, according to the system clock.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> u64 {
+    (js_sys::Date::now() / 1000.) as u64
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+mod backend {
+    use std::path::PathBuf;
+
+    use directories::ProjectDirs;
+
+    use super::StorageError;
+
+    /// The data directory of the platform, like `~/.local/share/insectivore` on Linux. Without a
+    /// home directory, the files go to `saves` in the working directory.
+    fn directory() -> PathBuf {
+        ProjectDirs::from("", "", "Insectivore").map_or_else(
+            || PathBuf::from("saves"),
+            |dirs| dirs.data_dir().to_path_buf(),
+        )
+    }
+
+    fn path(key: &str) -> PathBuf {
+        directory().join(key)
+    }
+
+    pub fn read(key: &str) -> Option<String> {
+        std::fs::read_to_string(path(key)).ok()
+    }
+
+    pub fn write(key: &str, value: &str) -> Result<(), StorageError> {
+        std::fs::create_dir_all(directory()).map_err(StorageError::Io)?;
+        std::fs::write(path(key), value).map_err(StorageError::Io)
+    }
+
+    pub fn remove(key: &str) -> Result<(), StorageError> {
+        match std::fs::remove_file(path(key)) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(StorageError::Io(error))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+mod backend {
+    use super::StorageError;
+
+    const PREFIX: &str = "insectivore.";
+
+    fn local_storage() -> Option<web_sys::Storage> {
+        web_sys::window()?.local_storage().ok()?
+    }
+
+    pub fn read(key: &str) -> Option<String> {
+        local_storage()?.get_item(&format!("{PREFIX}{key}")).ok()?
+    }
+
+    pub fn write(key: &str, value: &str) -> Result<(), StorageError> {
+        local_storage()
+            .ok_or(StorageError::Unavailable)?
+            .set_item(&format!("{PREFIX}{key}"), value)
+            .map_err(|_| StorageError::Unavailable)
+    }
+
+    pub fn remove(key: &str) -> Result<(), StorageError> {
+        local_storage()
+            .ok_or(StorageError::Unavailable)?
+            .remove_item(&format!("{PREFIX}{key}"))
+            .map_err(|_| StorageError::Unavailable)
+    }
+}
+
+pub use backend::{read, remove, write};
@@ -1,6 +1,6 @@
 use bevy::{core_pipeline::clear_color::ClearColorConfig, prelude::*, render::view::RenderLayers};
 
-use crate::GameState;
+use crate::{GameState, PlayingEntity};
 
 pub struct Camera2DPlugin;
 
@@ -33,5 +33,6 @@ pub fn setup(mut commands: Commands) {
         Name::new("hud_camera"),
         UI_LAYER,
         UiCamera,
+        PlayingEntity,
     ));
 }
@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+
+use super::camera2d::UI_LAYER;
+use crate::{combat::CombatLog, idle::AutoPlay, GameState};
+
+pub struct CombatLogPlugin;
+
+impl Plugin for CombatLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_combat_log.run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// Number of lines visible at once, older ones scroll out of the panel.
+const VISIBLE_LINES: usize = 8;
+
+#[derive(Component)]
+pub struct CombatLogText;
+
+#[derive(Component)]
+pub struct AutoPlayText;
+
+/// Spawns the combat log panel at the bottom of the dungeon view.
+pub fn spawn_combat_log(hud_image_node: &mut ChildBuilder, region: Style) {
+    let text_style = TextStyle {
+        font_size: 16.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+        ..default()
+    };
+
+    hud_image_node
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::FlexEnd,
+                    overflow: Overflow::clip(),
+                    padding: UiRect::all(Val::Px(4.)),
+                    ..region
+                },
+                background_color: Color::rgba(0., 0., 0., 0.6).into(),
+                ..default()
+            },
+            UI_LAYER,
+            Name::new("combat_log_node"),
+        ))
+        .with_children(|log_node| {
+            log_node.spawn((
+                TextBundle::from_section("", text_style.clone()),
+                UI_LAYER,
+                AutoPlayText,
+            ));
+            log_node.spawn((
+                TextBundle::from_section("", text_style),
+                UI_LAYER,
+                CombatLogText,
+            ));
+        });
+}
+
+fn update_combat_log(
+    log: Res<CombatLog>,
+    auto_play: Res<AutoPlay>,
+    mut log_text: Query<&mut Text, (With<CombatLogText>, Without<AutoPlayText>)>,
+    mut auto_play_text: Query<&mut Text, (With<AutoPlayText>, Without<CombatLogText>)>,
+) {
+    if log.is_changed() {
+        for mut text in log_text.iter_mut() {
+            let lines: Vec<&str> = log.last(VISIBLE_LINES).map(String::as_str).collect();
+            text.sections[0].value = lines.join("\n");
+        }
+    }
+    if auto_play.is_changed() {
+        for mut text in auto_play_text.iter_mut() {
+            text.sections[0].value = format!(
+                "Auto-play: {} ({}, {:?})",
+                if auto_play.enabled { "on" } else { "off" },
+                auto_play.speed.label(),
+                auto_play.tactic,
+            );
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use super::camera2d::UI_LAYER;
+use crate::{
+    dungeon::{
+        camera3d::{CameraDirection, GridPosition, Player},
+        labyrinth::CurrentFloor,
+    },
+    settings::Settings,
+    GameState,
+};
+
+pub struct CompassPlugin;
+
+/// This plugin shows where the player is heading on a compass dial that turns along with them,
+/// and optionally their coordinates on the floor.
+impl Plugin for CompassPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (turn_compass, update_coordinates, show_compass).run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// What the compass region shows, part of the [`Settings`].
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default)]
+pub struct CompassSettings {
+    pub show_compass: bool,
+    /// `(x, z)` and floor, under the dial
+    pub show_coordinates: bool,
+}
+
+impl Default for CompassSettings {
+    fn default() -> Self {
+        CompassSettings {
+            show_compass: true,
+            show_coordinates: false,
+        }
+    }
+}
+
+/// How fast the dial catches up with the heading, higher is snappier.
+const TURN_SPEED: f32 = 12.;
+
+#[derive(Component)]
+struct Compass;
+
+/// The turning part of the compass, `angle` being the heading it shows.
+#[derive(Component, Default)]
+struct CompassDial {
+    angle: f32,
+}
+
+/// Letters are turned back so that they stay upright on the dial.
+#[derive(Component)]
+struct CompassLetter;
+
+#[derive(Component)]
+struct CoordinatesText;
+
+/// Spawns the compass in its region.
+pub fn spawn_compass(hud_image_node: &mut ChildBuilder, region: Style) {
+    let text_style = TextStyle {
+        font_size: 18.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+        ..default()
+    };
+
+    hud_image_node
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    row_gap: Val::Px(4.),
+                    ..region
+                },
+                ..default()
+            },
+            UI_LAYER,
+            Compass,
+            Name::new("compass_node"),
+        ))
+        .with_children(|compass| {
+            compass
+                .spawn((
+                    NodeBundle {
+                        style: Style {
+                            width: Val::Percent(100.),
+                            aspect_ratio: Some(1.),
+                            ..default()
+                        },
+                        background_color: Color::rgba(0., 0., 0., 0.6).into(),
+                        ..default()
+                    },
+                    UI_LAYER,
+                    CompassDial::default(),
+                ))
+                .with_children(|dial| {
+                    // Letters around the dial, in percent of its size
+                    for (letter, left, top) in [
+                        ("N", 35., 0.),
+                        ("E", 70., 35.),
+                        ("S", 35., 70.),
+                        ("W", 0., 35.),
+                    ] {
+                        dial.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    position_type: PositionType::Absolute,
+                                    left: Val::Percent(left),
+                                    top: Val::Percent(top),
+                                    width: Val::Percent(30.),
+                                    height: Val::Percent(30.),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            UI_LAYER,
+                            CompassLetter,
+                        ))
+                        .with_children(|node| {
+                            let color = if letter == "N" {
+                                Color::rgb(0.9, 0.3, 0.25)
+                            } else {
+                                text_style.color
+                            };
+                            node.spawn((
+                                TextBundle::from_section(
+                                    letter,
+                                    TextStyle {
+                                        color,
+                                        ..text_style.clone()
+                                    },
+                                ),
+                                UI_LAYER,
+                            ));
+                        });
+                    }
+                });
+            compass.spawn((
+                TextBundle::from_section("", text_style.clone()),
+                UI_LAYER,
+                CoordinatesText,
+            ));
+        });
+}
+
+/// Heading of `direction` in radians, clockwise from North.
+pub fn heading(direction: CameraDirection) -> f32 {
+    match direction {
+        CameraDirection::North => 0.,
+        CameraDirection::East => FRAC_PI_2,
+        CameraDirection::South => PI,
+        CameraDirection::West => 3. * FRAC_PI_2,
+    }
+}
+
+/// The angle to add to `from` to reach `to` the short way round, in `-PI..PI`.
+pub fn shortest_turn(from: f32, to: f32) -> f32 {
+    (to - from + PI).rem_euclid(TAU) - PI
+}
+
+fn turn_compass(
+    time: Res<Time>,
+    player: Query<&CameraDirection, With<Player>>,
+    mut dials: Query<(&mut CompassDial, &mut Transform)>,
+    mut letters: Query<&mut Transform, (With<CompassLetter>, Without<CompassDial>)>,
+) {
+    let Ok(direction) = player.get_single() else {
+        return;
+    };
+    let target = heading(*direction);
+    let step = 1. - (-TURN_SPEED * time.delta_seconds()).exp();
+    for (mut dial, mut transform) in dials.iter_mut() {
+        dial.angle = (dial.angle + shortest_turn(dial.angle, target) * step).rem_euclid(TAU);
+        // UI space is y-down, a positive angle turns clockwise on screen: the heading is brought
+        // up by turning the dial the other way
+        transform.rotation = Quat::from_rotation_z(-dial.angle);
+        for mut letter in letters.iter_mut() {
+            letter.rotation = Quat::from_rotation_z(dial.angle);
+        }
+    }
+}
+
+fn update_coordinates(
+    floor: Res<CurrentFloor>,
+    player: Query<Ref<GridPosition>, With<Player>>,
+    mut texts: Query<(Ref<CoordinatesText>, &mut Text)>,
+) {
+    let Ok(position) = player.get_single() else {
+        return;
+    };
+    for (added, mut text) in texts.iter_mut() {
+        if position.is_changed() || floor.is_changed() || added.is_added() {
+            text.sections[0].value = format!("({}, {}) floor {}", position.0, position.1, floor.0);
+        }
+    }
+}
+
+fn show_compass(
+    settings: Res<Settings>,
+    added: Query<(), Added<Compass>>,
+    mut compasses: Query<&mut Visibility, With<Compass>>,
+    mut coordinates: Query<&mut Style, With<CoordinatesText>>,
+) {
+    if !settings.is_changed() && added.is_empty() {
+        return;
+    }
+    let settings = settings.compass;
+    for mut visibility in compasses.iter_mut() {
+        *visibility = if settings.show_compass {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+    }
+    for mut style in coordinates.iter_mut() {
+        style.display = if settings.show_coordinates {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+}
@@ -0,0 +1,230 @@
+use bevy::prelude::*;
+
+use super::camera2d::UI_LAYER;
+use crate::{
+    combat::CombatLog,
+    inventory::{item_at, move_item, use_item, Inventory, ItemLocation, INVENTORY_SIZE},
+    loading::GameData,
+    party::Party,
+    GameState, PlayingState,
+};
+
+pub struct InventoryPanelPlugin;
+
+/// Items are dragged with the left mouse button between the inventory and the equipment slots
+/// of the party panel. Clicking a consumable uses it.
+///
+/// Without a mouse, pressing the focused slot picks its item up and pressing another one drops it
+/// there, pressing the same slot again uses it.
+impl Plugin for InventoryPanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DraggedItem>().add_systems(
+            Update,
+            (
+                (drag_items, press_items).run_if(in_state(PlayingState::Exploring)),
+                update_item_slots,
+            )
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// A button showing the item at a location, items can be dragged from and dropped on it.
+#[derive(Component)]
+pub struct ItemSlot(pub ItemLocation);
+
+/// Where the item being dragged comes from.
+#[derive(Resource, Default)]
+struct DraggedItem(Option<ItemLocation>);
+
+const EMPTY_COLOR: Color = Color::rgba(0.1, 0.1, 0.1, 0.8);
+const FILLED_COLOR: Color = Color::rgba(0.3, 0.25, 0.15, 0.9);
+const DRAGGED_COLOR: Color = Color::rgba(0.6, 0.5, 0.2, 0.9);
+
+/// Spawns the inventory grid in the item bar at the bottom of the HUD.
+pub fn spawn_inventory_panel(hud_image_node: &mut ChildBuilder, region: Style) {
+    hud_image_node
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_wrap: FlexWrap::Wrap,
+                    align_content: AlignContent::Center,
+                    row_gap: Val::Px(6.),
+                    column_gap: Val::Px(6.),
+                    ..region
+                },
+                ..default()
+            },
+            UI_LAYER,
+            Name::new("inventory_node"),
+        ))
+        .with_children(|inventory_node| {
+            for index in 0..INVENTORY_SIZE {
+                spawn_item_slot(
+                    inventory_node,
+                    ItemLocation::Inventory(index),
+                    Val::Percent(100. / (INVENTORY_SIZE / 2) as f32 - 1.),
+                );
+            }
+        });
+}
+
+pub fn spawn_item_slot(parent: &mut ChildBuilder, location: ItemLocation, width: Val) {
+    parent
+        .spawn((
+            ButtonBundle {
+                style: Style {
+                    width,
+                    aspect_ratio: Some(1.),
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    overflow: Overflow::clip(),
+                    // Shows the focus
+                    border: UiRect::all(Val::Px(2.)),
+                    ..default()
+                },
+                background_color: EMPTY_COLOR.into(),
+                ..default()
+            },
+            UI_LAYER,
+            ItemSlot(location),
+        ))
+        .with_children(|slot| {
+            slot.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 12.0,
+                        color: Color::rgb(0.9, 0.9, 0.9),
+                        ..default()
+                    },
+                ),
+                UI_LAYER,
+            ));
+        });
+}
+
+fn drag_items(
+    mouse_input: Res<Input<MouseButton>>,
+    mut dragged: ResMut<DraggedItem>,
+    mut inventory: ResMut<Inventory>,
+    mut party: ResMut<Party>,
+    mut log: ResMut<CombatLog>,
+    data: GameData,
+    slots: Query<(&Interaction, &ItemSlot)>,
+) {
+    let slot_with = |state: Interaction| {
+        slots
+            .iter()
+            .find(|(interaction, _)| **interaction == state)
+            .map(|(_, ItemSlot(location))| *location)
+    };
+
+    if mouse_input.just_pressed(MouseButton::Left) {
+        dragged.0 = slot_with(Interaction::Pressed)
+            .filter(|location| item_at(&inventory, &party, *location).is_some());
+    }
+    if !mouse_input.just_released(MouseButton::Left) {
+        return;
+    }
+    let Some(from) = dragged.0.take() else {
+        return;
+    };
+    // Dropping the item outside of a slot leaves it where it was
+    let Some(to) = slot_with(Interaction::Hovered) else {
+        return;
+    };
+
+    drop_item(&mut inventory, &mut party, &mut log, &data, from, to);
+}
+
+/// Moves the dragged item from `from` to `to`, or uses it when dropped back on its own slot.
+fn drop_item(
+    inventory: &mut Inventory,
+    party: &mut Party,
+    log: &mut CombatLog,
+    data: &GameData,
+    from: ItemLocation,
+    to: ItemLocation,
+) {
+    let (Some(classes), Some(items)) = (data.classes(), data.items()) else {
+        return;
+    };
+    if from == to {
+        if let ItemLocation::Inventory(index) = from {
+            if let Some(message) = use_item(inventory, party, items, index) {
+                log.push(message);
+            }
+        }
+    } else if let Err(reason) = move_item(inventory, party, classes, items, from, to) {
+        log.push(format!("Cannot move the item: {reason}"));
+    }
+}
+
+/// Picks up and drops items with the slots pressed from the keyboard or a gamepad, mouse presses
+/// being left to [`drag_items`].
+fn press_items(
+    mouse_input: Res<Input<MouseButton>>,
+    mut dragged: ResMut<DraggedItem>,
+    mut inventory: ResMut<Inventory>,
+    mut party: ResMut<Party>,
+    mut log: ResMut<CombatLog>,
+    data: GameData,
+    slots: Query<(&Interaction, &ItemSlot), Changed<Interaction>>,
+) {
+    if mouse_input.pressed(MouseButton::Left) || mouse_input.just_released(MouseButton::Left) {
+        return;
+    }
+    for (interaction, ItemSlot(location)) in slots.iter() {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match dragged.0.take() {
+            Some(from) => drop_item(&mut inventory, &mut party, &mut log, &data, from, *location),
+            None => {
+                dragged.0 = Some(*location)
+                    .filter(|location| item_at(&inventory, &party, *location).is_some());
+            }
+        }
+    }
+}
+
+fn update_item_slots(
+    inventory: Res<Inventory>,
+    party: Res<Party>,
+    dragged: Res<DraggedItem>,
+    data: GameData,
+    mut slots: Query<(&ItemSlot, &Children, &mut BackgroundColor)>,
+    mut texts: Query<&mut Text>,
+) {
+    if !inventory.is_changed() && !party.is_changed() && !dragged.is_changed() {
+        return;
+    }
+    let Some(items) = data.items() else {
+        return;
+    };
+
+    for (ItemSlot(location), children, mut background) in slots.iter_mut() {
+        let item = item_at(&inventory, &party, *location);
+        *background = if dragged.0 == Some(*location) {
+            DRAGGED_COLOR
+        } else if item.is_some() {
+            FILLED_COLOR
+        } else {
+            EMPTY_COLOR
+        }
+        .into();
+
+        let label = match (item, location) {
+            (Some(item), _) => items.name(item),
+            (None, ItemLocation::Equipment { slot, .. }) => slot.label().to_string(),
+            (None, ItemLocation::Inventory(_)) => String::new(),
+        };
+        for &child in children.iter() {
+            if let Ok(mut text) = texts.get_mut(child) {
+                text.sections[0].value = label.clone();
+            }
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use bevy::{prelude::*, render::render_resource::Extent3d};
+use serde::Deserialize;
+
+use super::{DungeonViewImage, HudImage, MinimapImage};
+use crate::{
+    loading::{TextureAssets, UIConfig},
+    settings::Settings,
+    GameState,
+};
+
+pub struct LayoutPlugin;
+
+/// This plugin keeps the HUD image as large as the window allows without stretching it, bars
+/// filling the rest. Regions of the HUD follow since they are placed in percent of the image.
+///
+/// The dungeon view and minimap images are then rendered at the size they are shown at, scaled by
+/// the render scale setting.
+impl Plugin for LayoutPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (letterbox_hud, resize_render_targets)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// A rectangle of the HUD image, in pixels of the image.
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+pub struct Region {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+/// A region as percentages of the HUD image, as UI nodes place their children.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct PercentRect {
+    pub left: f32,
+    pub top: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl Region {
+    /// The region within an image of `size` pixels.
+    pub fn percent(&self, size: (f32, f32)) -> PercentRect {
+        PercentRect {
+            left: self.x * 100. / size.0,
+            top: self.y * 100. / size.1,
+            width: self.width * 100. / size.0,
+            height: self.height * 100. / size.1,
+        }
+    }
+
+    /// Places a node over the region, within the HUD image node. Panels add their own layout on
+    /// top of it.
+    pub fn style(&self, size: (f32, f32)) -> Style {
+        let rect = self.percent(size);
+        Style {
+            position_type: PositionType::Absolute,
+            // Unlike margins, insets in percent are relative to the height when vertical
+            left: Val::Percent(rect.left),
+            top: Val::Percent(rect.top),
+            width: Val::Percent(rect.width),
+            height: Val::Percent(rect.height),
+            ..default()
+        }
+    }
+}
+
+/// The largest size with the aspect ratio of `image` that fits in `available`.
+pub fn letterbox(available: Vec2, image: Vec2) -> Vec2 {
+    let scale = (available.x / image.x).min(available.y / image.y);
+    image * scale.max(0.)
+}
+
+fn letterbox_hud(
+    textures: Res<TextureAssets>,
+    configs: Res<Assets<UIConfig>>,
+    windows: Query<&Window>,
+    mut hud: Query<&mut Style, With<HudImage>>,
+) {
+    let (Ok(window), Some(config)) = (windows.get_single(), configs.get(&textures.hud_config))
+    else {
+        return;
+    };
+    let size = letterbox(
+        Vec2::new(window.width(), window.height()),
+        Vec2::new(config.size.0, config.size.1),
+    );
+    let (width, height) = (Val::Px(size.x), Val::Px(size.y));
+    for mut style in hud.iter_mut() {
+        // Only touch the style on resize, to leave the layout alone the rest of the time
+        if style.width != width || style.height != height {
+            style.width = width;
+            style.height = height;
+        }
+    }
+}
+
+/// Size in physical pixels of a render target shown over `node_size` logical pixels.
+pub fn render_target_size(node_size: Vec2, scale_factor: f32, quality: f32) -> Extent3d {
+    let size = (node_size * scale_factor * quality).round().max(Vec2::ONE);
+    Extent3d {
+        width: size.x as u32,
+        height: size.y as u32,
+        ..default()
+    }
+}
+
+/// Resizes the images the dungeon and minimap cameras render to, to the size of the nodes showing
+/// them. The cameras keep the same image handles and pick up the new size by themselves.
+fn resize_render_targets(
+    settings: Res<Settings>,
+    windows: Query<&Window>,
+    mut images: ResMut<Assets<Image>>,
+    views: Query<(&Node, &UiImage), Or<(With<DungeonViewImage>, With<MinimapImage>)>>,
+) {
+    let Ok(window) = windows.get_single() else {
+        return;
+    };
+    for (node, image) in views.iter() {
+        // Nodes are only laid out after their first frame
+        if node.size() == Vec2::ZERO {
+            continue;
+        }
+        let size = render_target_size(
+            node.size(),
+            window.scale_factor() as f32,
+            settings.render_scale,
+        );
+        // Looking first, since borrowing the image mutably marks it as modified
+        let current = images
+            .get(&image.texture)
+            .map(|image| image.texture_descriptor.size);
+        if current.map_or(true, |current| current == size) {
+            continue;
+        }
+        if let Some(image) = images.get_mut(&image.texture) {
+            debug!("render target resized to {}x{}", size.width, size.height);
+            image.resize(size);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_are_converted_to_percent_of_the_image() {
+        let region = Region {
+            x: 160.,
+            y: 90.,
+            width: 320.,
+            height: 45.,
+        };
+        assert_eq!(
+            region.percent((640., 360.)),
+            PercentRect {
+                left: 25.,
+                top: 25.,
+                width: 50.,
+                height: 12.5,
+            }
+        );
+    }
+
+    #[test]
+    fn the_whole_image_is_a_hundred_percent() {
+        let size = (1920., 1080.);
+        let region = Region {
+            x: 0.,
+            y: 0.,
+            width: size.0,
+            height: size.1,
+        };
+        let rect = region.percent(size);
+        assert_eq!((rect.left, rect.top), (0., 0.));
+        assert_eq!((rect.width, rect.height), (100., 100.));
+    }
+
+    #[test]
+    fn letterboxing_keeps_the_aspect_ratio() {
+        let image = Vec2::new(1920., 1080.);
+        // Bars above and below a tall window, on the sides of a wide one
+        assert_eq!(
+            letterbox(Vec2::new(480., 2000.), image),
+            Vec2::new(480., 270.)
+        );
+        assert_eq!(
+            letterbox(Vec2::new(4000., 540.), image),
+            Vec2::new(960., 540.)
+        );
+        // No bars when the ratio matches, and the image grows to fill larger windows
+        assert_eq!(
+            letterbox(Vec2::new(3840., 2160.), image),
+            Vec2::new(3840., 2160.)
+        );
+    }
+
+    #[test]
+    fn a_collapsed_window_gives_an_empty_image() {
+        let image = Vec2::new(1920., 1080.);
+        assert_eq!(letterbox(Vec2::ZERO, image), Vec2::ZERO);
+        assert_eq!(letterbox(Vec2::new(-5., 100.), image), Vec2::ZERO);
+    }
+}
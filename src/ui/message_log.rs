@@ -0,0 +1,178 @@
+use bevy::prelude::*;
+
+use super::camera2d::UI_LAYER;
+use crate::{
+    message::{GameMessage, MessageLog},
+    GameState,
+};
+
+pub struct MessageLogPlugin;
+
+/// This plugin shows the message log in its HUD region, scrolled with Page Up and Page Down, and
+/// pops up the important messages over the dungeon view.
+impl Plugin for MessageLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (scroll_log, update_message_log, spawn_toasts, fade_toasts)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+const VISIBLE_LINES: usize = 12;
+const TOAST_SECONDS: f32 = 3.;
+const FONT_SIZE: f32 = 15.;
+
+#[derive(Component)]
+pub struct MessageLogText;
+
+/// Holds the toasts, over the dungeon view.
+#[derive(Component)]
+pub struct ToastArea;
+
+#[derive(Component)]
+struct Toast(Timer);
+
+/// Spawns the message log panel in its region.
+pub fn spawn_message_log(hud_image_node: &mut ChildBuilder, region: Style) {
+    hud_image_node
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::FlexEnd,
+                    overflow: Overflow::clip(),
+                    padding: UiRect::all(Val::Px(4.)),
+                    ..region
+                },
+                background_color: Color::rgba(0., 0., 0., 0.6).into(),
+                ..default()
+            },
+            UI_LAYER,
+            Name::new("message_log_node"),
+        ))
+        .with_children(|log_node| {
+            log_node.spawn((TextBundle::from_sections([]), UI_LAYER, MessageLogText));
+        });
+}
+
+/// Spawns the column toasts stack in, at the top of the dungeon view.
+pub fn spawn_toast_area(hud_image_node: &mut ChildBuilder, region: Style) {
+    hud_image_node.spawn((
+        NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                row_gap: Val::Px(4.),
+                padding: UiRect::top(Val::Px(12.)),
+                ..region
+            },
+            ..default()
+        },
+        UI_LAYER,
+        ToastArea,
+        Name::new("toast_area_node"),
+    ));
+}
+
+fn scroll_log(keyboard_input: Res<Input<KeyCode>>, mut log: ResMut<MessageLog>) {
+    if keyboard_input.just_pressed(KeyCode::PageUp) {
+        log.scroll(VISIBLE_LINES as i32 / 2);
+    } else if keyboard_input.just_pressed(KeyCode::PageDown) {
+        log.scroll(-(VISIBLE_LINES as i32 / 2));
+    } else if keyboard_input.just_pressed(KeyCode::End) {
+        log.catch_up();
+    }
+}
+
+fn update_message_log(
+    log: Res<MessageLog>,
+    added: Query<(), Added<MessageLogText>>,
+    mut texts: Query<&mut Text, With<MessageLogText>>,
+) {
+    // Freshly spawned after a HUD rebuild, the text needs filling even if the log is the same
+    if !log.is_changed() && added.is_empty() {
+        return;
+    }
+    let mut sections: Vec<TextSection> = log
+        .visible(VISIBLE_LINES)
+        .map(|message| {
+            TextSection::new(
+                format!("{}\n", message.timestamped()),
+                TextStyle {
+                    font_size: FONT_SIZE,
+                    color: message.severity.color(),
+                    ..default()
+                },
+            )
+        })
+        .collect();
+    if log.scrolled_back() {
+        sections.push(TextSection::new(
+            "(scrolled back, End to catch up)",
+            TextStyle {
+                font_size: FONT_SIZE,
+                color: Color::GRAY,
+                ..default()
+            },
+        ));
+    }
+    for mut text in texts.iter_mut() {
+        text.sections = sections.clone();
+    }
+}
+
+fn spawn_toasts(
+    mut commands: Commands,
+    mut messages: EventReader<GameMessage>,
+    areas: Query<Entity, With<ToastArea>>,
+) {
+    let Ok(area) = areas.get_single() else {
+        messages.clear();
+        return;
+    };
+    for message in messages.read().filter(|message| message.toast) {
+        let toast = commands
+            .spawn((
+                TextBundle::from_section(
+                    message.text.clone(),
+                    TextStyle {
+                        font_size: 22.,
+                        color: message.severity.color(),
+                        ..default()
+                    },
+                )
+                .with_style(Style {
+                    padding: UiRect::axes(Val::Px(10.), Val::Px(4.)),
+                    ..default()
+                })
+                .with_background_color(Color::rgba(0., 0., 0., 0.7)),
+                UI_LAYER,
+                Toast(Timer::from_seconds(TOAST_SECONDS, TimerMode::Once)),
+                Name::new("toast"),
+            ))
+            .id();
+        commands.entity(area).add_child(toast);
+    }
+}
+
+/// Fades the toasts out over their last second, then removes them.
+fn fade_toasts(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut toasts: Query<(Entity, &mut Toast, &mut Text, &mut BackgroundColor)>,
+) {
+    for (entity, mut toast, mut text, mut background) in toasts.iter_mut() {
+        toast.0.tick(time.delta());
+        if toast.0.finished() {
+            commands.entity(entity).despawn_recursive();
+            continue;
+        }
+        let alpha = toast.0.remaining_secs().min(1.);
+        for section in text.sections.iter_mut() {
+            section.style.color.set_a(alpha);
+        }
+        background.0.set_a(alpha * 0.7);
+    }
+}
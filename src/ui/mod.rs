@@ -1,28 +1,61 @@
 mod camera2d;
+mod combat_log;
+pub mod compass;
+mod inventory_panel;
+pub mod layout;
+mod message_log;
+mod party_panel;
 
 use bevy::{
+    asset::LoadState,
     prelude::*,
     render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages},
 };
 
 use crate::{
     loading::{TextureAssets, UIConfig},
-    GameState,
+    GameState, PlayingEntity,
 };
 use camera2d::Camera2DPlugin;
 
-use self::camera2d::UI_LAYER;
+use self::{
+    camera2d::UI_LAYER,
+    combat_log::{spawn_combat_log, CombatLogPlugin},
+    compass::{spawn_compass, CompassPlugin},
+    inventory_panel::{spawn_inventory_panel, InventoryPanelPlugin},
+    layout::LayoutPlugin,
+    message_log::{spawn_message_log, spawn_toast_area, MessageLogPlugin},
+    party_panel::{spawn_party_panel, PartyPanelPlugin},
+};
 
 pub struct UIPlugin;
 
+/// This plugin builds the HUD from `HUD_config.ui.ron`, and builds it again whenever the config or
+/// the HUD image change on disk (with the `file_watcher` feature).
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
-        app.add_plugins(Camera2DPlugin)
-            .add_systems(OnEnter(GameState::Playing), setup);
+        app.add_plugins((
+            Camera2DPlugin,
+            CombatLogPlugin,
+            PartyPanelPlugin,
+            InventoryPanelPlugin,
+            LayoutPlugin,
+            MessageLogPlugin,
+            CompassPlugin,
+        ))
+        .add_systems(OnEnter(GameState::Playing), setup)
+        .add_systems(OnExit(GameState::Playing), remove_render_views)
+        .add_systems(
+            Update,
+            reload_hud
+                .run_if(resource_exists::<HUDRenderViews>())
+                .run_if(in_state(GameState::Playing)),
+        );
     }
 }
 
-const ASPECT_RATIO_4_3: f32 = 1.3574498567;
+#[derive(Component)]
+pub struct HudRoot;
 
 #[derive(Component)]
 pub struct HudImage;
@@ -33,81 +66,188 @@ pub struct MinimapImage;
 #[derive(Component)]
 pub struct DungeonViewImage;
 
+/// Shown over the HUD when the config can't be used.
+#[derive(Component)]
+struct HudError;
+
 pub fn setup(
     mut commands: Commands,
     textures: Res<TextureAssets>,
-    config: Res<Assets<UIConfig>>,
+    configs: Res<Assets<UIConfig>>,
     mut images: ResMut<Assets<Image>>,
 ) {
-    let config = config.get(textures.hud_config.id()).unwrap();
-    // Create an empty image to hold the dungeon view
-    let mut dungeon_render_target = Image::new_fill(
-        Extent3d {
-            width: config.dungeon_view_size.0 as u32,
-            height: config.dungeon_view_size.1 as u32,
-            ..default()
-        },
-        TextureDimension::D2,
-        &[255; 4],
-        TextureFormat::Rgba8UnormSrgb,
-    );
-
-    // Create an empty image to hold the minimap
-    let mut minimap_render_target = Image::new_fill(
-        Extent3d {
-            width: config.minimap_size.0 as u32,
-            height: config.minimap_size.1 as u32,
-            ..default()
-        },
-        TextureDimension::D2,
-        &[255; 4],
-        TextureFormat::Rgba8UnormSrgb,
-    );
-
-    dungeon_render_target.texture_descriptor.usage =
-        TextureUsages::RENDER_ATTACHMENT | dungeon_render_target.texture_descriptor.usage;
-    let dungeon_handle = images.add(dungeon_render_target);
-
-    minimap_render_target.texture_descriptor.usage =
-        TextureUsages::RENDER_ATTACHMENT | minimap_render_target.texture_descriptor.usage;
-    let minimap_handle = images.add(minimap_render_target);
-
-    let ui_config = HUDRenderViews {
-        dungeon_handle: dungeon_handle.clone(),
-        minimap_handle: minimap_handle.clone(),
+    // Empty images to hold the dungeon view and the minimap, sized along with the HUD
+    let mut render_target = || {
+        let mut image = Image::new_fill(
+            Extent3d {
+                width: 1,
+                height: 1,
+                ..default()
+            },
+            TextureDimension::D2,
+            &[255; 4],
+            TextureFormat::Rgba8UnormSrgb,
+        );
+        image.texture_descriptor.usage =
+            TextureUsages::RENDER_ATTACHMENT | image.texture_descriptor.usage;
+        images.add(image)
+    };
+    let views = HUDRenderViews {
+        dungeon_handle: render_target(),
+        minimap_handle: render_target(),
     };
-    commands.insert_resource(ui_config);
 
     info!("ui node setup");
+    match usable_config(&configs, &textures) {
+        Ok(config) => spawn_hud(&mut commands, &textures, &views, &mut images, config),
+        Err(error) => spawn_error(&mut commands, error),
+    }
+    commands.insert_resource(views);
+}
+
+/// Removed along with the HUD, so that the cameras wait for the new render targets on return.
+fn remove_render_views(mut commands: Commands) {
+    commands.remove_resource::<HUDRenderViews>();
+}
+
+fn usable_config<'a>(
+    configs: &'a Assets<UIConfig>,
+    textures: &TextureAssets,
+) -> Result<&'a UIConfig, String> {
+    let config = configs
+        .get(textures.hud_config.id())
+        .ok_or("HUD_config.ui.ron is not loaded")?;
+    config.validate()?;
+    Ok(config)
+}
+
+/// Rebuilds the HUD when its config or image is modified. An invalid config leaves the current
+/// HUD in place and shows what is wrong with it.
+#[allow(clippy::too_many_arguments)]
+fn reload_hud(
+    mut commands: Commands,
+    mut config_events: EventReader<AssetEvent<UIConfig>>,
+    mut image_events: EventReader<AssetEvent<Image>>,
+    mut failed: Local<bool>,
+    asset_server: Res<AssetServer>,
+    textures: Res<TextureAssets>,
+    configs: Res<Assets<UIConfig>>,
+    views: Res<HUDRenderViews>,
+    mut images: ResMut<Assets<Image>>,
+    roots: Query<Entity, With<HudRoot>>,
+    errors: Query<Entity, With<HudError>>,
+) {
+    let config_id = textures.hud_config.id();
+    let hud_id = textures.hud.id();
+    let modified = config_events
+        .read()
+        .filter(|event| event.is_modified(config_id))
+        .count()
+        + image_events
+            .read()
+            .filter(|event| event.is_modified(hud_id))
+            .count()
+        > 0;
+    // A config that no longer parses keeps the previous asset, only its load state tells
+    let now_failed = asset_server.load_state(config_id) == LoadState::Failed;
+    let newly_failed = now_failed && !*failed;
+    *failed = now_failed;
+    if !modified && !newly_failed {
+        return;
+    }
+
+    for entity in errors.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    if newly_failed {
+        spawn_error(
+            &mut commands,
+            "HUD_config.ui.ron could not be read, see the logs".into(),
+        );
+        return;
+    }
+    match usable_config(&configs, &textures) {
+        Ok(config) => {
+            info!("HUD reloaded");
+            for entity in roots.iter() {
+                commands.entity(entity).despawn_recursive();
+            }
+            spawn_hud(&mut commands, &textures, &views, &mut images, config);
+        }
+        Err(error) => spawn_error(&mut commands, error),
+    }
+}
+
+fn spawn_error(commands: &mut Commands, error: String) {
+    warn!("invalid HUD config: {error}");
+    commands.spawn((
+        TextBundle::from_section(
+            format!("Invalid HUD config: {error}"),
+            TextStyle {
+                font_size: 20.0,
+                color: Color::rgb(1., 0.3, 0.3),
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(8.),
+            top: Val::Px(8.),
+            ..default()
+        })
+        .with_background_color(Color::rgba(0., 0., 0., 0.8)),
+        UI_LAYER,
+        HudError,
+        PlayingEntity,
+        Name::new("hud_error_node"),
+    ));
+}
+
+/// Sizes the render targets to their regions and spawns the HUD node tree.
+fn spawn_hud(
+    commands: &mut Commands,
+    textures: &TextureAssets,
+    views: &HUDRenderViews,
+    images: &mut Assets<Image>,
+    config: &UIConfig,
+) {
+    let dungeon_view = config.region("dungeon_view");
+    let minimap = config.region("minimap");
+    for (handle, region) in [
+        (&views.dungeon_handle, dungeon_view),
+        (&views.minimap_handle, minimap),
+    ] {
+        if let Some(image) = images.get_mut(handle) {
+            image.resize(Extent3d {
+                width: region.width as u32,
+                height: region.height as u32,
+                ..default()
+            });
+        }
+    }
+
     commands
         .spawn((
             NodeBundle {
                 style: Style {
                     height: Val::Vh(100.0),
-                    // flex_direction: FlexDirection::Column,
                     width: Val::Vw(100.0),
-                    // align_items: AlignItems::Center,
                     justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
                     ..default()
                 },
                 ..default()
             },
             Name::new("ui_root_node"),
             UI_LAYER,
+            HudRoot,
+            PlayingEntity,
         ))
-        // Add the HUD withing the UI node using Image Bundle
+        // Add the HUD withing the UI node using Image Bundle, sized by the layout plugin
         .with_children(|ui_root_node| {
             ui_root_node
                 .spawn((
                     ImageBundle {
-                        style: Style {
-                            min_height: Val::Percent(100.0),
-                            max_width: Val::Percent(100.0),
-                            aspect_ratio: Some(ASPECT_RATIO_4_3),
-                            // align_items: AlignItems::Center,
-                            // justify_content: JustifyContent::Center,
-                            ..default()
-                        },
                         image: UiImage::new(textures.hud.clone()),
                         ..default()
                     },
@@ -116,59 +256,43 @@ pub fn setup(
                     Name::new("hud_image_node"),
                 ))
                 .with_children(|hud_image_node| {
-                    // Add the dungeon view image
-                    let left = config.dongeon_view_margin.0 * 100. / config.size.0;
-                    let top =
-                        config.dongeon_view_margin.1 / ASPECT_RATIO_4_3 * 100. / config.size.1;
-
                     hud_image_node.spawn((
                         ImageBundle {
-                            style: Style {
-                                width: Val::Percent(
-                                    (config.dungeon_view_size.0 * 100.) / config.size.0,
-                                ),
-                                height: Val::Percent(
-                                    (config.dungeon_view_size.1 * 100.) / config.size.1,
-                                ),
-                                margin: UiRect::percent(left, 0., top, 0.),
-                                position_type: PositionType::Absolute,
-                                ..default()
-                            },
-                            image: UiImage::new(dungeon_handle.clone()),
+                            style: dungeon_view.style(config.size),
+                            image: UiImage::new(views.dungeon_handle.clone()),
                             ..default()
                         },
                         UI_LAYER,
                         DungeonViewImage,
                         Name::new("dungeon_view_node"),
                     ));
-                })
-                .with_children(|hud_image_node| {
-                    // Add the minimap image
-                    let left = (config.dongeon_view_margin.0
-                        + config.dungeon_view_size.0
-                        + config.minimap_margin.0)
-                        * 100.
-                        / config.size.0;
-                    let top = config.minimap_margin.1 / ASPECT_RATIO_4_3 * 100. / config.size.1;
-
                     hud_image_node.spawn((
                         ImageBundle {
-                            style: Style {
-                                width: Val::Percent((config.minimap_size.0 * 100.) / config.size.0),
-                                height: Val::Percent(
-                                    (config.minimap_size.1 * 100.) / config.size.1,
-                                ),
-                                margin: UiRect::percent(left, 0., top, 0.),
-                                position_type: PositionType::Absolute,
-                                ..default()
-                            },
-                            image: UiImage::new(minimap_handle.clone()),
+                            style: minimap.style(config.size),
+                            image: UiImage::new(views.minimap_handle.clone()),
                             ..default()
                         },
                         UI_LAYER,
                         MinimapImage,
                         Name::new("minimap_node"),
                     ));
+                    // The combat log sits over the bottom of the dungeon view
+                    spawn_combat_log(
+                        hud_image_node,
+                        config.region("combat_log").style(config.size),
+                    );
+                    spawn_party_panel(hud_image_node, config.region("party").style(config.size));
+                    spawn_inventory_panel(
+                        hud_image_node,
+                        config.region("inventory").style(config.size),
+                    );
+                    spawn_message_log(
+                        hud_image_node,
+                        config.region("message_log").style(config.size),
+                    );
+                    spawn_compass(hud_image_node, config.region("compass").style(config.size));
+                    // Toasts pop up over the top of the dungeon view
+                    spawn_toast_area(hud_image_node, dungeon_view.style(config.size));
                 });
         });
 }
@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+
+use super::camera2d::UI_LAYER;
+use super::inventory_panel::spawn_item_slot;
+use crate::{
+    combat::ActiveEncounter,
+    inventory::{item::EquipSlot, ItemLocation},
+    loading::GameData,
+    menu::ButtonColors,
+    party::{character::Attribute, Party, MAX_PARTY_SIZE},
+    GameState,
+};
+
+pub struct PartyPanelPlugin;
+
+impl Plugin for PartyPanelPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (update_party_panel, raise_attribute).run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+/// Parts of the party panel, along with the index of the member they show.
+#[derive(Component)]
+enum PartyWidget {
+    Slot(usize),
+    Name(usize),
+    Health(usize),
+    Mana(usize),
+    /// Level up choices, only shown while the member has points to spend
+    LevelUp(usize),
+}
+
+#[derive(Component)]
+pub struct RaiseAttribute {
+    pub member: usize,
+    pub attribute: Attribute,
+}
+
+const HEALTH_COLOR: Color = Color::rgb(0.75, 0.15, 0.15);
+const MANA_COLOR: Color = Color::rgb(0.2, 0.35, 0.8);
+
+/// Spawns one portrait per possible party member in the HUD, next to the dungeon view.
+pub fn spawn_party_panel(hud_image_node: &mut ChildBuilder, region: Style) {
+    let text_style = TextStyle {
+        font_size: 18.0,
+        color: Color::rgb(0.9, 0.9, 0.9),
+        ..default()
+    };
+
+    hud_image_node
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    flex_direction: FlexDirection::Column,
+                    row_gap: Val::Px(6.),
+                    ..region
+                },
+                ..default()
+            },
+            UI_LAYER,
+            Name::new("party_node"),
+        ))
+        .with_children(|party_node| {
+            for member in 0..MAX_PARTY_SIZE {
+                party_node
+                    .spawn((
+                        NodeBundle {
+                            style: Style {
+                                flex_grow: 1.,
+                                column_gap: Val::Px(6.),
+                                padding: UiRect::all(Val::Px(4.)),
+                                ..default()
+                            },
+                            background_color: Color::rgba(0., 0., 0., 0.6).into(),
+                            ..default()
+                        },
+                        UI_LAYER,
+                        PartyWidget::Slot(member),
+                    ))
+                    .with_children(|slot| {
+                        // No artwork yet, the portrait is a plain frame
+                        slot.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    height: Val::Percent(100.),
+                                    aspect_ratio: Some(1.),
+                                    ..default()
+                                },
+                                background_color: Color::rgb(0.3, 0.25, 0.2).into(),
+                                ..default()
+                            },
+                            UI_LAYER,
+                        ));
+                        slot.spawn((
+                            NodeBundle {
+                                style: Style {
+                                    flex_grow: 1.,
+                                    flex_direction: FlexDirection::Column,
+                                    row_gap: Val::Px(4.),
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            UI_LAYER,
+                        ))
+                        .with_children(|details| {
+                            details.spawn((
+                                TextBundle::from_section("", text_style.clone()),
+                                UI_LAYER,
+                                PartyWidget::Name(member),
+                            ));
+                            spawn_bar(details, HEALTH_COLOR, PartyWidget::Health(member));
+                            spawn_bar(details, MANA_COLOR, PartyWidget::Mana(member));
+                            details
+                                .spawn((
+                                    NodeBundle {
+                                        style: Style {
+                                            column_gap: Val::Px(4.),
+                                            ..default()
+                                        },
+                                        ..default()
+                                    },
+                                    UI_LAYER,
+                                ))
+                                .with_children(|equipment| {
+                                    for slot in EquipSlot::ALL {
+                                        spawn_item_slot(
+                                            equipment,
+                                            ItemLocation::Equipment { member, slot },
+                                            Val::Px(40.),
+                                        );
+                                    }
+                                });
+                            details
+                                .spawn((
+                                    NodeBundle {
+                                        style: Style {
+                                            column_gap: Val::Px(4.),
+                                            ..default()
+                                        },
+                                        ..default()
+                                    },
+                                    UI_LAYER,
+                                    PartyWidget::LevelUp(member),
+                                ))
+                                .with_children(|choices| {
+                                    for attribute in Attribute::ALL {
+                                        choices
+                                            .spawn((
+                                                ButtonBundle {
+                                                    style: Style {
+                                                        padding: UiRect::horizontal(Val::Px(4.)),
+                                                        ..default()
+                                                    },
+                                                    ..default()
+                                                },
+                                                UI_LAYER,
+                                                ButtonColors::default(),
+                                                RaiseAttribute { member, attribute },
+                                            ))
+                                            .with_children(|button| {
+                                                button.spawn((
+                                                    TextBundle::from_section(
+                                                        format!("+{}", attribute.label()),
+                                                        TextStyle {
+                                                            font_size: 14.0,
+                                                            ..text_style.clone()
+                                                        },
+                                                    ),
+                                                    UI_LAYER,
+                                                ));
+                                            });
+                                    }
+                                });
+                        });
+                    });
+            }
+        });
+}
+
+fn spawn_bar(parent: &mut ChildBuilder, color: Color, widget: PartyWidget) {
+    parent
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.),
+                    height: Val::Px(10.),
+                    ..default()
+                },
+                background_color: Color::rgb(0.1, 0.1, 0.1).into(),
+                ..default()
+            },
+            UI_LAYER,
+        ))
+        .with_children(|bar| {
+            bar.spawn((
+                NodeBundle {
+                    style: Style {
+                        height: Val::Percent(100.),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    ..default()
+                },
+                UI_LAYER,
+                widget,
+            ));
+        });
+}
+
+fn ratio(value: i32, max: i32) -> f32 {
+    (value.max(0) as f32 / max.max(1) as f32).min(1.)
+}
+
+/// Keeps the panel in sync with the party. During a fight the bars follow the encounter, since the
+/// party itself is only updated once the fight is over.
+fn update_party_panel(
+    party: Res<Party>,
+    encounter: Option<Res<ActiveEncounter>>,
+    mut widgets: Query<(&PartyWidget, &mut Style, Option<&mut Text>)>,
+) {
+    let encounter_changed = encounter.as_ref().map_or(false, |e| e.is_changed());
+    if !party.is_changed() && !encounter_changed {
+        return;
+    }
+    let stats = |member: usize| {
+        encounter
+            .as_ref()
+            .and_then(|combat| combat.encounter.combatants().get(member))
+            .map(|combatant| combatant.stats)
+            .or_else(|| party.members.get(member).map(|m| m.stats()))
+    };
+
+    for (widget, mut style, text) in widgets.iter_mut() {
+        match *widget {
+            PartyWidget::Slot(member) => {
+                style.display = if member < party.members.len() {
+                    Display::Flex
+                } else {
+                    Display::None
+                };
+            }
+            PartyWidget::Name(member) => {
+                if let (Some(character), Some(mut text)) = (party.members.get(member), text) {
+                    text.sections[0].value = format!(
+                        "{} - {} lv {}",
+                        character.name, character.class, character.level
+                    );
+                    if character.unspent_points > 0 {
+                        text.sections[0].value += &format!(" ({} pts)", character.unspent_points);
+                    }
+                }
+            }
+            PartyWidget::Health(member) => {
+                if let Some(stats) = stats(member) {
+                    style.width = Val::Percent(ratio(stats.hp, stats.max_hp) * 100.);
+                }
+            }
+            PartyWidget::Mana(member) => {
+                if let Some(stats) = stats(member) {
+                    style.width = Val::Percent(ratio(stats.mp, stats.max_mp) * 100.);
+                }
+            }
+            PartyWidget::LevelUp(member) => {
+                let pending = party
+                    .members
+                    .get(member)
+                    .map_or(false, |character| character.unspent_points > 0);
+                style.display = if pending {
+                    Display::Flex
+                } else {
+                    Display::None
+                };
+            }
+        }
+    }
+}
+
+fn raise_attribute(
+    mut party: ResMut<Party>,
+    data: GameData,
+    interaction_query: Query<(&Interaction, &RaiseAttribute), Changed<Interaction>>,
+) {
+    let (Some(table), Some(items)) = (data.classes(), data.items()) else {
+        return;
+    };
+    for (interaction, raise) in &interaction_query {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Some(character) = party.members.get_mut(raise.member) else {
+            continue;
+        };
+        if let Some(class) = table.class(&character.class) {
+            character.raise(raise.attribute, class, items);
+        }
+    }
+}